@@ -0,0 +1,101 @@
+//! Parsing SMP responses through the lookup client, with the network faked.
+//!
+//! Run with `cargo bench`. Each benchmark serves one fixture from memory, so
+//! the time is spent parsing: a small service group, a 340 KB one with 1000
+//! references, one with extensions, and service metadata with and without
+//! signature verification.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use peppol_lookup::lookup::BIS_BILLING_INVOICE;
+use peppol_lookup::{Certificate, LookupClient, LookupClientBuilder, Participant, PeppolError, Resolver, SmlRecord, SmpClient};
+
+const SERVICE_GROUP: &str = include_str!("../tests/fixtures/service_group.xml");
+const SERVICE_GROUP_LARGE: &str = include_str!("fixtures/service_group_large.xml");
+const SERVICE_METADATA: &str = include_str!("../tests/fixtures/service_metadata.xml");
+const SIGNED_SERVICE_METADATA: &str = include_str!("../tests/fixtures/signed_service_metadata.xml");
+const SMP_CA: &str = include_str!("../tests/fixtures/smp_ca.pem");
+
+/// Resolves every participant to `smp.example.com`
+struct FixedResolver;
+
+impl Resolver for FixedResolver {
+    fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError> {
+        Ok(Some(SmlRecord {
+            alias: alias.to_string(),
+            hostname: "smp.example.com".to_string(),
+            ttl: None,
+            addresses: Vec::new(),
+        }))
+    }
+}
+
+/// Serves `service_group` for the participant URL and `service_metadata` for anything below it
+struct FixtureSmp {
+    service_group: &'static str,
+    service_metadata: &'static str,
+}
+
+impl SmpClient for FixtureSmp {
+    fn get(&self, url: &str, _fallback: Option<&str>) -> Result<String, PeppolError> {
+        if url.contains("/services/") {
+            Ok(self.service_metadata.to_string())
+        } else {
+            Ok(self.service_group.to_string())
+        }
+    }
+}
+
+fn builder(service_group: &'static str, service_metadata: &'static str) -> LookupClientBuilder {
+    LookupClient::builder()
+        .resolver(FixedResolver)
+        .smp_client(FixtureSmp { service_group, service_metadata })
+        .verify_signature(false)
+}
+
+fn fixture_client(service_group: &'static str, max_references: Option<usize>) -> LookupClient {
+    let mut builder = builder(service_group, SERVICE_METADATA);
+    if let Some(max) = max_references {
+        builder = builder.max_references(max);
+    }
+    builder.build().unwrap()
+}
+
+fn service_group(c: &mut Criterion) {
+    let participant: Participant = "0192:921605900".parse().unwrap();
+    let mut group = c.benchmark_group("service_group");
+
+    for (name, fixture) in [("small", SERVICE_GROUP), ("large", SERVICE_GROUP_LARGE)] {
+        let client = fixture_client(fixture, None);
+        group.throughput(Throughput::Bytes(fixture.len() as u64));
+        group.bench_function(name, |b| b.iter(|| client.smp_lookup(&participant).unwrap()));
+    }
+
+    let client = fixture_client(SERVICE_GROUP_LARGE, Some(10));
+    group.throughput(Throughput::Bytes(SERVICE_GROUP_LARGE.len() as u64));
+    group.bench_function("large_max_references_10", |b| b.iter(|| client.smp_lookup(&participant).unwrap()));
+
+    let client = fixture_client(SERVICE_GROUP, None);
+    group.throughput(Throughput::Bytes(SERVICE_GROUP.len() as u64));
+    group.bench_function("with_extensions", |b| b.iter(|| client.service_group(&participant).unwrap()));
+    group.finish();
+}
+
+fn service_metadata(c: &mut Criterion) {
+    let participant: Participant = "0192:921605900".parse().unwrap();
+    let client = fixture_client(SERVICE_GROUP, None);
+    let mut group = c.benchmark_group("service_metadata");
+    group.throughput(Throughput::Bytes(SERVICE_METADATA.len() as u64));
+    group.bench_function("endpoint", |b| b.iter(|| client.get_endpoint(&participant, BIS_BILLING_INVOICE).unwrap()));
+
+    let client = builder(SERVICE_GROUP, SIGNED_SERVICE_METADATA)
+        .verify_signature(true)
+        .root_certificates([Certificate::from_pem(SMP_CA).unwrap()])
+        .build()
+        .unwrap();
+    group.throughput(Throughput::Bytes(SIGNED_SERVICE_METADATA.len() as u64));
+    group.bench_function("endpoint_verified", |b| b.iter(|| client.get_endpoint(&participant, BIS_BILLING_INVOICE).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, service_group, service_metadata);
+criterion_main!(benches);
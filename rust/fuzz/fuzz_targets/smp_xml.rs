@@ -0,0 +1,9 @@
+//! Every SMP response parser must return an error, never panic or hang, on malformed input.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    peppol_lookup::fuzz::parse_smp_response(data);
+});
@@ -0,0 +1,154 @@
+//! Looking up many participants at once.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+
+use crate::client::LookupClient;
+use crate::document::DocumentTypeIdentifier;
+use crate::environment::SmlEnvironment;
+use crate::error::PeppolError;
+use crate::participant::Participant;
+
+/// Default number of participants looked up at the same time
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 16;
+
+/// The result of a batch: each participant with its document types or error
+pub type BatchResults = Vec<(Participant, Result<Vec<DocumentTypeIdentifier>, PeppolError>)>;
+
+/// Batch results yielded one at a time, in the order the lookups complete
+///
+/// Returned by [`lookup_many_unordered`]. Each call to `next` blocks until a
+/// worker finishes a participant, and the iterator ends once every
+/// participant has been looked up. Dropping it early stops the workers after
+/// their current lookup.
+pub struct BatchIter {
+    receiver: mpsc::Receiver<(Participant, Result<Vec<DocumentTypeIdentifier>, PeppolError>)>,
+}
+
+impl Iterator for BatchIter {
+    type Item = (Participant, Result<Vec<DocumentTypeIdentifier>, PeppolError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Resolve the document types of many participants concurrently
+///
+/// Runs the SML and SMP steps for each participant on up to `concurrency`
+/// worker threads ([`DEFAULT_BATCH_CONCURRENCY`] is a reasonable starting
+/// point). Results are returned in the same order as `participants`,
+/// and a failure for one participant doesn't affect the others.
+///
+/// All workers share one [`LookupClient`], so participants hosted by the same
+/// SMP reuse its kept-alive connections; see [`LookupClient::lookup_many`].
+/// The only error is failing to build that client, before any lookup starts.
+pub fn lookup_many(participants: &[Participant], environment: SmlEnvironment, concurrency: usize) -> Result<BatchResults, PeppolError> {
+    let client = LookupClient::builder().environment(environment).build()?;
+    Ok(client.lookup_many(participants, concurrency))
+}
+
+/// Like [`lookup_many`], but yields each result as soon as it is ready
+///
+/// Meant for batches too large to hold in memory at once: `participants` is
+/// consumed lazily by the workers, so it can itself be a stream (e.g. lines
+/// read from a file), and nothing is collected before the first result is
+/// yielded. Results arrive in completion order, not input order. As with
+/// [`lookup_many`], the only error is failing to build the shared client.
+pub fn lookup_many_unordered<I>(participants: I, environment: SmlEnvironment, concurrency: usize) -> Result<BatchIter, PeppolError>
+where
+    I: IntoIterator<Item = Participant>,
+    I::IntoIter: Send + 'static,
+{
+    let client = LookupClient::builder().environment(environment).build()?;
+    Ok(Arc::new(client).lookup_many_unordered(participants, concurrency))
+}
+
+impl LookupClient {
+    /// Like [`lookup_many_unordered`], using this client's resolver, SMP client and cache
+    ///
+    /// The workers outlive this call, so they share the client through an [`Arc`].
+    pub fn lookup_many_unordered<I>(self: Arc<Self>, participants: I, concurrency: usize) -> BatchIter
+    where
+        I: IntoIterator<Item = Participant>,
+        I::IntoIter: Send + 'static,
+    {
+        spawn(participants, concurrency, move |participant| self.smp_lookup(participant))
+    }
+
+    /// Like [`lookup_many`], using this client's resolver, SMP client and cache
+    ///
+    /// The SMP client's connection pool is shared by all workers: a batch of
+    /// participants on one SMP opens about `concurrency` connections rather
+    /// than one per participant.
+    pub fn lookup_many(&self, participants: &[Participant], concurrency: usize) -> BatchResults {
+        run(participants, concurrency, |participant| self.smp_lookup(participant))
+    }
+}
+
+/// Apply `lookup` to every participant on up to `concurrency` worker threads
+fn run<F>(participants: &[Participant], concurrency: usize, lookup: F) -> BatchResults
+where
+    F: Fn(&Participant) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, participants.len().max(1)) {
+            let sender = sender.clone();
+            let (next, lookup) = (&next, &lookup);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(participant) = participants.get(index) else {
+                        break;
+                    };
+                    // The receiver outlives the scope, so sending can't fail
+                    let _ = sender.send((index, lookup(participant)));
+                }
+            });
+        }
+    });
+    drop(sender);
+
+    let mut results: Vec<_> = receiver.into_iter().collect();
+    results.sort_by_key(|(index, _)| *index);
+    participants
+        .iter()
+        .cloned()
+        .zip(results.into_iter().map(|(_, result)| result))
+        .collect()
+}
+
+/// Apply `lookup` to every participant on `concurrency` detached worker
+/// threads, sending each result as soon as it is ready
+fn spawn<I, F>(participants: I, concurrency: usize, lookup: F) -> BatchIter
+where
+    I: IntoIterator<Item = Participant>,
+    I::IntoIter: Send + 'static,
+    F: Fn(&Participant) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> + Send + Sync + 'static,
+{
+    let participants = Arc::new(Mutex::new(participants.into_iter()));
+    let lookup = Arc::new(lookup);
+    let (sender, receiver) = mpsc::channel();
+
+    for _ in 0..concurrency.max(1) {
+        let (participants, lookup, sender) = (participants.clone(), lookup.clone(), sender.clone());
+        thread::spawn(move || loop {
+            // A panicking input iterator poisons the lock; the others keep draining it
+            let next = participants.lock().unwrap_or_else(PoisonError::into_inner).next();
+            let Some(participant) = next else {
+                break;
+            };
+            let result = lookup(&participant);
+            // The iterator was dropped: nobody wants the remaining results
+            if sender.send((participant, result)).is_err() {
+                break;
+            }
+        });
+    }
+
+    BatchIter { receiver }
+}
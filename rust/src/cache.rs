@@ -0,0 +1,245 @@
+//! In-memory caching of SML and SMP lookups.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::config::LookupConfig;
+#[cfg(feature = "blocking")]
+use crate::document::DocumentTypeIdentifier;
+use crate::environment::SmlEnvironment;
+use crate::error::PeppolError;
+use crate::lookup::sml_lookup_with_config;
+#[cfg(feature = "blocking")]
+use crate::lookup::service_group_with_config;
+use crate::participant::Participant;
+use crate::sml::SmlRecord;
+
+/// Default time an entry stays cached
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Memoized SML records and SMP document lists, keyed by participant
+///
+/// Entries expire after the lifetime the network gave them: the DNS TTL of an
+/// SML record, and the `Cache-Control` or `Expires` header of the SMP's
+/// service group. The configured TTL caps those lifetimes and applies to
+/// answers that carried none. Responses marked `no-store` or `no-cache` are
+/// not cached. Expired entries are evicted lazily when they are next accessed.
+///
+/// Entries can also be dropped early with [`invalidate`](Self::invalidate),
+/// e.g. when a partner is known to have just changed their registration, or
+/// all at once with [`clear`](Self::clear).
+#[derive(Debug)]
+pub struct LookupCache {
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    sml: Mutex<HashMap<Participant, Entry<SmlRecord>>>,
+    #[cfg(feature = "blocking")]
+    smp: Mutex<HashMap<Participant, Entry<Vec<DocumentTypeIdentifier>>>>,
+}
+
+/// How a [`LookupCache`] has been doing, from [`LookupCache::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups the cache had no live entry for
+    pub misses: u64,
+    /// Entries currently cached and not yet expired, SML records and
+    /// document lists counted separately
+    pub size: usize,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    value: T,
+    expires_at: Instant,
+}
+
+impl LookupCache {
+    /// Create an empty cache whose entries live for at most `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        LookupCache {
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            sml: Mutex::new(HashMap::new()),
+            #[cfg(feature = "blocking")]
+            smp: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The maximum time an entry stays cached, and the lifetime of entries
+    /// whose answer carried none
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Drop the cached entries of `participant`, so its next lookup goes to the network
+    ///
+    /// Returns whether anything was cached for it.
+    pub fn invalidate(&self, participant: &Participant) -> bool {
+        let sml = lock(&self.sml).remove(participant).is_some();
+        #[cfg(feature = "blocking")]
+        let smp = lock(&self.smp).remove(participant).is_some();
+        #[cfg(not(feature = "blocking"))]
+        let smp = false;
+        sml || smp
+    }
+
+    /// Drop all cached entries
+    ///
+    /// The hit and miss counts of [`stats`](Self::stats) are kept.
+    pub fn clear(&self) {
+        lock(&self.sml).clear();
+        #[cfg(feature = "blocking")]
+        lock(&self.smp).clear();
+    }
+
+    /// Hits, misses and the number of live entries, for monitoring
+    pub fn stats(&self) -> CacheStats {
+        let size = live_entries(&self.sml);
+        #[cfg(feature = "blocking")]
+        let size = size + live_entries(&self.smp);
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size,
+        }
+    }
+
+    pub(crate) fn sml_record(&self, participant: &Participant) -> Option<SmlRecord> {
+        self.count(get(&self.sml, participant))
+    }
+
+    pub(crate) fn insert_sml_record(&self, participant: &Participant, record: SmlRecord) {
+        let ttl = self.lifetime(record.ttl);
+        insert(&self.sml, participant, record, ttl);
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn document_types(&self, participant: &Participant) -> Option<Vec<DocumentTypeIdentifier>> {
+        self.count(get(&self.smp, participant))
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn insert_document_types(&self, participant: &Participant, document_types: Vec<DocumentTypeIdentifier>, max_age: Option<Duration>) {
+        insert(&self.smp, participant, document_types, self.lifetime(max_age));
+    }
+
+    fn count<T>(&self, value: Option<T>) -> Option<T> {
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// The network's lifetime for an entry, capped at the configured TTL
+    fn lifetime(&self, network: Option<Duration>) -> Duration {
+        network.map_or(self.ttl, |network| network.min(self.ttl))
+    }
+}
+
+impl Default for LookupCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_TTL)
+    }
+}
+
+fn lock<K, T>(map: &Mutex<HashMap<K, Entry<T>>>) -> MutexGuard<'_, HashMap<K, Entry<T>>> {
+    map.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn live_entries<K, T>(map: &Mutex<HashMap<K, Entry<T>>>) -> usize {
+    let now = Instant::now();
+    lock(map).values().filter(|entry| entry.expires_at > now).count()
+}
+
+fn get<K: Eq + Hash, T: Clone>(map: &Mutex<HashMap<K, Entry<T>>>, key: &K) -> Option<T> {
+    let mut map = lock(map);
+    match map.get(key) {
+        Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+        Some(_) => {
+            map.remove(key);
+            None
+        }
+        None => None,
+    }
+}
+
+fn insert<K: Eq + Hash + Clone, T>(map: &Mutex<HashMap<K, Entry<T>>>, key: &K, value: T, ttl: Duration) {
+    if ttl.is_zero() {
+        return;
+    }
+    let entry = Entry { value, expires_at: Instant::now() + ttl };
+    lock(map).insert(key.clone(), entry);
+}
+
+/// Lookups that go through a [`LookupCache`]
+///
+/// Construct it once and share it across threads (e.g. in an `Arc`); repeated
+/// lookups of the same participant are then answered from memory until their
+/// entries expire. Failed lookups are not cached.
+#[derive(Debug)]
+pub struct CachedLookup {
+    environment: SmlEnvironment,
+    config: LookupConfig,
+    cache: LookupCache,
+}
+
+impl CachedLookup {
+    /// Cached lookups in `environment`, with a default configuration and cache
+    pub fn new(environment: SmlEnvironment) -> Self {
+        Self::with_cache(environment.clone(), LookupConfig::for_environment(environment), LookupCache::default())
+    }
+
+    /// Cached lookups using the given configuration and cache
+    pub fn with_cache(environment: SmlEnvironment, config: LookupConfig, cache: LookupCache) -> Self {
+        CachedLookup { environment, config, cache }
+    }
+
+    /// The underlying cache
+    pub fn cache(&self) -> &LookupCache {
+        &self.cache
+    }
+
+    /// Drop the cached entries of `participant`; see [`LookupCache::invalidate`]
+    pub fn invalidate(&self, participant: &Participant) -> bool {
+        self.cache.invalidate(participant)
+    }
+
+    /// Drop all cached entries; see [`LookupCache::clear`]
+    pub fn clear(&self) {
+        self.cache.clear()
+    }
+
+    /// The cache's hits, misses and size; see [`LookupCache::stats`]
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Cached [`sml_lookup`](crate::lookup::sml_lookup)
+    pub fn sml_lookup(&self, participant: &Participant) -> Result<SmlRecord, PeppolError> {
+        if let Some(record) = self.cache.sml_record(participant) {
+            return Ok(record);
+        }
+        let record = sml_lookup_with_config(participant, self.environment.clone(), &self.config)?;
+        self.cache.insert_sml_record(participant, record.clone());
+        Ok(record)
+    }
+
+    /// Cached SML and SMP lookup, returning the participant's document types
+    #[cfg(feature = "blocking")]
+    pub fn document_types(&self, participant: &Participant) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> {
+        if let Some(document_types) = self.cache.document_types(participant) {
+            return Ok(document_types);
+        }
+        let record = self.sml_lookup(participant)?;
+        let service_group = service_group_with_config(&record.hostname, participant, &self.config)?;
+        self.cache.insert_document_types(participant, service_group.document_types.clone(), service_group.max_age);
+        Ok(service_group.document_types)
+    }
+}
@@ -0,0 +1,204 @@
+//! Access point certificates published in SMP endpoint metadata.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use time::{Duration, OffsetDateTime};
+use x509_parser::oid_registry::{OID_X509_COMMON_NAME, OID_X509_SERIALNUMBER};
+use x509_parser::parse_x509_certificate;
+
+use crate::error::PeppolError;
+
+/// How close to expiry a certificate is reported as [`CertificateStatus::ExpiringSoon`] by default
+pub const DEFAULT_EXPIRY_WARNING: Duration = Duration::days(30);
+
+/// Whether a certificate is still usable, from its `notAfter` date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CertificateStatus {
+    /// Valid for longer than the warning period
+    Valid,
+    /// Still valid, but expires within the warning period
+    ExpiringSoon {
+        /// Whole days left until the certificate expires
+        days: i64,
+    },
+    /// Past its `notAfter` date
+    Expired,
+}
+
+/// X.509 certificate of a receiving access point
+///
+/// Senders encrypt to (and verify signatures from) this certificate. The
+/// commonly needed fields are parsed up front so callers can check for
+/// certificates that are about to expire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    der: Vec<u8>,
+    /// Subject distinguished name, e.g. `CN=PNO000104, O=..., C=NO`
+    pub subject: String,
+    /// Issuer distinguished name
+    pub issuer: String,
+    /// End of the certificate's validity period
+    pub not_after: OffsetDateTime,
+}
+
+impl Certificate {
+    /// Parse a DER-encoded certificate
+    pub fn from_der(der: Vec<u8>) -> Result<Self, PeppolError> {
+        let (_, parsed) = parse_x509_certificate(&der)
+            .map_err(|err| PeppolError::Certificate(err.to_string()))?;
+        let subject = parsed.subject().to_string();
+        let issuer = parsed.issuer().to_string();
+        let not_after = parsed.validity().not_after.to_datetime();
+
+        Ok(Certificate { der, subject, issuer, not_after })
+    }
+
+    /// Parse the base64 content of an SMP `Certificate` element
+    ///
+    /// Whitespace and line breaks inside the element are ignored.
+    pub fn from_base64(encoded: &str) -> Result<Self, PeppolError> {
+        let cleaned: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+        let der = STANDARD
+            .decode(cleaned)
+            .map_err(|err| PeppolError::Certificate(err.to_string()))?;
+        Self::from_der(der)
+    }
+
+    /// Parse a PEM-encoded certificate
+    ///
+    /// Only the first `CERTIFICATE` block is read; use [`Certificate::all_from_pem`]
+    /// for bundles.
+    pub fn from_pem(pem: &str) -> Result<Self, PeppolError> {
+        Self::all_from_pem(pem)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| PeppolError::Certificate("no PEM certificate found".to_string()))
+    }
+
+    /// Parse every `CERTIFICATE` block in a PEM bundle
+    pub fn all_from_pem(pem: &str) -> Result<Vec<Self>, PeppolError> {
+        let mut certificates = Vec::new();
+        let mut rest = pem;
+        while let Some(start) = rest.find("-----BEGIN CERTIFICATE-----") {
+            let body = &rest[start + "-----BEGIN CERTIFICATE-----".len()..];
+            let end = body
+                .find("-----END CERTIFICATE-----")
+                .ok_or_else(|| PeppolError::Certificate("unterminated PEM certificate".to_string()))?;
+            certificates.push(Self::from_base64(&body[..end])?);
+            rest = &body[end..];
+        }
+        Ok(certificates)
+    }
+
+    /// Whether `uid` identifies this certificate's subject
+    ///
+    /// SMP redirects name the target's certificate by a `CertificateUID`. It
+    /// matches the full subject DN, whatever the order of its attributes and
+    /// the whitespace around them (`C=NO, CN=SMP` matches `CN=SMP,C=NO`), or
+    /// the subject's `CN` or `serialNumber` on its own. Other attributes,
+    /// such as `O` or `C`, are shared by many certificates and never match
+    /// alone.
+    pub fn matches_uid(&self, uid: &str) -> bool {
+        let uid = uid.trim();
+        if uid.is_empty() {
+            return false;
+        }
+        if normalize_dn(uid).is_some_and(|dn| Some(dn) == normalize_dn(&self.subject)) {
+            return true;
+        }
+        match parse_x509_certificate(&self.der) {
+            Ok((_, parsed)) => {
+                let subject = parsed.subject();
+                let mut identifiers = subject.iter_by_oid(&OID_X509_COMMON_NAME).chain(subject.iter_by_oid(&OID_X509_SERIALNUMBER));
+                identifiers.any(|attribute| attribute.as_str().is_ok_and(|value| value.trim() == uid))
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// The certificate's status at `at`
+    ///
+    /// A certificate expiring within `warn_within` of `at` is
+    /// [`CertificateStatus::ExpiringSoon`]; one whose `not_after` is at or
+    /// before `at` is [`CertificateStatus::Expired`].
+    pub fn status(&self, at: OffsetDateTime, warn_within: Duration) -> CertificateStatus {
+        let remaining = self.not_after - at;
+        if remaining <= Duration::ZERO {
+            CertificateStatus::Expired
+        } else if remaining <= warn_within {
+            CertificateStatus::ExpiringSoon { days: remaining.whole_days() }
+        } else {
+            CertificateStatus::Valid
+        }
+    }
+
+    /// The raw DER-encoded certificate bytes
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The certificate encoded as PEM
+    pub fn to_pem(&self) -> String {
+        let encoded = STANDARD.encode(&self.der);
+        let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+        // PEM bodies are wrapped at 64 characters; base64 output is ASCII
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+            pem.push('\n');
+        }
+        pem.push_str("-----END CERTIFICATE-----\n");
+        pem
+    }
+}
+
+/// The attributes of a distinguished name such as `CN=SMP, O=Example, C=NO`, sorted
+///
+/// Attribute types are uppercased and whitespace around types and values is
+/// dropped, so the same name written differently compares equal. `None` if
+/// `dn` isn't a list of `type=value` pairs.
+fn normalize_dn(dn: &str) -> Option<Vec<(String, String)>> {
+    let mut attributes = Vec::new();
+    let mut attribute = String::new();
+    let mut chars = dn.chars();
+    // Separators are `,` between RDNs and `+` within one; `\` escapes them
+    loop {
+        let next = chars.next();
+        match next {
+            Some('\\') => {
+                attribute.push('\\');
+                attribute.extend(chars.next());
+            }
+            Some(',' | '+') | None => {
+                let (name, value) = attribute.split_once('=')?;
+                let (name, value) = (name.trim(), value.trim());
+                if name.is_empty() || value.is_empty() {
+                    return None;
+                }
+                attributes.push((name.to_ascii_uppercase(), value.to_string()));
+                attribute.clear();
+                if next.is_none() {
+                    break;
+                }
+            }
+            Some(c) => attribute.push(c),
+        }
+    }
+    attributes.sort();
+    Some(attributes)
+}
+
+/// Serialized as the base64 DER encoding, as in SMP metadata
+#[cfg(feature = "serde")]
+impl serde::Serialize for Certificate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.der))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Certificate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Certificate::from_base64(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,448 @@
+//! A reusable lookup client.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::cache::LookupCache;
+use crate::certificate::Certificate;
+use crate::config::{LookupConfig, RetryPolicy, SmpScheme};
+use crate::document::{DocumentType, DocumentTypeIdentifier};
+use crate::endpoint::Endpoint;
+use crate::environment::SmlEnvironment;
+use crate::error::PeppolError;
+use crate::explanation::Explanation;
+use crate::extension::BusinessCard;
+use crate::lookup::{
+    business_card_with_client, document_types_with_client, explanation, find_document_type_with_client, get_endpoint_with_client, get_endpoints_with_client, get_redirect_with_client, lookup_all_metadata_with_client,
+    service_group_with_client,
+    sml_lookup_with_resolver, smp_lookup_raw_with_client,
+};
+use crate::metadata::ParticipantMetadata;
+use crate::participant::Participant;
+use crate::redirect::Redirect;
+use crate::resolver::{DnsResolver, Resolver};
+use crate::service_group::ServiceGroup;
+use crate::signature::TrustStore;
+use crate::sml::SmlRecord;
+use crate::smp_client::{HttpSmpClient, SmpClient};
+
+/// A lookup client holding its configuration and a reused HTTP client
+///
+/// The free functions in [`lookup`](crate::lookup) build a new HTTP client
+/// for every call. A `LookupClient` is built once with
+/// [`LookupClient::builder`] and keeps its connection pool between lookups,
+/// so repeated queries to the same SMP skip the connection and TLS setup.
+///
+/// Each method performs the SML step for the configured environment itself.
+/// The DNS and HTTP steps go through a [`Resolver`] and an [`SmpClient`],
+/// which can be replaced with fakes to test without a network.
+/// If the client was built with a cache, SML records and document lists are
+/// answered from it until they expire.
+pub struct LookupClient {
+    environment: SmlEnvironment,
+    config: LookupConfig,
+    resolver: Box<dyn Resolver>,
+    smp: Box<dyn SmpClient>,
+    cache: Option<LookupCache>,
+}
+
+impl LookupClient {
+    /// Start building a client, defaulting to the production SML
+    pub fn builder() -> LookupClientBuilder {
+        LookupClientBuilder::default()
+    }
+
+    /// The SML environment this client queries
+    pub fn environment(&self) -> &SmlEnvironment {
+        &self.environment
+    }
+
+    /// The configuration used for every lookup
+    pub fn config(&self) -> &LookupConfig {
+        &self.config
+    }
+
+    /// The cache, if the client was built with one
+    pub fn cache(&self) -> Option<&LookupCache> {
+        self.cache.as_ref()
+    }
+
+    /// Step 1: Find the participant's SMP in the SML
+    pub fn sml_lookup(&self, participant: &Participant) -> Result<SmlRecord, PeppolError> {
+        if let Some(record) = self.cache.as_ref().and_then(|cache| cache.sml_record(participant)) {
+            return Ok(record);
+        }
+        let record = sml_lookup_with_resolver(self.resolver.as_ref(), participant, &self.environment)?;
+        if let Some(cache) = &self.cache {
+            cache.insert_sml_record(participant, record.clone());
+        }
+        Ok(record)
+    }
+
+    /// The base URL of the participant's SMP, for building other SMP requests
+    ///
+    /// See [`SmlRecord::smp_base_url`]; uses this client's SMP scheme.
+    pub fn smp_base_url(&self, participant: &Participant) -> Result<String, PeppolError> {
+        Ok(self.sml_lookup(participant)?.smp_base_url(self.config.smp_scheme))
+    }
+
+    /// Describe what a lookup of `participant` would request, without contacting the SMP
+    ///
+    /// Does the SML step through this client's resolver (and cache); see
+    /// [`explain_with_dns`](crate::lookup::explain_with_dns).
+    pub fn explain(&self, participant: &Participant, document_types: &[&str]) -> Result<Explanation, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        Ok(explanation(participant, &self.environment, Some(&record.hostname), document_types, &self.config))
+    }
+
+    /// Step 2: Find the participant's SMP and list the document types it accepts
+    pub fn smp_lookup(&self, participant: &Participant) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> {
+        if let Some(document_types) = self.cache.as_ref().and_then(|cache| cache.document_types(participant)) {
+            return Ok(document_types);
+        }
+        let record = self.sml_lookup(participant)?;
+        let service_group = service_group_with_client(self.smp.as_ref(), &record.hostname, participant, &self.config)?;
+        if let Some(cache) = &self.cache {
+            cache.insert_document_types(participant, service_group.document_types.clone(), service_group.max_age);
+        }
+        Ok(service_group.document_types)
+    }
+
+    /// Like [`smp_lookup`](Self::smp_lookup), also returning the service group XML as sent
+    ///
+    /// See [`smp_lookup_raw`](crate::lookup::smp_lookup_raw). These results are not cached.
+    pub fn smp_lookup_raw(&self, participant: &Participant) -> Result<(Vec<DocumentTypeIdentifier>, String), PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        smp_lookup_raw_with_client(self.smp.as_ref(), &record.hostname, participant, &self.config)
+    }
+
+    /// Like [`smp_lookup`](Self::smp_lookup), also reporting the SMP software
+    ///
+    /// See [`service_group`](crate::lookup::service_group). These results are not cached.
+    pub fn service_group(&self, participant: &Participant) -> Result<ServiceGroup, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        service_group_with_client(self.smp.as_ref(), &record.hostname, participant, &self.config)
+    }
+
+    /// Like [`smp_lookup`](Self::smp_lookup), including the processes of each document type
+    ///
+    /// See [`document_types`](crate::lookup::document_types). Signatures are
+    /// verified if the configuration asks for it. These results are not cached.
+    pub fn document_types(&self, participant: &Participant) -> Result<Vec<DocumentType>, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        document_types_with_client(self.smp.as_ref(), &record.hostname, participant, &self.config)
+    }
+
+    /// The first document type of the participant that `predicate` accepts
+    ///
+    /// See [`find_document_type`](crate::lookup::find_document_type). Signatures
+    /// are verified if the configuration asks for it.
+    pub fn find_document_type(
+        &self,
+        participant: &Participant,
+        mut predicate: impl FnMut(&DocumentType) -> bool,
+    ) -> Result<Option<DocumentType>, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        find_document_type_with_client(self.smp.as_ref(), &record.hostname, participant, &self.config, &mut predicate)
+    }
+
+    /// The participant's SMP and the full metadata of every document type
+    ///
+    /// See [`lookup_all_metadata`](crate::lookup::lookup_all_metadata).
+    /// Signatures are verified if the configuration asks for it.
+    pub fn lookup_all_metadata(&self, participant: &Participant) -> Result<ParticipantMetadata, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        lookup_all_metadata_with_client(self.smp.as_ref(), record, participant, &self.config)
+    }
+
+    /// Step 3: Fetch the endpoint the participant uses to receive `doc_type`
+    ///
+    /// See [`get_endpoint`](crate::lookup::get_endpoint). The signature is
+    /// verified if the configuration asks for it.
+    pub fn get_endpoint(&self, participant: &Participant, doc_type: &str) -> Result<Endpoint, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        get_endpoint_with_client(self.smp.as_ref(), &record.hostname, participant, doc_type, &self.config)
+    }
+
+    /// Every endpoint the participant publishes for `doc_type`
+    ///
+    /// See [`get_endpoints`](crate::lookup::get_endpoints). The signature is
+    /// verified if the configuration asks for it.
+    pub fn get_endpoints(&self, participant: &Participant, doc_type: &str) -> Result<Vec<Endpoint>, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        get_endpoints_with_client(self.smp.as_ref(), &record.hostname, participant, doc_type, &self.config)
+    }
+
+    /// The participant's business card from their SMP, if it publishes one
+    ///
+    /// See [`business_card`](crate::lookup::business_card).
+    pub fn business_card(&self, participant: &Participant) -> Result<Option<BusinessCard>, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        business_card_with_client(self.smp.as_ref(), &record.hostname, participant, &self.config)
+    }
+
+    /// The redirect the participant's SMP answers with for `doc_type`, if any
+    ///
+    /// See [`get_redirect`](crate::lookup::get_redirect).
+    pub fn get_redirect(&self, participant: &Participant, doc_type: &str) -> Result<Option<Redirect>, PeppolError> {
+        let record = self.sml_lookup(participant)?;
+        get_redirect_with_client(self.smp.as_ref(), &record.hostname, participant, doc_type, &self.config)
+    }
+}
+
+impl fmt::Debug for LookupClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LookupClient")
+            .field("environment", &self.environment)
+            .field("config", &self.config)
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for a [`LookupClient`]
+///
+/// Holds the [`LookupConfig`] the client will use, starting from
+/// [`LookupConfig::for_environment`] for the production SML. Each setter
+/// changes the matching field.
+#[derive(Default)]
+pub struct LookupClientBuilder {
+    environment: SmlEnvironment,
+    config: LookupConfig,
+    resolver: Option<Box<dyn Resolver>>,
+    smp_client: Option<Box<dyn SmpClient>>,
+    cache_ttl: Option<Duration>,
+}
+
+impl LookupClientBuilder {
+    /// Which SML to query
+    ///
+    /// Also resets the settings whose default depends on the environment,
+    /// [`verify_signature`](LookupConfig::verify_signature),
+    /// [`smp_scheme`](LookupConfig::smp_scheme) and
+    /// [`block_private_addresses`](LookupConfig::block_private_addresses), so
+    /// call this first.
+    pub fn environment(mut self, environment: SmlEnvironment) -> Self {
+        let defaults = LookupConfig::for_environment(environment.clone());
+        self.config.verify_signature = defaults.verify_signature;
+        self.config.smp_scheme = defaults.smp_scheme;
+        self.config.block_private_addresses = defaults.block_private_addresses;
+        self.environment = environment;
+        self
+    }
+
+    /// Resolve SML hostnames with `resolver` instead of DNS
+    ///
+    /// Mainly useful for tests, together with [`smp_client`](Self::smp_client).
+    pub fn resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Fetch SMP documents with `smp_client` instead of over HTTP
+    ///
+    /// The HTTP settings (timeouts, TLS, retries) only apply to the default
+    /// [`HttpSmpClient`].
+    pub fn smp_client(mut self, smp_client: impl SmpClient + 'static) -> Self {
+        self.smp_client = Some(Box::new(smp_client));
+        self
+    }
+
+    /// Use `config` as the configuration, replacing every setting made so far
+    pub fn config(mut self, config: LookupConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Whether to verify signatures on `SignedServiceMetadata` responses
+    pub fn verify_signature(mut self, verify_signature: bool) -> Self {
+        self.config.verify_signature = verify_signature;
+        self
+    }
+
+    /// CA certificates trusted to issue SMP signing certificates
+    pub fn trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.config.trust_store = trust_store;
+        self
+    }
+
+    /// Extra root certificates for SMP TLS and signatures; see [`LookupConfig::root_certificates`]
+    pub fn root_certificates<I>(mut self, certificates: I) -> Self
+    where
+        I: IntoIterator<Item = Certificate>,
+    {
+        self.config.root_certificates = certificates.into_iter().collect();
+        self
+    }
+
+    /// DNS servers to query for the SML step; see [`LookupConfig::dns_servers`]
+    pub fn dns_servers<I>(mut self, servers: I) -> Self
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        self.config.dns_servers = servers.into_iter().collect();
+        self
+    }
+
+    /// How long to wait for a TCP connection to the SMP
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    /// Total time allowed for each SMP request
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = timeout;
+        self
+    }
+
+    /// How long to wait for each DNS query
+    pub fn dns_timeout(mut self, timeout: Duration) -> Self {
+        self.config.dns_timeout = timeout;
+        self
+    }
+
+    /// Resolve SML hostnames through the DNS-over-HTTPS resolver at `url`
+    ///
+    /// See [`LookupConfig::dns_over_https`].
+    pub fn dns_over_https(mut self, url: impl Into<String>) -> Self {
+        self.config.dns_over_https = Some(url.into());
+        self
+    }
+
+    /// Which scheme to use when connecting to SMPs
+    pub fn smp_scheme(mut self, smp_scheme: SmpScheme) -> Self {
+        self.config.smp_scheme = smp_scheme;
+        self
+    }
+
+    /// Accept invalid TLS certificates from SMPs; only for debugging test SMPs
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.config.danger_accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// How SMP requests are retried after transient failures
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.config.retry = retry;
+        self
+    }
+
+    /// Idle connections kept open per SMP host; see [`LookupConfig::pool_max_idle_per_host`]
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.config.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// How long idle connections are kept, `None` for no limit; see [`LookupConfig::pool_idle_timeout`]
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Upper bound on SMP requests in flight at once; see [`LookupConfig::max_concurrent_requests`]
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.config.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// Upper bound on requests per second to each SMP host; see [`LookupConfig::smp_requests_per_second`]
+    pub fn smp_requests_per_second(mut self, max: u32) -> Self {
+        self.config.smp_requests_per_second = Some(max);
+        self
+    }
+
+    /// Send SMP requests through the proxy at `url`; see [`LookupConfig::proxy`]
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.config.proxy = Some(url.into());
+        self
+    }
+
+    /// `User-Agent` header for SMP requests; see [`LookupConfig::user_agent`]
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.config.user_agent = user_agent.into();
+        self
+    }
+
+    /// Only list and fetch document types starting with one of `prefixes`; see [`LookupConfig::document_filter`]
+    pub fn document_filter<I>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.config.document_filter = prefixes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Prefer endpoints with these transport profiles, best first; see [`LookupConfig::transport_preference`]
+    pub fn transport_preference<I>(mut self, profiles: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.config.transport_preference = profiles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Follow at most `max` service metadata references; see [`LookupConfig::max_references`]
+    pub fn max_references(mut self, max: usize) -> Self {
+        self.config.max_references = Some(max);
+        self
+    }
+
+    /// Whether to fail on elements outside the SMP schema; see [`LookupConfig::strict`]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    /// Whether to refuse SMP hosts with private addresses; see [`LookupConfig::block_private_addresses`]
+    pub fn block_private_addresses(mut self, block: bool) -> Self {
+        self.config.block_private_addresses = block;
+        self
+    }
+
+    /// Cache SML records and document lists for at most `ttl`
+    ///
+    /// Without this the client doesn't cache.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Build the client
+    ///
+    /// Fails with `PeppolError::Http` if the HTTP client can't be initialized,
+    /// e.g. when the TLS backend is unavailable or the proxy URL is invalid.
+    pub fn build(self) -> Result<LookupClient, PeppolError> {
+        let config = self.config;
+        let resolver = match self.resolver {
+            Some(resolver) => resolver,
+            None => Box::new(DnsResolver::new(&config)),
+        };
+        let smp = match self.smp_client {
+            Some(smp_client) => smp_client,
+            None => Box::new(HttpSmpClient::new(&config)?),
+        };
+
+        Ok(LookupClient {
+            environment: self.environment,
+            resolver,
+            smp,
+            cache: self.cache_ttl.map(LookupCache::new),
+            config,
+        })
+    }
+}
+
+impl fmt::Debug for LookupClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LookupClientBuilder")
+            .field("environment", &self.environment)
+            .field("custom_resolver", &self.resolver.is_some())
+            .field("custom_smp_client", &self.smp_client.is_some())
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,324 @@
+//! Options controlling how lookups are performed.
+
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::certificate::Certificate;
+use crate::document::DocumentTypeIdentifier;
+use crate::endpoint::Endpoint;
+use crate::environment::SmlEnvironment;
+use crate::signature::TrustStore;
+
+/// `User-Agent` sent with SMP requests unless [`LookupConfig::user_agent`] is changed
+pub const DEFAULT_USER_AGENT: &str = concat!("peppol-lookup/", env!("CARGO_PKG_VERSION"));
+
+/// How long idle SMP connections are kept unless [`LookupConfig::pool_idle_timeout`] is changed
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Configuration shared by the lookup functions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupConfig {
+    /// Verify the XMLDSig signature on `SignedServiceMetadata` responses
+    ///
+    /// Users who only want discovery can turn this off. It is on by default
+    /// for production lookups, where it needs the OpenPeppol SMP CA
+    /// certificates in [`trust_store`](Self::trust_store): with no trusted
+    /// certificates at all, fetching service metadata fails with
+    /// `PeppolError::Config`.
+    pub verify_signature: bool,
+    /// CA certificates trusted to issue SMP signing certificates
+    pub trust_store: TrustStore,
+    /// Extra root certificates trusted for SMP TLS connections and SMP signatures
+    ///
+    /// For networks with a private PKI, such as the PEPPOL test network: the
+    /// certificates are trusted for HTTPS (SMP and DNS-over-HTTPS requests) in
+    /// addition to the system roots, and for signing certificates in addition
+    /// to [`trust_store`](Self::trust_store). Empty by default.
+    pub root_certificates: Vec<Certificate>,
+    /// DNS servers to query for the SML step; empty uses the system resolver
+    pub dns_servers: Vec<SocketAddr>,
+    /// DNS-over-HTTPS (RFC 8484) resolver for the SML step, e.g. `https://cloudflare-dns.com/dns-query`
+    ///
+    /// For networks where plain DNS to the SML is blocked or tampered with.
+    /// Queries are sent with the same HTTP client settings as SMP requests,
+    /// proxy included, and take precedence over [`dns_servers`](Self::dns_servers).
+    /// `None` (the default) uses plain DNS.
+    pub dns_over_https: Option<String>,
+    /// How long to wait for each DNS query before giving up
+    pub dns_timeout: Duration,
+    /// How long to wait for a TCP connection to the SMP
+    pub connect_timeout: Duration,
+    /// Total time allowed for each SMP request, including reading the body
+    pub request_timeout: Duration,
+    /// Total time allowed for a combined [`lookup`](crate::lookup::lookup), across the SML and SMP steps
+    ///
+    /// The per-step timeouts above still apply, but are cut to whatever is
+    /// left of the budget, so a slow DNS answer leaves less time for the SMP
+    /// requests. A lookup that runs out fails with
+    /// `PeppolError::DeadlineExceeded`. `None` (the default) sets no overall limit.
+    /// Only [`lookup_with_config`](crate::lookup::lookup_with_config) applies it;
+    /// a [`LookupClient`](crate::client::LookupClient) ignores it.
+    pub time_budget: Option<Duration>,
+    /// Which scheme to use when an SMP is given as a bare hostname
+    pub smp_scheme: SmpScheme,
+    /// Accept invalid TLS certificates from SMPs
+    ///
+    /// Only meant for debugging misconfigured test SMPs: with this set, any
+    /// server can impersonate an SMP over HTTPS.
+    pub danger_accept_invalid_certs: bool,
+    /// How often SMP requests are retried after transient failures
+    pub retry: RetryPolicy,
+    /// Idle connections kept open to each SMP host for reuse
+    ///
+    /// Unlimited by default. Batch jobs looking up many participants on a few
+    /// large SMPs should keep at least as many as they run lookups in
+    /// parallel; interactive services talking to many SMPs can keep a few
+    /// each (say 4) to bound the number of open sockets.
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection is kept before it is closed
+    ///
+    /// 90 seconds by default; `None` keeps idle connections until the SMP
+    /// closes them. Lower it (to 30 seconds, say) for interactive services
+    /// that rarely query the same SMP twice in a row.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Upper bound on SMP requests in flight at once through one [`HttpSmpClient`](crate::smp_client::HttpSmpClient)
+    ///
+    /// Shared by every thread using the client, e.g. the workers of
+    /// [`LookupClient::lookup_many`](crate::client::LookupClient::lookup_many),
+    /// so a high-QPS service can't overwhelm SMPs or exhaust its own sockets.
+    /// Further requests wait for a slot. `None` (the default) sets no limit.
+    pub max_concurrent_requests: Option<usize>,
+    /// Upper bound on requests per second to each SMP host through one [`HttpSmpClient`](crate::smp_client::HttpSmpClient)
+    ///
+    /// Requests to the same host are spaced evenly, across every thread
+    /// using the client, so a batch of participants that happen to share an
+    /// SMP doesn't flood its operator. Other hosts aren't slowed down.
+    /// Retries of a request count as that one request. `None` (the default)
+    /// sets no limit.
+    pub smp_requests_per_second: Option<u32>,
+    /// Proxy URL for SMP requests, e.g. `http://proxy.example.com:3128`
+    ///
+    /// `None` uses the `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` environment
+    /// variables, if set. `socks5://` and `socks5h://` proxies need the
+    /// `socks` feature. The SML step is plain DNS and doesn't use the proxy
+    /// unless it goes through [`dns_over_https`](Self::dns_over_https).
+    pub proxy: Option<String>,
+    /// `User-Agent` header for SMP requests, [`DEFAULT_USER_AGENT`] by default
+    ///
+    /// SMP operators use it to tell callers apart; applications can add their
+    /// own name and contact, e.g. `"my-app/1.0 (ops@example.com)"`.
+    pub user_agent: String,
+    /// Only list and fetch document types whose identifier starts with one of these prefixes
+    ///
+    /// Prefixes are matched against the identifier value without its scheme,
+    /// e.g. `urn:oasis:names:specification:ubl:schema:xsd:Invoice-2`. Service
+    /// metadata references are skipped before they are followed, which saves
+    /// a request per excluded document type. Empty (the default) keeps all.
+    pub document_filter: Vec<String>,
+    /// Transport profiles to prefer, best first, e.g. `peppol-transport-as4-v2_0`
+    ///
+    /// [`get_endpoints`](crate::lookup::get_endpoints) sorts the endpoints by
+    /// this order, and [`get_endpoint`](crate::lookup::get_endpoint) returns
+    /// the best match. Profiles not listed come after the listed ones, in the
+    /// order the SMP published them. Empty (the default) keeps the SMP's order.
+    pub transport_preference: Vec<String>,
+    /// Follow at most this many service metadata references per service group
+    ///
+    /// Applied after [`document_filter`](Self::document_filter), in the order
+    /// the SMP lists them. Participants publishing dozens of document types
+    /// otherwise cost a request each in [`document_types`](crate::lookup::document_types)
+    /// and [`lookup_all_metadata`](crate::lookup::lookup_all_metadata); the
+    /// lists returned by [`smp_lookup`](crate::lookup::smp_lookup) are cut
+    /// the same way. `None` (the default) follows them all.
+    pub max_references: Option<usize>,
+    /// Fail on SMP responses with elements outside the SMP schema
+    ///
+    /// For conformance testing of SMP implementations: responses are checked
+    /// before they are parsed, and an unknown element fails the lookup with
+    /// `PeppolError::Xml` naming it and its parent. The content of
+    /// `Extension` and signature elements isn't checked. Off by default,
+    /// where unknown elements are skipped.
+    pub strict: bool,
+    /// Refuse SMP hosts that resolve to loopback, private or link-local addresses
+    ///
+    /// Guards against a poisoned SML answer or a malicious SMP redirect
+    /// pointing a lookup at an internal service. Checked for the SMP host the
+    /// SML points at, for service metadata references and participant list
+    /// pages leaving the host that linked them, and for the targets of SMP
+    /// `Redirect`s and HTTP redirects; blocked hosts fail with
+    /// `PeppolError::BlockedAddress`. On by default for production only, so a
+    /// [`SmlEnvironment::Custom`] SML on a local network works as is; turn it
+    /// on there if the SMPs it points at are all public.
+    pub block_private_addresses: bool,
+}
+
+/// How SMP URLs are built from a bare hostname
+///
+/// Full URLs (e.g. from [`discover_smp_url`](crate::lookup::discover_smp_url))
+/// are always used as given. The default depends on the environment; see
+/// [`SmpScheme::for_environment`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SmpScheme {
+    /// Try `https://` first and fall back to `http://` if the connection fails
+    ///
+    /// Never a default: a failed connection, or an attacker blocking port
+    /// 443, would send the lookup over cleartext.
+    HttpsWithHttpFallback,
+    /// Only use `https://`
+    #[default]
+    Https,
+    /// Only use `http://`
+    Http,
+}
+
+impl SmpScheme {
+    /// The default scheme for SMPs registered in `environment`
+    ///
+    /// Production (and custom network) lookups only use HTTPS, so nothing is
+    /// looked up in cleartext. SMPs that only serve HTTP need
+    /// [`HttpsWithHttpFallback`](Self::HttpsWithHttpFallback) or
+    /// [`Http`](Self::Http), set explicitly. Many test SMPs only serve HTTP,
+    /// so they are queried over HTTP directly.
+    pub fn for_environment(environment: &SmlEnvironment) -> Self {
+        match environment {
+            SmlEnvironment::Test => SmpScheme::Http,
+            SmlEnvironment::Production | SmlEnvironment::Custom { .. } => SmpScheme::Https,
+        }
+    }
+}
+
+impl LookupConfig {
+    /// Default configuration for an environment
+    ///
+    /// Signatures are verified and SMP hosts with private addresses refused in
+    /// production only. The SMP scheme comes from [`SmpScheme::for_environment`].
+    /// The trust store starts empty, since the crate doesn't ship the
+    /// OpenPeppol SMP CA certificates. Add them for the environment before
+    /// fetching service metadata in production, or turn `verify_signature`
+    /// off for discovery only; otherwise those lookups fail with
+    /// `PeppolError::Config` rather than skipping verification.
+    pub fn for_environment(environment: SmlEnvironment) -> Self {
+        LookupConfig {
+            verify_signature: environment == SmlEnvironment::Production,
+            trust_store: TrustStore::new(),
+            root_certificates: Vec::new(),
+            dns_servers: Vec::new(),
+            dns_over_https: None,
+            dns_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(10),
+            time_budget: None,
+            smp_scheme: SmpScheme::for_environment(&environment),
+            danger_accept_invalid_certs: false,
+            retry: RetryPolicy::default(),
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+            max_concurrent_requests: None,
+            smp_requests_per_second: None,
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            document_filter: Vec::new(),
+            transport_preference: Vec::new(),
+            max_references: None,
+            strict: false,
+            block_private_addresses: environment == SmlEnvironment::Production,
+        }
+    }
+
+    /// Sort `endpoints` by [`transport_preference`](Self::transport_preference), keeping ties in order
+    pub(crate) fn sort_endpoints(&self, endpoints: &mut [Endpoint]) {
+        if self.transport_preference.is_empty() {
+            return;
+        }
+        endpoints.sort_by_key(|endpoint| {
+            self.transport_preference
+                .iter()
+                .position(|profile| profile.eq_ignore_ascii_case(&endpoint.transport_profile))
+                .unwrap_or(self.transport_preference.len())
+        });
+    }
+
+    /// The certificates trusted to issue SMP signing certificates: the trust store and the extra roots
+    pub(crate) fn signature_trust_store(&self) -> Cow<'_, TrustStore> {
+        if self.root_certificates.is_empty() {
+            return Cow::Borrowed(&self.trust_store);
+        }
+        let mut trust_store = self.trust_store.clone();
+        for certificate in &self.root_certificates {
+            trust_store.add(certificate.clone());
+        }
+        Cow::Owned(trust_store)
+    }
+
+    /// Whether [`document_filter`](Self::document_filter) lets `identifier` through
+    pub(crate) fn includes_document(&self, identifier: &DocumentTypeIdentifier) -> bool {
+        self.document_filter.is_empty() || self.document_filter.iter().any(|prefix| identifier.value.starts_with(prefix.as_str()))
+    }
+}
+
+impl Default for LookupConfig {
+    /// The configuration for [`SmlEnvironment::Production`]
+    ///
+    /// Used by the lookup functions that take no `config`, whatever network
+    /// the SMP they are given is on; see [`LookupConfig::for_environment`].
+    fn default() -> Self {
+        Self::for_environment(SmlEnvironment::default())
+    }
+}
+
+/// Retries for SMP requests that fail transiently
+///
+/// Timeouts, connection errors and 5xx responses are retried with jittered
+/// exponential backoff; these are the errors
+/// [`PeppolError::is_retryable`](crate::error::PeppolError::is_retryable) is
+/// true for. Other responses, including 404 (the SMP has no such service), are
+/// returned straight away. Each attempt gets the full
+/// [`request_timeout`](LookupConfig::request_timeout), so set
+/// [`time_budget`](LookupConfig::time_budget) to bound how long retrying a
+/// hung SMP can take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        RetryPolicy { max_attempts: 1, ..Self::default() }
+    }
+
+    /// Delay before retrying after `attempt` (starting at 1) failed
+    ///
+    /// Half of the exponential delay is fixed and the other half random, so
+    /// clients that failed together don't all retry at the same moment.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        let mut random = [0u8; 4];
+        // Without randomness the delay is just not jittered
+        let _ = SystemRandom::new().fill(&mut random);
+        let fraction = f64::from(u32::from_le_bytes(random)) / f64::from(u32::MAX);
+        exponential / 2 + exponential.mul_f64(fraction / 2.0)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, waiting about 200ms and then 400ms in between
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Changes between two lookups of the same participant, for change alerts.
+
+use std::fmt;
+
+use crate::certificate::Certificate;
+use crate::document::{DocumentType, DocumentTypeIdentifier};
+use crate::endpoint::Endpoint;
+use crate::metadata::{DocumentMetadata, ParticipantMetadata};
+
+/// What changed between two [`ParticipantMetadata`] snapshots
+///
+/// Returned by [`ParticipantMetadata::diff`]. Document types are matched by
+/// identifier, and endpoints within a document type by transport profile.
+/// A certificate change on an endpoint that is otherwise the same is listed
+/// in [`certificate_changes`](Self::certificate_changes) only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetadataDiff {
+    /// Document types only the newer snapshot has
+    pub added: Vec<DocumentTypeIdentifier>,
+    /// Document types only the older snapshot has
+    pub removed: Vec<DocumentTypeIdentifier>,
+    /// Endpoints that appeared, disappeared or changed other than in their certificate
+    pub changed_endpoints: Vec<EndpointChange>,
+    /// Endpoints whose certificate was added, removed or replaced
+    pub certificate_changes: Vec<CertificateChange>,
+}
+
+impl MetadataDiff {
+    /// Whether the snapshots are the same, as far as the diff can tell
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed_endpoints.is_empty() && self.certificate_changes.is_empty()
+    }
+}
+
+/// One line per change, newest state last
+impl fmt::Display for MetadataDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for document in &self.added {
+            writeln!(f, "+ {}", document.value)?;
+        }
+        for document in &self.removed {
+            writeln!(f, "- {}", document.value)?;
+        }
+        for change in &self.changed_endpoints {
+            match (&change.old, &change.new) {
+                (None, Some(new)) => writeln!(f, "{}: added {} endpoint {}", change.document.value, change.transport_profile, new.url)?,
+                (Some(old), None) => writeln!(f, "{}: removed {} endpoint {}", change.document.value, change.transport_profile, old.url)?,
+                (Some(old), Some(new)) if old.url != new.url => {
+                    writeln!(f, "{}: {} endpoint moved from {} to {}", change.document.value, change.transport_profile, old.url, new.url)?
+                }
+                _ => writeln!(f, "{}: {} endpoint {} changed", change.document.value, change.transport_profile, change.url())?,
+            }
+        }
+        for change in &self.certificate_changes {
+            let describe = |certificate: &Option<Certificate>| match certificate {
+                Some(certificate) => format!("{} (expires {})", certificate.subject, certificate.not_after.date()),
+                None => "none".to_string(),
+            };
+            writeln!(
+                f,
+                "{}: certificate of {} changed from {} to {}",
+                change.document.value,
+                change.endpoint_url,
+                describe(&change.old),
+                describe(&change.new)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An endpoint of a document type both snapshots have, before and after
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EndpointChange {
+    /// The document type the endpoint receives
+    pub document: DocumentTypeIdentifier,
+    /// The transport profile the endpoints were matched by
+    pub transport_profile: String,
+    /// The endpoint in the older snapshot, `None` if it was added
+    pub old: Option<Endpoint>,
+    /// The endpoint in the newer snapshot, `None` if it was removed
+    pub new: Option<Endpoint>,
+}
+
+impl EndpointChange {
+    /// The endpoint's current access point URL, or its last one if it was removed
+    pub fn url(&self) -> &str {
+        self.new.as_ref().or(self.old.as_ref()).map_or("", |endpoint| endpoint.url.as_str())
+    }
+}
+
+/// An endpoint's certificate before and after
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CertificateChange {
+    /// The document type the endpoint receives
+    pub document: DocumentTypeIdentifier,
+    /// The endpoint's access point URL in the newer snapshot
+    pub endpoint_url: String,
+    /// The certificate in the older snapshot
+    pub old: Option<Certificate>,
+    /// The certificate in the newer snapshot
+    pub new: Option<Certificate>,
+}
+
+/// Compare the `old` snapshot with the `new` one
+pub(crate) fn diff(old: &ParticipantMetadata, new: &ParticipantMetadata) -> MetadataDiff {
+    let mut diff = MetadataDiff::default();
+    for document in &new.documents {
+        let Some(identifier) = identifier(document) else { continue };
+        match find(old, &identifier) {
+            None => diff.added.push(identifier),
+            Some(old_document) => {
+                if let (Ok(old_type), Ok(new_type)) = (&old_document.result, &document.result) {
+                    diff_endpoints(&mut diff, &identifier, old_type, new_type);
+                }
+            }
+        }
+    }
+    for document in &old.documents {
+        let Some(identifier) = identifier(document) else { continue };
+        if find(new, &identifier).is_none() {
+            diff.removed.push(identifier);
+        }
+    }
+    diff
+}
+
+fn diff_endpoints(diff: &mut MetadataDiff, document: &DocumentTypeIdentifier, old: &DocumentType, new: &DocumentType) {
+    let change = |old: Option<&Endpoint>, new: Option<&Endpoint>, transport_profile: &str| EndpointChange {
+        document: document.clone(),
+        transport_profile: transport_profile.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+    for new_endpoint in &new.endpoints {
+        let profile = &new_endpoint.transport_profile;
+        match old.endpoints.iter().find(|endpoint| endpoint.transport_profile == *profile) {
+            None => diff.changed_endpoints.push(change(None, Some(new_endpoint), profile)),
+            Some(old_endpoint) => {
+                let same_certificate = Endpoint { certificate: new_endpoint.certificate.clone(), ..old_endpoint.clone() };
+                if same_certificate != *new_endpoint {
+                    diff.changed_endpoints.push(change(Some(old_endpoint), Some(new_endpoint), profile));
+                }
+                if old_endpoint.certificate != new_endpoint.certificate {
+                    diff.certificate_changes.push(CertificateChange {
+                        document: document.clone(),
+                        endpoint_url: new_endpoint.url.clone(),
+                        old: old_endpoint.certificate.clone(),
+                        new: new_endpoint.certificate.clone(),
+                    });
+                }
+            }
+        }
+    }
+    for old_endpoint in &old.endpoints {
+        let profile = &old_endpoint.transport_profile;
+        if !new.endpoints.iter().any(|endpoint| endpoint.transport_profile == *profile) {
+            diff.changed_endpoints.push(change(Some(old_endpoint), None, profile));
+        }
+    }
+}
+
+/// The document's identifier from its reference, or else from its service metadata
+fn identifier(document: &DocumentMetadata) -> Option<DocumentTypeIdentifier> {
+    document
+        .identifier
+        .clone()
+        .or_else(|| document.result.as_ref().ok().map(DocumentType::identifier))
+}
+
+fn find<'a>(metadata: &'a ParticipantMetadata, identifier: &DocumentTypeIdentifier) -> Option<&'a DocumentMetadata> {
+    metadata.documents.iter().find(|document| self::identifier(document).as_ref() == Some(identifier))
+}
@@ -0,0 +1,161 @@
+//! Name search in the OpenPeppol Directory.
+//!
+//! The SML and SMP steps need the participant ID up front. The Directory
+//! (`directory.peppol.eu`) indexes the business cards participants publish, so
+//! it can turn a company name or country into candidate participant IDs that
+//! are then looked up as usual.
+
+use serde_json::Value;
+
+use crate::config::LookupConfig;
+use crate::document::DocumentTypeIdentifier;
+use crate::error::PeppolError;
+use crate::http;
+use crate::participant::Participant;
+
+/// The production OpenPeppol Directory
+pub const PRODUCTION_DIRECTORY_URL: &str = "https://directory.peppol.eu";
+
+/// The OpenPeppol Directory for the test network
+pub const TEST_DIRECTORY_URL: &str = "https://test-directory.peppol.eu";
+
+/// What to search the Directory for
+///
+/// The Directory matches names fuzzily. At least one of `name` and `country`
+/// must be set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryQuery {
+    /// (Part of) the registered business name
+    pub name: Option<String>,
+    /// ISO 3166 country code, e.g. `NO`
+    pub country: Option<String>,
+}
+
+impl DirectoryQuery {
+    /// Search by business name
+    pub fn name(name: impl Into<String>) -> Self {
+        DirectoryQuery { name: Some(name.into()), country: None }
+    }
+
+    /// Only return participants registered in `country`
+    pub fn country(mut self, country: impl Into<String>) -> Self {
+        self.country = Some(country.into());
+        self
+    }
+
+    /// The search URL below `directory_url`
+    fn url(&self, directory_url: &str) -> Result<String, PeppolError> {
+        let mut parameters = Vec::new();
+        if let Some(name) = self.name.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+            parameters.push(format!("name={}", urlencoding::encode(name)));
+        }
+        if let Some(country) = self.country.as_deref().map(str::trim).filter(|country| !country.is_empty()) {
+            parameters.push(format!("country={}", urlencoding::encode(&country.to_uppercase())));
+        }
+        if parameters.is_empty() {
+            return Err(PeppolError::InvalidIdentifier("directory query needs a name or country".to_string()));
+        }
+        Ok(format!("{}/search/1.0/json?{}", directory_url.trim_end_matches('/'), parameters.join("&")))
+    }
+}
+
+/// A participant found in the Directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirectoryMatch {
+    /// The participant ID, ready for [`sml_lookup`](crate::lookup::sml_lookup)
+    pub participant: Participant,
+    /// Registered names of the participant's business entities
+    pub names: Vec<String>,
+    /// Country code of the first business entity
+    pub country_code: Option<String>,
+    /// Document types the Directory lists for the participant
+    pub document_types: Vec<DocumentTypeIdentifier>,
+}
+
+/// Search the production Directory
+///
+/// Returns the first page of matches (the Directory's default page size).
+/// Matches whose participant ID can't be parsed are skipped.
+#[cfg(feature = "blocking")]
+pub fn search_directory(query: &DirectoryQuery) -> Result<Vec<DirectoryMatch>, PeppolError> {
+    search_directory_with_config(PRODUCTION_DIRECTORY_URL, query, &LookupConfig::default())
+}
+
+/// Like [`search_directory`], against the Directory at `directory_url` with the HTTP settings from `config`
+#[cfg(feature = "blocking")]
+pub fn search_directory_with_config(
+    directory_url: &str,
+    query: &DirectoryQuery,
+    config: &LookupConfig,
+) -> Result<Vec<DirectoryMatch>, PeppolError> {
+    let url = query.url(directory_url)?;
+    debug!(%url, "searching directory");
+    let response = http::get_blocking(&http::blocking_client(config)?, &url, None, config, None)?;
+    parse_matches(&response.body)
+}
+
+/// Async variant of [`search_directory`]
+pub async fn search_directory_async(query: &DirectoryQuery) -> Result<Vec<DirectoryMatch>, PeppolError> {
+    search_directory_with_config_async(PRODUCTION_DIRECTORY_URL, query, &LookupConfig::default()).await
+}
+
+/// Async variant of [`search_directory_with_config`]
+pub async fn search_directory_with_config_async(
+    directory_url: &str,
+    query: &DirectoryQuery,
+    config: &LookupConfig,
+) -> Result<Vec<DirectoryMatch>, PeppolError> {
+    let url = query.url(directory_url)?;
+    debug!(%url, "searching directory");
+    let response = http::get(&http::client(config)?, &url, None, config).await?;
+    parse_matches(&response.body)
+}
+
+/// Parse the `matches` of a Directory JSON search response
+fn parse_matches(response: &str) -> Result<Vec<DirectoryMatch>, PeppolError> {
+    let response: Value = serde_json::from_str(response).map_err(|err| PeppolError::Directory(err.to_string()))?;
+    let matches = response
+        .get("matches")
+        .and_then(Value::as_array)
+        .ok_or_else(|| PeppolError::Directory("response has no matches".to_string()))?;
+
+    Ok(matches.iter().filter_map(parse_match).collect())
+}
+
+fn parse_match(value: &Value) -> Option<DirectoryMatch> {
+    let participant = identifier(value.get("participantID")?)?.parse().ok()?;
+
+    let entities = value.get("entities").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    let names = entities
+        .iter()
+        .flat_map(|entity| match entity.get("name") {
+            // Business card 2018 and later: a list of names in different languages
+            Some(Value::Array(names)) => names.iter().filter_map(|name| name.get("name")?.as_str()).collect(),
+            Some(Value::String(name)) => vec![name.as_str()],
+            _ => Vec::new(),
+        })
+        .map(str::to_string)
+        .collect();
+    let country_code = entities
+        .iter()
+        .find_map(|entity| entity.get("countryCode")?.as_str())
+        .map(str::to_string);
+
+    let document_types = value
+        .get("docTypes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|doc_type| identifier(doc_type)?.parse().ok())
+        .collect();
+
+    Some(DirectoryMatch { participant, names, country_code, document_types })
+}
+
+/// `<scheme>::<value>` from a `{ "scheme": ..., "value": ... }` object
+fn identifier(value: &Value) -> Option<String> {
+    let scheme = value.get("scheme")?.as_str()?;
+    let value = value.get("value")?.as_str()?;
+    Some(format!("{}::{}", scheme, value))
+}
@@ -0,0 +1,385 @@
+//! DNS queries used by the SML step.
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::lookup::Lookup;
+use hickory_resolver::lookup_ip::LookupIp;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::system_conf::read_system_conf;
+use hickory_resolver::{Resolver, TokioAsyncResolver};
+use regex::Regex;
+use tokio::runtime::{self, Runtime};
+
+use crate::config::LookupConfig;
+use crate::doh::DohResolver;
+use crate::error::PeppolError;
+
+/// NAPTR service name under which SMPs are published
+const SMP_NAPTR_SERVICE: &str = "Meta:SMP";
+
+/// Upper bound on CNAME hops, guarding against loops
+const MAX_CNAME_HOPS: usize = 8;
+
+/// Check whether `hostname` resolves, without following its CNAME chain
+///
+/// Both A and AAAA records are queried, and either counts, as does a CNAME
+/// whose target has no addresses (yet). Returns `Ok(false)` for NXDOMAIN and
+/// names with neither; other failures are reported as `PeppolError::Dns`.
+pub(crate) fn hostname_exists(hostname: &str, config: &LookupConfig) -> Result<bool, PeppolError> {
+    let resolver = SmlResolver::new(config)?;
+    Ok(resolver.lookup_ip(hostname)?.is_some() || resolver.lookup(hostname, RecordType::CNAME)?.is_some())
+}
+
+/// The answer for an SML name: where its CNAME chain ends, and its addresses
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedName {
+    /// The canonical name at the end of the CNAME chain
+    pub(crate) name: String,
+    /// The shortest remaining TTL of the address answer and the CNAME records followed
+    pub(crate) ttl: Duration,
+    /// The A and AAAA addresses the name resolved to
+    pub(crate) addresses: Vec<IpAddr>,
+}
+
+/// Resolve `hostname` and follow its CNAME chain to the canonical name
+///
+/// Returns the canonical name, the addresses it resolved to, and the
+/// shortest remaining TTL of the address answer and the CNAME records
+/// followed, so a cached record expires with the first of them.
+/// A CNAME is proof of registration on its own: if its target has no address
+/// records yet, the canonical name is returned without addresses.
+/// Returns `Ok(None)` for NXDOMAIN and names with neither. Anything
+/// else that goes wrong (SERVFAIL, timeouts, unreachable servers) is reported
+/// as `PeppolError::Dns` so it isn't mistaken for an unregistered participant.
+///
+/// With [`LookupConfig::block_private_addresses`], a name resolving to a
+/// private address fails with `PeppolError::BlockedAddress`.
+pub(crate) fn resolve_canonical_name(hostname: &str, config: &LookupConfig) -> Result<Option<ResolvedName>, PeppolError> {
+    let resolver = SmlResolver::new(config)?;
+    let (addresses, mut ttl) = match resolver.lookup_ip(hostname)? {
+        Some(lookup) => {
+            check_addresses(hostname, lookup.iter(), config)?;
+            (lookup.iter().collect(), lookup.valid_until().saturating_duration_since(Instant::now()))
+        }
+        None => (Vec::new(), Duration::MAX),
+    };
+
+    let mut name = hostname.trim_end_matches('.').to_string();
+    let mut aliased = false;
+    for _ in 0..MAX_CNAME_HOPS {
+        let Some(lookup) = resolver.lookup(&name, RecordType::CNAME)? else {
+            break;
+        };
+        ttl = ttl.min(lookup.valid_until().saturating_duration_since(Instant::now()));
+        let target = lookup.record_iter().find_map(|record| match record.data() {
+            Some(RData::CNAME(cname)) => Some(cname.0.to_utf8()),
+            _ => None,
+        });
+        match target {
+            Some(target) => {
+                name = target.trim_end_matches('.').to_string();
+                aliased = true;
+            }
+            None => break,
+        }
+    }
+
+    // Neither addresses nor a CNAME: the name isn't registered
+    if addresses.is_empty() && !aliased {
+        return Ok(None);
+    }
+    Ok(Some(ResolvedName { name, ttl, addresses }))
+}
+
+/// Async variant of [`resolve_canonical_name`] using hickory's tokio resolver
+pub(crate) async fn resolve_canonical_name_async(hostname: &str, config: &LookupConfig) -> Result<Option<ResolvedName>, PeppolError> {
+    let resolver = AsyncSmlResolver::new(config)?;
+    let (addresses, mut ttl) = match resolver.lookup_ip(hostname).await? {
+        Some(lookup) => {
+            check_addresses(hostname, lookup.iter(), config)?;
+            (lookup.iter().collect(), lookup.valid_until().saturating_duration_since(Instant::now()))
+        }
+        None => (Vec::new(), Duration::MAX),
+    };
+
+    let mut name = hostname.trim_end_matches('.').to_string();
+    let mut aliased = false;
+    for _ in 0..MAX_CNAME_HOPS {
+        let Some(lookup) = resolver.lookup(&name, RecordType::CNAME).await? else {
+            break;
+        };
+        ttl = ttl.min(lookup.valid_until().saturating_duration_since(Instant::now()));
+        let target = lookup.record_iter().find_map(|record| match record.data() {
+            Some(RData::CNAME(cname)) => Some(cname.0.to_utf8()),
+            _ => None,
+        });
+        match target {
+            Some(target) => {
+                name = target.trim_end_matches('.').to_string();
+                aliased = true;
+            }
+            None => break,
+        }
+    }
+
+    // Neither addresses nor a CNAME: the name isn't registered
+    if addresses.is_empty() && !aliased {
+        return Ok(None);
+    }
+    Ok(Some(ResolvedName { name, ttl, addresses }))
+}
+
+/// Check that the host of `url` doesn't resolve to a private address
+///
+/// Used before following a redirect to another SMP. Does nothing unless
+/// [`LookupConfig::block_private_addresses`] is set. Hosts that don't
+/// resolve are let through; the request to them fails on its own.
+#[cfg(feature = "blocking")]
+pub(crate) fn check_url_host(url: &str, config: &LookupConfig) -> Result<(), PeppolError> {
+    let Some(host) = host_to_check(url, config) else {
+        return Ok(());
+    };
+    match host.parse::<IpAddr>() {
+        Ok(address) => check_addresses(&host, [address], config),
+        Err(_) => match resolver(config)?.lookup_ip(host.as_str()) {
+            Ok(lookup) => check_addresses(&host, lookup.iter(), config),
+            Err(_) => Ok(()),
+        },
+    }
+}
+
+/// Async variant of [`check_url_host`]
+pub(crate) async fn check_url_host_async(url: &str, config: &LookupConfig) -> Result<(), PeppolError> {
+    let Some(host) = host_to_check(url, config) else {
+        return Ok(());
+    };
+    match host.parse::<IpAddr>() {
+        Ok(address) => check_addresses(&host, [address], config),
+        Err(_) => match async_resolver(config)?.lookup_ip(host.as_str()).await {
+            Ok(lookup) => check_addresses(&host, lookup.iter(), config),
+            Err(_) => Ok(()),
+        },
+    }
+}
+
+/// The host of `url` without IPv6 brackets, if private addresses are blocked
+fn host_to_check(url: &str, config: &LookupConfig) -> Option<String> {
+    if !config.block_private_addresses {
+        return None;
+    }
+    let url = reqwest::Url::parse(url).ok()?;
+    Some(url.host_str()?.trim_start_matches('[').trim_end_matches(']').to_string())
+}
+
+/// Fail with `PeppolError::BlockedAddress` if any of `addresses` is private and `config` blocks those
+fn check_addresses(hostname: &str, addresses: impl IntoIterator<Item = IpAddr>, config: &LookupConfig) -> Result<(), PeppolError> {
+    if !config.block_private_addresses {
+        return Ok(());
+    }
+    match addresses.into_iter().find(|&address| is_private(address)) {
+        Some(address) => Err(PeppolError::BlockedAddress(format!("{} resolves to {}", hostname, address))),
+        None => Ok(()),
+    }
+}
+
+/// Loopback, RFC 1918, carrier-grade NAT, link-local, unique local and unspecified addresses
+fn is_private(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            // 100.64.0.0/10 (RFC 6598), which std doesn't cover yet
+            let shared = address.octets()[0] == 100 && address.octets()[1] & 0xc0 == 64;
+            address.is_private() || shared || address.is_loopback() || address.is_link_local() || address.is_unspecified()
+        }
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(mapped) => is_private(IpAddr::V4(mapped)),
+            None => {
+                address.is_loopback()
+                    || address.is_unspecified()
+                    || address.is_unique_local()
+                    || address.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Look up the SMP URL published in a U-NAPTR record for `hostname`
+///
+/// Picks the `Meta:SMP` record with the lowest order and preference and
+/// applies its regexp to the queried name. Returns `Ok(None)` if the name has
+/// no such record.
+pub(crate) fn naptr_smp_url(hostname: &str, config: &LookupConfig) -> Result<Option<String>, PeppolError> {
+    let Some(lookup) = SmlResolver::new(config)?.lookup(hostname, RecordType::NAPTR)? else {
+        return Ok(None);
+    };
+
+    let mut records: Vec<_> = lookup
+        .record_iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::NAPTR(naptr)) => Some(naptr.clone()),
+            _ => None,
+        })
+        .filter(|naptr| {
+            naptr.flags().eq_ignore_ascii_case(b"U")
+                && naptr.services().eq_ignore_ascii_case(SMP_NAPTR_SERVICE.as_bytes())
+        })
+        .collect();
+    records.sort_by_key(|naptr| (naptr.order(), naptr.preference()));
+
+    match records.first() {
+        Some(naptr) => apply_naptr_regexp(&String::from_utf8_lossy(naptr.regexp()), hostname).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Apply a NAPTR regexp field such as `!^.*$!https://smp.example.com!` to `input`
+///
+/// The first character is the delimiter separating the pattern from the
+/// replacement. Back-references are written `\1` in NAPTR and are translated
+/// to the regex crate's `${1}` syntax.
+fn apply_naptr_regexp(field: &str, input: &str) -> Result<String, PeppolError> {
+    let invalid = || PeppolError::Dns(format!("invalid NAPTR regexp {:?}", field));
+
+    let delimiter = field.chars().next().ok_or_else(invalid)?;
+    let mut parts = field[delimiter.len_utf8()..].split(delimiter);
+    let (pattern, replacement) = match (parts.next(), parts.next()) {
+        (Some(pattern), Some(replacement)) => (pattern, replacement),
+        _ => return Err(invalid()),
+    };
+
+    let pattern = Regex::new(pattern).map_err(|_| invalid())?;
+    let replacement = Regex::new(r"\\(\d)")
+        .map_err(|_| invalid())?
+        .replace_all(replacement, "$${$1}");
+    Ok(pattern.replace(input, replacement.as_ref()).into_owned())
+}
+
+/// Where the SML step sends its queries: DNS servers, or a DoH resolver
+///
+/// Both answer `Ok(None)` for names or records that don't exist, and
+/// `PeppolError::Dns` for every other failure.
+enum SmlResolver {
+    Dns(Box<Resolver>),
+    // The DoH client is async, so blocking queries run on a runtime of their own
+    Doh(DohResolver, Runtime),
+}
+
+impl SmlResolver {
+    /// A resolver for the DNS settings in `config`, preferring its DoH URL if set
+    fn new(config: &LookupConfig) -> Result<Self, PeppolError> {
+        match DohResolver::new(config)? {
+            Some(doh) => {
+                let runtime = runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|err| PeppolError::Dns(err.to_string()))?;
+                Ok(SmlResolver::Doh(doh, runtime))
+            }
+            None => Ok(SmlResolver::Dns(Box::new(resolver(config)?))),
+        }
+    }
+
+    fn lookup_ip(&self, name: &str) -> Result<Option<LookupIp>, PeppolError> {
+        match self {
+            SmlResolver::Dns(resolver) => found(resolver.lookup_ip(name)),
+            SmlResolver::Doh(doh, runtime) => runtime.block_on(doh.lookup_ip(name)),
+        }
+    }
+
+    fn lookup(&self, name: &str, record_type: RecordType) -> Result<Option<Lookup>, PeppolError> {
+        match self {
+            SmlResolver::Dns(resolver) => found(resolver.lookup(name, record_type)),
+            SmlResolver::Doh(doh, runtime) => runtime.block_on(doh.lookup(name, record_type)),
+        }
+    }
+}
+
+/// Async variant of [`SmlResolver`]
+enum AsyncSmlResolver {
+    Dns(Box<TokioAsyncResolver>),
+    Doh(DohResolver),
+}
+
+impl AsyncSmlResolver {
+    fn new(config: &LookupConfig) -> Result<Self, PeppolError> {
+        match DohResolver::new(config)? {
+            Some(doh) => Ok(AsyncSmlResolver::Doh(doh)),
+            None => Ok(AsyncSmlResolver::Dns(Box::new(async_resolver(config)?))),
+        }
+    }
+
+    async fn lookup_ip(&self, name: &str) -> Result<Option<LookupIp>, PeppolError> {
+        match self {
+            AsyncSmlResolver::Dns(resolver) => found(resolver.lookup_ip(name).await),
+            AsyncSmlResolver::Doh(doh) => doh.lookup_ip(name).await,
+        }
+    }
+
+    async fn lookup(&self, name: &str, record_type: RecordType) -> Result<Option<Lookup>, PeppolError> {
+        match self {
+            AsyncSmlResolver::Dns(resolver) => found(resolver.lookup(name, record_type).await),
+            AsyncSmlResolver::Doh(doh) => doh.lookup(name, record_type).await,
+        }
+    }
+}
+
+/// A hickory answer, with missing names and records as `None`
+fn found<T>(result: Result<T, ResolveError>) -> Result<Option<T>, PeppolError> {
+    match result {
+        Ok(lookup) => Ok(Some(lookup)),
+        Err(err) if is_not_found(&err) => Ok(None),
+        Err(err) => Err(PeppolError::Dns(err.to_string())),
+    }
+}
+
+/// Build a resolver from the DNS settings in `config`
+fn resolver(config: &LookupConfig) -> Result<Resolver, PeppolError> {
+    let (resolver_config, options) = resolver_settings(config)?;
+    Resolver::new(resolver_config, options).map_err(|err| PeppolError::Dns(err.to_string()))
+}
+
+/// Build an async resolver from the DNS settings in `config`
+fn async_resolver(config: &LookupConfig) -> Result<TokioAsyncResolver, PeppolError> {
+    let (resolver_config, options) = resolver_settings(config)?;
+    Ok(TokioAsyncResolver::tokio(resolver_config, options))
+}
+
+/// Resolver configuration for the DNS settings in `config`
+///
+/// Without explicit `dns_servers` the system configuration is used, with the
+/// configured timeout and dual-stack address lookups applied on top.
+fn resolver_settings(config: &LookupConfig) -> Result<(ResolverConfig, ResolverOpts), PeppolError> {
+    let (resolver_config, mut options) = if config.dns_servers.is_empty() {
+        read_system_conf().map_err(|err| PeppolError::Dns(err.to_string()))?
+    } else {
+        let name_servers = config
+            .dns_servers
+            .iter()
+            .flat_map(|&server| {
+                [
+                    NameServerConfig::new(server, Protocol::Udp),
+                    NameServerConfig::new(server, Protocol::Tcp),
+                ]
+            })
+            .collect::<Vec<_>>();
+        (ResolverConfig::from_parts(None, vec![], name_servers), Default::default())
+    };
+    options.timeout = config.dns_timeout;
+    // Query A and AAAA together, so a name with only IPv6 addresses still
+    // counts as registered whatever the system resolver prefers
+    options.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+
+    Ok((resolver_config, options))
+}
+
+/// Whether the error means the name or record simply doesn't exist
+fn is_not_found(err: &ResolveError) -> bool {
+    matches!(
+        err.kind(),
+        ResolveErrorKind::NoRecordsFound { response_code, .. }
+            if *response_code == ResponseCode::NXDomain || *response_code == ResponseCode::NoError
+    )
+}
@@ -0,0 +1,353 @@
+//! Document types and the processes they are used in.
+
+use std::fmt;
+use std::str::FromStr;
+
+use time::OffsetDateTime;
+
+use crate::endpoint::Endpoint;
+use crate::error::PeppolError;
+use crate::extension::Extension;
+
+/// The document identifier scheme used by PEPPOL BIS documents
+pub const DOCUMENT_SCHEME: &str = "busdox-docid-qns";
+
+/// The document identifier scheme for wildcard registrations
+///
+/// Introduced with the PINT migration: a `*` in the customization stands for
+/// any text, so one registration covers every specialization of a
+/// specification (e.g. `urn:peppol:pint:billing-1@jp-1` under
+/// `urn:peppol:pint:billing-1*`). See [`DocumentTypeIdentifier::accepts`].
+pub const WILDCARD_DOCUMENT_SCHEME: &str = "peppol-doctype-wildcard";
+
+/// A document type identifier, split into its scheme and value
+///
+/// On the wire (in SMP URLs and `ServiceMetadataReference` hrefs) it is
+/// written `<scheme>::<value>`, e.g.
+/// `busdox-docid-qns::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##<customization>`.
+/// [`Display`](fmt::Display) writes that form and [`FromStr`] parses it back.
+///
+/// ```
+/// use peppol_lookup::document::BIS_BILLING_INVOICE;
+/// use peppol_lookup::DocumentTypeIdentifier;
+///
+/// let identifier: DocumentTypeIdentifier =
+///     "busdox-docid-qns::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##urn:cen.eu:en16931:2017"
+///         .parse()?;
+/// assert_eq!(identifier.scheme, "busdox-docid-qns");
+/// assert!(identifier.matches(BIS_BILLING_INVOICE));
+/// # Ok::<(), peppol_lookup::PeppolError>(())
+/// ```
+///
+/// With the `serde` feature it is serialized as that `<scheme>::<value>`
+/// string, which stays stable across versions of this crate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DocumentTypeIdentifier {
+    /// Identifier scheme, e.g. `busdox-docid-qns`
+    pub scheme: String,
+    /// The document identifier, including the customization after `##`
+    pub value: String,
+}
+
+impl DocumentTypeIdentifier {
+    /// An identifier in the [`DOCUMENT_SCHEME`] scheme
+    pub fn new(value: &str) -> Self {
+        DocumentTypeIdentifier {
+            scheme: DOCUMENT_SCHEME.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// The root namespace and local name, e.g. `urn:...:Invoice-2::Invoice`
+    pub fn root(&self) -> &str {
+        self.value.split_once("##").map_or(&self.value, |(root, _)| root)
+    }
+
+    /// The customization after `##`, if any
+    pub fn customization(&self) -> Option<&str> {
+        self.value.split_once("##").map(|(_, customization)| customization)
+    }
+
+    /// Whether this is the document `identifier`
+    ///
+    /// An identifier with a customization must match exactly. One without,
+    /// such as [`BIS_BILLING_INVOICE`],
+    /// matches every customization of that root. A `<scheme>::` prefix on
+    /// `identifier` is compared as well.
+    pub fn matches(&self, identifier: &str) -> bool {
+        let (scheme, value) = split_scheme(identifier);
+        if scheme.is_some_and(|scheme| !scheme.eq_ignore_ascii_case(&self.scheme)) {
+            return false;
+        }
+        if value.contains("##") {
+            self.value == value
+        } else {
+            self.root() == value
+        }
+    }
+
+    /// Whether this is a PINT billing document, in any jurisdiction
+    ///
+    /// True for customizations starting with [`PINT_CUSTOMIZATION`], which
+    /// includes specialisations such as `@jp-1` and wildcard registrations
+    /// like `urn:peppol:pint:billing-1*`.
+    pub fn is_pint(&self) -> bool {
+        self.customization().is_some_and(|customization| customization.starts_with(PINT_CUSTOMIZATION))
+    }
+
+    /// Whether this identifier is in the [`WILDCARD_DOCUMENT_SCHEME`]
+    pub fn is_wildcard(&self) -> bool {
+        self.scheme.eq_ignore_ascii_case(WILDCARD_DOCUMENT_SCHEME)
+    }
+
+    /// Whether a participant registered with this identifier accepts `document`
+    ///
+    /// A registration in any scheme but the wildcard one accepts only the
+    /// same identifier. A wildcard registration accepts document identifiers
+    /// of any scheme whose value matches its own, with the first `*` standing
+    /// for any (possibly empty) text.
+    pub fn accepts(&self, document: &DocumentTypeIdentifier) -> bool {
+        if !self.is_wildcard() {
+            return self == document;
+        }
+        match self.value.split_once('*') {
+            Some((prefix, suffix)) => {
+                document.value.len() >= prefix.len() + suffix.len()
+                    && document.value.starts_with(prefix)
+                    && document.value.ends_with(suffix)
+            }
+            None => self.value == document.value,
+        }
+    }
+
+    /// A readable name for this document type; see [`document_name`]
+    pub fn name(&self) -> Option<&'static str> {
+        document_name(&self.value)
+    }
+}
+
+impl fmt::Display for DocumentTypeIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.scheme, self.value)
+    }
+}
+
+impl FromStr for DocumentTypeIdentifier {
+    type Err = PeppolError;
+
+    /// Parse `<scheme>::<value>`, or a bare value in the [`DOCUMENT_SCHEME`] scheme
+    fn from_str(identifier: &str) -> Result<Self, Self::Err> {
+        let (scheme, value) = split_scheme(identifier.trim());
+        if value.is_empty() {
+            return Err(PeppolError::InvalidIdentifier("document identifier is empty".to_string()));
+        }
+        Ok(DocumentTypeIdentifier {
+            scheme: scheme.unwrap_or(DOCUMENT_SCHEME).to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DocumentTypeIdentifier {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DocumentTypeIdentifier {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Split a `<scheme>::` prefix off a document identifier
+///
+/// Document identifiers contain `::` themselves (`...:Invoice-2::Invoice`),
+/// so only a prefix without any `:` counts as a scheme.
+pub(crate) fn split_scheme(identifier: &str) -> (Option<&str>, &str) {
+    match identifier.split_once("::") {
+        Some((scheme, value)) if !scheme.is_empty() && !scheme.contains(':') => (Some(scheme), value),
+        _ => (None, identifier),
+    }
+}
+
+/// Default process identifier scheme, used by PEPPOL processes
+pub const PROCESS_SCHEME: &str = "cenbii-procid-ubl";
+
+/// A process identifier, e.g. the PEPPOL BIS Billing 3.0 process
+/// `urn:fdc:peppol.eu:2017:poacc:billing:01:1.0` in scheme `cenbii-procid-ubl`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessId {
+    /// Identifier scheme, e.g. `cenbii-procid-ubl`
+    pub scheme: String,
+    /// The process identifier itself
+    pub value: String,
+}
+
+impl fmt::Display for ProcessId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.scheme, self.value)
+    }
+}
+
+impl FromStr for ProcessId {
+    type Err = PeppolError;
+
+    /// Parse `<scheme>::<value>`, or a bare value in the [`PROCESS_SCHEME`] scheme
+    fn from_str(identifier: &str) -> Result<Self, Self::Err> {
+        let (scheme, value) = split_scheme(identifier.trim());
+        if value.is_empty() {
+            return Err(PeppolError::InvalidIdentifier("process identifier is empty".to_string()));
+        }
+        Ok(ProcessId {
+            scheme: scheme.unwrap_or(PROCESS_SCHEME).to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+/// A document type a participant can receive, with the processes it is
+/// registered for
+///
+/// A document type is only usable within the listed processes, so checking
+/// for both the document and the process avoids sending something the
+/// receiver can't handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocumentType {
+    /// Identifier scheme, e.g. `busdox-docid-qns`
+    pub scheme: String,
+    /// The full document identifier, including the customization after `##`
+    pub value: String,
+    /// Processes this document type is registered for
+    pub processes: Vec<ProcessId>,
+    /// Endpoints receiving this document type, across all its processes
+    pub endpoints: Vec<Endpoint>,
+    /// Extensions on the service metadata and its service information
+    pub extensions: Vec<Extension>,
+}
+
+/// PEPPOL BIS Billing 3.0 invoice document identifier
+pub const BIS_BILLING_INVOICE: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice";
+/// PEPPOL BIS Billing 3.0 credit note document identifier
+pub const BIS_BILLING_CREDITNOTE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote";
+
+/// PEPPOL BIS Billing 3.0 process identifier
+pub const BIS_BILLING_PROCESS: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+
+/// Customization of PEPPOL International (PINT) billing documents
+///
+/// Jurisdiction specialisations append to it, e.g. `urn:peppol:pint:billing-1@jp-1`.
+pub const PINT_CUSTOMIZATION: &str = "urn:peppol:pint:billing-1";
+/// PINT invoice document identifier, without a jurisdiction specialisation
+pub const PINT_INVOICE: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##urn:peppol:pint:billing-1::2.1";
+/// PINT credit note document identifier, without a jurisdiction specialisation
+pub const PINT_CREDITNOTE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote##urn:peppol:pint:billing-1::2.1";
+/// PINT billing process identifier
+pub const PINT_PROCESS: &str = "urn:peppol:bis:billing";
+
+/// The document types registered for the process `process_id`
+///
+/// Answers questions like "which documents does this participant accept for
+/// Billing 3.0?" ([`BIS_BILLING_PROCESS`]). Only the process identifier value
+/// is compared, not its scheme.
+pub fn documents_for_process<'a>(document_types: &'a [DocumentType], process_id: &str) -> Vec<&'a DocumentType> {
+    document_types
+        .iter()
+        .filter(|document_type| document_type.processes.iter().any(|process| process.value == process_id))
+        .collect()
+}
+
+/// Readable names for document identifiers, by root namespace and local name
+const DOCUMENT_NAMES: &[(&str, &str)] = &[
+    ("urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice", "Invoice"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote", "Credit Note"),
+    ("urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100::CrossIndustryInvoice", "Invoice (CII)"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:Order-2::Order", "Order"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:OrderResponse-2::OrderResponse", "Order Response"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:OrderChange-2::OrderChange", "Order Change"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:OrderCancellation-2::OrderCancellation", "Order Cancellation"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:DespatchAdvice-2::DespatchAdvice", "Despatch Advice"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:ReceiptAdvice-2::ReceiptAdvice", "Receipt Advice"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:Catalogue-2::Catalogue", "Catalogue"),
+    ("urn:oasis:names:specification:ubl:schema:xsd:ApplicationResponse-2::ApplicationResponse", "Application Response"),
+];
+
+/// `ApplicationResponse` is used for several messages, told apart by their customization
+const APPLICATION_RESPONSE_NAMES: &[(&str, &str)] = &[
+    ("urn:fdc:peppol.eu:poacc:trns:mlr:3", "Message Level Response"),
+    ("urn:fdc:peppol.eu:poacc:trns:invoice_response:3", "Invoice Response"),
+    ("urn:fdc:peppol.eu:poacc:trns:catalogue_response:3", "Catalogue Response"),
+];
+
+/// A readable name for a document identifier, e.g. `Invoice` or `Order Response`
+///
+/// Accepts the identifier with or without its customization (after `##`).
+/// Returns `None` for identifiers that aren't in the built-in list.
+pub fn document_name(identifier: &str) -> Option<&'static str> {
+    let (root, customization) = identifier.split_once("##").unwrap_or((identifier, ""));
+
+    if root == "urn:oasis:names:specification:ubl:schema:xsd:ApplicationResponse-2::ApplicationResponse" {
+        let name = APPLICATION_RESPONSE_NAMES
+            .iter()
+            .find(|(prefix, _)| customization.starts_with(prefix))
+            .map(|(_, name)| *name);
+        if name.is_some() {
+            return name;
+        }
+    }
+
+    DOCUMENT_NAMES
+        .iter()
+        .find(|(known, _)| *known == root)
+        .map(|(_, name)| *name)
+}
+
+impl DocumentType {
+    /// The scheme and value of this document type as an identifier
+    pub fn identifier(&self) -> DocumentTypeIdentifier {
+        DocumentTypeIdentifier {
+            scheme: self.scheme.clone(),
+            value: self.value.clone(),
+        }
+    }
+
+    /// A readable name for this document type; see [`document_name`]
+    pub fn name(&self) -> Option<&'static str> {
+        document_name(&self.value)
+    }
+
+    /// Whether any endpoint for this document type is active at `at`; see [`Endpoint::is_active`]
+    pub fn is_active(&self, at: OffsetDateTime) -> bool {
+        self.endpoints.iter().any(|endpoint| endpoint.is_active(at))
+    }
+}
+
+/// The document types that can be received right now
+///
+/// Drops document types whose every endpoint is not yet active or has
+/// expired, so a registration with a future activation date isn't reported
+/// as usable. Endpoints without dates count as always active.
+pub fn active_document_types(document_types: &[DocumentType]) -> Vec<&DocumentType> {
+    let now = OffsetDateTime::now_utc();
+    document_types
+        .iter()
+        .filter(|document_type| document_type.is_active(now))
+        .collect()
+}
+
+/// Pair each document type with its readable name, if it has one
+///
+/// Unknown document types are kept, with `None` as their name.
+pub fn with_names(document_types: Vec<DocumentType>) -> Vec<(DocumentType, Option<&'static str>)> {
+    document_types
+        .into_iter()
+        .map(|document_type| {
+            let name = document_type.name();
+            (document_type, name)
+        })
+        .collect()
+}
@@ -0,0 +1,100 @@
+//! DNS-over-HTTPS (RFC 8484) queries used by the SML step.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::lookup::Lookup;
+use hickory_resolver::lookup_ip::LookupIp;
+use hickory_resolver::proto::op::{Message, Query, ResponseCode};
+use hickory_resolver::proto::rr::{Name, RecordType};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+
+use crate::config::LookupConfig;
+use crate::error::PeppolError;
+use crate::http;
+
+/// Media type of DNS messages sent to and received from a DoH resolver
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// Sends DNS queries to a DoH resolver with the configured HTTP client
+///
+/// The HTTP client carries the proxy, TLS and `User-Agent` settings from the
+/// [`LookupConfig`], so the SML step works wherever SMP requests do.
+#[derive(Debug, Clone)]
+pub(crate) struct DohResolver {
+    client: reqwest::Client,
+    url: String,
+    timeout: Duration,
+}
+
+impl DohResolver {
+    /// A resolver for the DoH URL in `config`, if it sets one
+    pub(crate) fn new(config: &LookupConfig) -> Result<Option<Self>, PeppolError> {
+        let Some(url) = &config.dns_over_https else {
+            return Ok(None);
+        };
+        Ok(Some(DohResolver {
+            client: http::client(config)?,
+            url: url.clone(),
+            timeout: config.dns_timeout,
+        }))
+    }
+
+    /// Query A and AAAA records for `name` together, like the DNS resolver does
+    ///
+    /// Returns `Ok(None)` if the name has neither.
+    pub(crate) async fn lookup_ip(&self, name: &str) -> Result<Option<LookupIp>, PeppolError> {
+        let ipv4 = self.lookup(name, RecordType::A).await?;
+        let ipv6 = self.lookup(name, RecordType::AAAA).await?;
+        let lookup = match (ipv4, ipv6) {
+            (Some(ipv4), Some(ipv6)) => {
+                let records = [ipv4.records(), ipv6.records()].concat();
+                Lookup::new_with_deadline(ipv4.query().clone(), records.into(), ipv4.valid_until().min(ipv6.valid_until()))
+            }
+            (Some(lookup), None) | (None, Some(lookup)) => lookup,
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(lookup.into()))
+    }
+
+    /// Query `record_type` records for `name`
+    ///
+    /// Returns `Ok(None)` for NXDOMAIN and for names without records of that
+    /// type. The answer, CNAMEs included, is valid for its shortest TTL.
+    pub(crate) async fn lookup(&self, name: &str, record_type: RecordType) -> Result<Option<Lookup>, PeppolError> {
+        let failed = |reason: String| PeppolError::Dns(format!("DNS-over-HTTPS query to {} failed: {}", self.url, reason));
+
+        let query = Query::query(Name::from_str(name).map_err(|err| failed(err.to_string()))?, record_type);
+        let mut request = Message::new();
+        // RFC 8484 asks for ID 0, so HTTP caches can share identical queries
+        request.set_id(0).set_recursion_desired(true).add_query(query.clone());
+        let body = request.to_vec().map_err(|err| failed(err.to_string()))?;
+
+        let response = self
+            .client
+            .post(&self.url)
+            .timeout(self.timeout)
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .header(ACCEPT, DNS_MESSAGE)
+            .body(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| failed(err.to_string()))?;
+        let bytes = response.bytes().await.map_err(|err| failed(err.to_string()))?;
+        let response = Message::from_vec(&bytes).map_err(|err| failed(err.to_string()))?;
+
+        match response.response_code() {
+            ResponseCode::NoError => {}
+            ResponseCode::NXDomain => return Ok(None),
+            code => return Err(failed(code.to_string())),
+        }
+        let records = response.answers();
+        if !records.iter().any(|record| record.record_type() == record_type) {
+            return Ok(None);
+        }
+        let ttl = records.iter().map(|record| record.ttl()).min().unwrap_or_default();
+        let valid_until = Instant::now() + Duration::from_secs(ttl.into());
+        Ok(Some(Lookup::new_with_deadline(query, records.into(), valid_until)))
+    }
+}
@@ -0,0 +1,72 @@
+//! Transport endpoint details from an SMP's signed service metadata.
+
+use time::{Duration, OffsetDateTime};
+
+use crate::certificate::{Certificate, CertificateStatus, DEFAULT_EXPIRY_WARNING};
+
+/// Transport profiles in use on the PEPPOL network
+///
+/// AS4 is current; the AS2 and START profiles are deprecated but may still be
+/// published. An endpoint with any other profile gets a
+/// [`LookupWarning::UnknownTransportProfile`](crate::warning::LookupWarning::UnknownTransportProfile).
+pub const KNOWN_TRANSPORT_PROFILES: &[&str] = &[
+    "peppol-transport-as4-v2_0",
+    "bdxr-transport-ebms3-as4-v1p0",
+    "busdox-transport-as2-ver2p0",
+    "busdox-transport-as2-ver1p0",
+    "busdox-transport-start",
+];
+
+/// Where and how a participant receives a specific document type
+///
+/// Parsed from the `Endpoint` element of a `SignedServiceMetadata` response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Endpoint {
+    /// URL of the receiving access point (e.g. the AS4 endpoint)
+    pub url: String,
+    /// Transport profile, e.g. `peppol-transport-as4-v2_0`
+    pub transport_profile: String,
+    /// When the service became (or becomes) active, as published by the SMP
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339::option"))]
+    pub service_activation_date: Option<OffsetDateTime>,
+    /// When the service expires, as published by the SMP
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339::option"))]
+    pub service_expiration_date: Option<OffsetDateTime>,
+    /// The access point's certificate, if the SMP published one
+    pub certificate: Option<Certificate>,
+    /// Whether the receiver requires documents to carry a business level signature
+    pub require_business_level_signature: Option<bool>,
+    /// The minimum authentication level the receiver requires of senders
+    pub minimum_authentication_level: Option<String>,
+    /// Where to reach the access point's technical support, usually a URL or email address
+    pub technical_contact_url: Option<String>,
+    /// Where to find technical information about the access point
+    pub technical_information_url: Option<String>,
+}
+
+impl Endpoint {
+    /// Whether the service is active at `at`
+    ///
+    /// The service is active from its activation date (inclusive) until its
+    /// expiration date (exclusive). A missing date doesn't limit the service,
+    /// so an endpoint without dates is always active.
+    pub fn is_active(&self, at: OffsetDateTime) -> bool {
+        self.service_activation_date.is_none_or(|activation| activation <= at)
+            && self.service_expiration_date.is_none_or(|expiration| at < expiration)
+    }
+
+    /// The status of the access point's certificate right now
+    ///
+    /// Certificates expiring within [`DEFAULT_EXPIRY_WARNING`] (30 days) are
+    /// reported as [`CertificateStatus::ExpiringSoon`]. Returns `None` if the
+    /// SMP didn't publish a certificate.
+    pub fn certificate_status(&self) -> Option<CertificateStatus> {
+        self.certificate_status_at(OffsetDateTime::now_utc(), DEFAULT_EXPIRY_WARNING)
+    }
+
+    /// The status of the access point's certificate at `at`, warning `warn_within` before expiry
+    pub fn certificate_status_at(&self, at: OffsetDateTime, warn_within: Duration) -> Option<CertificateStatus> {
+        self.certificate.as_ref().map(|certificate| certificate.status(at, warn_within))
+    }
+}
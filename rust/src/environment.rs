@@ -0,0 +1,79 @@
+//! SML environments and the DNS domains they map to.
+
+use std::str::FromStr;
+
+use crate::error::PeppolError;
+
+/// Production SML domain
+pub const PRODUCTION_SML_DOMAIN: &str = "edelivery.tech.ec.europa.eu";
+
+/// Test (acceptance) SML domain
+pub const TEST_SML_DOMAIN: &str = "acc.edelivery.tech.ec.europa.eu";
+
+/// The PEPPOL network a participant is looked up in
+///
+/// Production and test participants are registered in separate SMLs, so a
+/// participant that exists in one will not resolve in the other.
+///
+/// `Custom` points every lookup at a private SML, e.g. a local one for CI.
+/// Participant hostnames are built under its domain in the same way
+/// (`b-<hash>.<scheme>.<domain>`) and resolved through the same DNS settings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum SmlEnvironment {
+    /// The production PEPPOL network
+    #[default]
+    Production,
+    /// The PEPPOL test (acceptance) network
+    Test,
+    /// A private SML under `domain`, e.g. `localhost.sml.internal`
+    Custom {
+        /// The SML DNS domain, without a trailing dot
+        domain: String,
+    },
+}
+
+impl SmlEnvironment {
+    /// Returns the SML DNS domain for this environment
+    pub fn domain(&self) -> &str {
+        match self {
+            SmlEnvironment::Production => PRODUCTION_SML_DOMAIN,
+            SmlEnvironment::Test => TEST_SML_DOMAIN,
+            SmlEnvironment::Custom { domain } => domain,
+        }
+    }
+
+    /// A private SML under `domain`
+    ///
+    /// Surrounding whitespace and a trailing dot are removed and the domain is
+    /// lowercased, matching how participant hostnames are built.
+    pub fn custom(domain: &str) -> Self {
+        SmlEnvironment::Custom {
+            domain: domain.trim().trim_end_matches('.').to_lowercase(),
+        }
+    }
+}
+
+impl FromStr for SmlEnvironment {
+    type Err = PeppolError;
+
+    /// Parse an environment name, ignoring case and surrounding whitespace
+    ///
+    /// `production` (or `prod`) and `test` (or `acc`, `acceptance`) are
+    /// recognized. Custom SMLs have no name; use [`SmlEnvironment::custom`].
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "production" | "prod" => Ok(SmlEnvironment::Production),
+            "test" | "acc" | "acceptance" => Ok(SmlEnvironment::Test),
+            _ => Err(PeppolError::InvalidEnvironment(name.to_string())),
+        }
+    }
+}
+
+impl TryFrom<&str> for SmlEnvironment {
+    type Error = PeppolError;
+
+    /// See [`SmlEnvironment::from_str`]
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
@@ -0,0 +1,137 @@
+//! Error type returned by the lookup functions.
+
+use std::error::Error;
+use std::fmt;
+
+/// Errors that can occur while looking up a PEPPOL participant
+///
+/// Each variant corresponds to a step of the lookup, so callers can `match`
+/// on it to decide whether a retry makes sense.
+#[derive(Debug)]
+pub enum PeppolError {
+    /// The SML DNS lookup failed for a reason other than a missing record
+    Dns(String),
+    /// The HTTP request to the SMP failed, including 4xx and 5xx responses
+    /// other than 404; `reqwest::Error::status` tells them apart
+    #[cfg(feature = "lookup")]
+    Http(reqwest::Error),
+    /// The SMP answered 404 for this URL: it has no such service group or service metadata
+    NoServiceMetadata(String),
+    /// The SMP response could not be parsed
+    Xml(String),
+    /// An access point certificate could not be decoded
+    Certificate(String),
+    /// The signature on an SMP response is missing, invalid or untrusted
+    Signature(String),
+    /// The participant is not registered in the SML
+    NotAParticipant(String),
+    /// The participant identifier is malformed
+    InvalidIdentifier(String),
+    /// The name isn't one of the SML environments [`SmlEnvironment`](crate::environment::SmlEnvironment) parses
+    InvalidEnvironment(String),
+    /// The [`LookupConfig`](crate::config::LookupConfig) can't work as given,
+    /// e.g. signature verification without any trusted SMP CA certificates
+    Config(String),
+    /// The Peppol Directory response could not be parsed
+    Directory(String),
+    /// An SMP host resolved to a loopback, private or link-local address
+    ///
+    /// See [`LookupConfig::block_private_addresses`](crate::config::LookupConfig::block_private_addresses).
+    BlockedAddress(String),
+    /// A failure read back from a serialized result; only its message is kept
+    Deserialized(String),
+    /// The lookup ran out of its [`LookupConfig::time_budget`](crate::config::LookupConfig::time_budget)
+    DeadlineExceeded,
+}
+
+impl PeppolError {
+    /// Whether the same lookup might succeed if tried again later
+    ///
+    /// True for failures of the network or the SMP: DNS errors other than a
+    /// missing record, connection errors, timeouts (including an exhausted
+    /// time budget) and 5xx responses. False for answers that won't change on
+    /// a retry: an unregistered participant, a 404, other 4xx responses and
+    /// anything that failed to parse or verify.
+    ///
+    /// SMP requests are classified exactly as the lookups' own
+    /// [`RetryPolicy`](crate::config::RetryPolicy) classifies them, so an
+    /// error is only retryable here if the lookup would have retried it.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PeppolError::Dns(_) | PeppolError::DeadlineExceeded => true,
+            #[cfg(feature = "lookup")]
+            PeppolError::Http(err) => is_transient(err),
+            PeppolError::NoServiceMetadata(_)
+            | PeppolError::Xml(_)
+            | PeppolError::Certificate(_)
+            | PeppolError::Signature(_)
+            | PeppolError::NotAParticipant(_)
+            | PeppolError::InvalidIdentifier(_)
+            | PeppolError::InvalidEnvironment(_)
+            | PeppolError::Config(_)
+            | PeppolError::Directory(_)
+            | PeppolError::BlockedAddress(_)
+            | PeppolError::Deserialized(_) => false,
+        }
+    }
+}
+
+/// Whether a failed SMP request might succeed if sent again
+///
+/// Timeouts, connection errors, dropped connections and 5xx responses are.
+/// Both [`PeppolError::is_retryable`] and the lookups' retries use this.
+#[cfg(feature = "lookup")]
+pub(crate) fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.is_request() || err.status().is_some_and(is_transient_status)
+}
+
+/// Whether an SMP response with `status` might be different if requested again
+#[cfg(feature = "lookup")]
+pub(crate) fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+}
+
+impl fmt::Display for PeppolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeppolError::Dns(reason) => write!(f, "DNS lookup failed: {}", reason),
+            #[cfg(feature = "lookup")]
+            PeppolError::Http(err) => write!(f, "SMP request failed: {}", err),
+            PeppolError::NoServiceMetadata(url) => write!(f, "No service metadata at {}", url),
+            PeppolError::Xml(reason) => write!(f, "Invalid SMP response: {}", reason),
+            PeppolError::Certificate(reason) => write!(f, "Invalid certificate: {}", reason),
+            PeppolError::Signature(reason) => write!(f, "Signature verification failed: {}", reason),
+            PeppolError::NotAParticipant(participant_id) => {
+                write!(f, "Not a PEPPOL participant: {}", participant_id)
+            }
+            PeppolError::InvalidIdentifier(reason) => {
+                write!(f, "Invalid participant identifier: {}", reason)
+            }
+            PeppolError::InvalidEnvironment(name) => {
+                write!(f, "Unknown SML environment {:?}: expected production or test", name)
+            }
+            PeppolError::Config(reason) => write!(f, "Invalid configuration: {}", reason),
+            PeppolError::Directory(reason) => write!(f, "Invalid directory response: {}", reason),
+            PeppolError::BlockedAddress(reason) => write!(f, "Blocked private address: {}", reason),
+            PeppolError::Deserialized(message) => f.write_str(message),
+            PeppolError::DeadlineExceeded => f.write_str("Lookup exceeded its time budget"),
+        }
+    }
+}
+
+impl Error for PeppolError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "lookup")]
+            PeppolError::Http(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "lookup")]
+impl From<reqwest::Error> for PeppolError {
+    fn from(err: reqwest::Error) -> Self {
+        PeppolError::Http(err)
+    }
+}
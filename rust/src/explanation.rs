@@ -0,0 +1,59 @@
+//! A description of the steps a lookup would take, for dry runs.
+
+use std::fmt;
+
+use crate::participant::Participant;
+
+/// What a lookup would do for a participant, worked out without querying the SMP
+///
+/// Returned by [`explain`](crate::lookup::explain), which makes no network
+/// calls at all, and by [`explain_with_dns`](crate::lookup::explain_with_dns),
+/// which also does the SML step so [`smp_hostname`](Self::smp_hostname) is
+/// known. `Display` prints the steps in order, for documentation or for
+/// checking a configuration before any SMP is contacted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Explanation {
+    /// The participant being explained
+    pub participant: Participant,
+    /// The participant ID that is hashed: `icd:identifier`, lowercased
+    pub hashed_id: String,
+    /// Hex MD5 of [`hashed_id`](Self::hashed_id)
+    pub md5: String,
+    /// The `b-<md5>.<scheme>.<sml domain>` name queried in the SML
+    pub sml_hostname: String,
+    /// The SMP host the SML name points at, if DNS was queried
+    ///
+    /// Without it, the URLs below are built from
+    /// [`sml_hostname`](Self::sml_hostname), which is a CNAME for that host.
+    pub smp_hostname: Option<String>,
+    /// The service group URL requested first
+    pub smp_url: String,
+    /// The plain HTTP service group URL tried if the first one can't be reached
+    pub smp_fallback_url: Option<String>,
+    /// The service metadata URL for each document type asked about, in order
+    pub document_urls: Vec<String>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Participant: {}", self.participant)?;
+        writeln!(f, "1. MD5 of \"{}\": {}", self.hashed_id, self.md5)?;
+        writeln!(f, "2. SML query: {}", self.sml_hostname)?;
+        match &self.smp_hostname {
+            Some(smp_hostname) => writeln!(f, "   SMP host: {}", smp_hostname)?,
+            None => writeln!(f, "   SMP host: (not resolved; the CNAME target of the SML name)")?,
+        }
+        writeln!(f, "3. Service group: {}", self.smp_url)?;
+        if let Some(fallback) = &self.smp_fallback_url {
+            writeln!(f, "   falling back to: {}", fallback)?;
+        }
+        if !self.document_urls.is_empty() {
+            writeln!(f, "4. Service metadata:")?;
+            for url in &self.document_urls {
+                writeln!(f, "   - {}", url)?;
+            }
+        }
+        Ok(())
+    }
+}
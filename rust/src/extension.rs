@@ -0,0 +1,38 @@
+//! Extensions SMPs attach to service groups and service metadata.
+
+use time::OffsetDateTime;
+
+/// An `Extension` element from an SMP response
+///
+/// SMPs use extensions for vendor or network specific data. The content is
+/// kept as raw XML; OpenPeppol business cards are also parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Extension {
+    /// The `Extension` element as it appeared in the response
+    ///
+    /// Namespace prefixes declared on enclosing elements are not repeated, so
+    /// this isn't necessarily a standalone XML document.
+    pub xml: String,
+    /// The business card, if the extension holds an OpenPeppol business card
+    pub business_card: Option<BusinessCard>,
+}
+
+/// The registered business entity from an OpenPeppol business card
+///
+/// Found in SMP extensions, or fetched from the SMP's business card endpoint
+/// with [`business_card`](crate::lookup::business_card).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BusinessCard {
+    /// The registered business name
+    pub name: Option<String>,
+    /// Every registered name, e.g. one per language; `name` is the first
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub names: Vec<String>,
+    /// ISO 3166 country code, e.g. `NO`
+    pub country_code: Option<String>,
+    /// When the business entity was registered, if the card says
+    #[cfg_attr(feature = "serde", serde(default, with = "time::serde::rfc3339::option"))]
+    pub registration_date: Option<OffsetDateTime>,
+}
@@ -0,0 +1,26 @@
+//! Entry points for the `cargo fuzz` targets in `fuzz/`.
+//!
+//! Only compiled when fuzzing (`cargo fuzz` sets `--cfg fuzzing`), since the
+//! parsers themselves are private.
+
+use crate::signature::{verify_signature, TrustStore};
+use crate::xml;
+
+/// Run every SMP response parser over `data`, discarding the results
+///
+/// Malformed input must come back as an error from each of them.
+pub fn parse_smp_response(data: &[u8]) {
+    let Ok(response) = xml::decode(data) else {
+        return;
+    };
+    let _ = xml::parse_document_types(&response);
+    let _ = xml::parse_service_references(&response);
+    let _ = xml::parse_service_group_extensions(&response);
+    let _ = xml::parse_document_type(&response);
+    let _ = xml::parse_endpoint(&response);
+    let _ = xml::parse_endpoints(&response);
+    let _ = xml::parse_redirect(&response);
+    let _ = xml::parse_participant_list(&response);
+    let _ = xml::parse_business_card(&response);
+    let _ = verify_signature(&response, &TrustStore::new());
+}
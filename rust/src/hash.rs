@@ -0,0 +1,81 @@
+//! Hashing schemes used to turn a participant ID into an SML hostname.
+
+use data_encoding::BASE32_NOPAD;
+use md5::{Digest, Md5};
+use ring::digest::{digest, SHA256};
+
+use crate::participant::Participant;
+
+/// How the participant ID is hashed into a DNS name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HashScheme {
+    /// Legacy `b-<md5 hex>` hostnames, resolved as a CNAME to the SMP host
+    #[default]
+    Md5,
+    /// BDXL `<base32 sha256>` hostnames, resolved via a NAPTR record holding
+    /// the SMP URL
+    Sha256,
+    /// Try [`Md5`](Self::Md5) first and fall back to [`Sha256`](Self::Sha256)
+    /// if the SML has no MD5 record
+    ///
+    /// For networks at different stages of the BDXL migration; see
+    /// [`detect_smp_url`](crate::lookup::detect_smp_url) to learn which
+    /// scheme found the participant.
+    Auto,
+}
+
+impl HashScheme {
+    /// Returns the DNS name for `participant` in the given SML domain
+    ///
+    /// - MD5: `b-<hex md5 of icd:identifier>.<scheme>.<sml domain>`
+    /// - SHA-256: `<unpadded base32 sha256 of icd:identifier>.<scheme>.<sml domain>`
+    ///
+    /// The scheme is the participant's identifier scheme, normally
+    /// `iso6523-actorid-upis`.
+    ///
+    /// Both variants hash the lowercased participant ID, as the PEPPOL and
+    /// BDXL specifications require. [`Auto`](Self::Auto) gives the MD5
+    /// hostname, which it queries first.
+    pub fn hostname(&self, participant: &Participant, sml_domain: &str) -> String {
+        match self {
+            HashScheme::Md5 | HashScheme::Auto => participant_hostname(
+                participant.icd(),
+                participant.identifier(),
+                participant.scheme(),
+                sml_domain,
+            ),
+            HashScheme::Sha256 => {
+                let participant_id = format!("{}:{}", participant.icd(), participant.identifier());
+                let hash = digest(&SHA256, participant_id.to_lowercase().as_bytes());
+                format!(
+                    "{}.{}.{}",
+                    BASE32_NOPAD.encode(hash.as_ref()).to_lowercase(),
+                    participant.scheme(),
+                    sml_domain
+                )
+            }
+        }
+    }
+}
+
+/// Hex MD5 of the participant ID `icd:identifier`, as used in SML hostnames
+///
+/// The ID is lowercased before hashing, as PEPPOL requires, so identifiers
+/// that differ only in case give the same hash. This is only the hash; no
+/// validation or DNS is involved.
+pub fn participant_md5(icd: &str, identifier: &str) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(format!("{}:{}", icd, identifier).to_lowercase().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// The SML hostname `b-<md5>.<scheme>.<sml domain>` for a participant
+///
+/// Computed without any DNS queries, so it can be precomputed or compared with
+/// what a DNS tool shows. For example `0192`, `921605900`,
+/// `iso6523-actorid-upis` and `edelivery.tech.ec.europa.eu` give
+/// `b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.edelivery.tech.ec.europa.eu`.
+/// The scheme is lowercased, like [`Participant`] does.
+pub fn participant_hostname(icd: &str, identifier: &str, scheme: &str, sml_domain: &str) -> String {
+    format!("b-{}.{}.{}", participant_md5(icd, identifier), scheme.trim().to_lowercase(), sml_domain)
+}
@@ -0,0 +1,349 @@
+//! HTTP requests to SMPs.
+
+use std::time::{Duration, Instant};
+
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+
+use crate::config::LookupConfig;
+#[cfg(feature = "blocking")]
+use crate::dns::check_url_host;
+use crate::dns::check_url_host_async;
+use crate::error::{is_transient, is_transient_status, PeppolError};
+use crate::telemetry;
+use crate::xml;
+
+/// Maximum number of HTTP redirects followed for one SMP request
+const MAX_REDIRECTS: usize = 5;
+
+/// The body of an SMP response, with the headers callers are interested in
+#[derive(Debug)]
+pub(crate) struct Response {
+    pub(crate) body: String,
+    /// The `Server` header, which usually names the SMP software
+    pub(crate) server: Option<String>,
+    /// The next page of a paginated response, from a `Link: <url>; rel="next"` header
+    pub(crate) next: Option<String>,
+    /// How long the response may be cached, from `Cache-Control` or `Expires`
+    pub(crate) max_age: Option<Duration>,
+}
+
+/// Build a blocking HTTP client with the User-Agent, timeouts, pool, proxy and TLS settings (root certificates included) from `config`
+///
+/// The client doesn't follow redirects; [`get_blocking`] does, after
+/// checking each target like SMP `Redirect`s are.
+#[cfg(feature = "blocking")]
+pub(crate) fn blocking_client(config: &LookupConfig) -> Result<reqwest::blocking::Client, PeppolError> {
+    let mut builder = reqwest::blocking::Client::builder()
+        .user_agent(config.user_agent.as_str())
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.request_timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(config.pool_idle_timeout);
+    // Without an explicit proxy, reqwest picks up HTTP_PROXY and HTTPS_PROXY
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    for certificate in &config.root_certificates {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_der(certificate.der())?);
+    }
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    let builder = builder.danger_accept_invalid_certs(config.danger_accept_invalid_certs);
+    // rustls wins if both backends are enabled, since it has to be asked for explicitly
+    #[cfg(feature = "rustls")]
+    let builder = builder.use_rustls_tls();
+    Ok(builder.build()?)
+}
+
+/// Build an async HTTP client with the User-Agent, timeouts, pool, proxy and TLS settings (root certificates included) from `config`
+///
+/// Like [`blocking_client`], it leaves redirects to [`get`].
+pub(crate) fn client(config: &LookupConfig) -> Result<reqwest::Client, PeppolError> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(config.user_agent.as_str())
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.request_timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(config.pool_idle_timeout);
+    // Without an explicit proxy, reqwest picks up HTTP_PROXY and HTTPS_PROXY
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    for certificate in &config.root_certificates {
+        builder = builder.add_root_certificate(reqwest::Certificate::from_der(certificate.der())?);
+    }
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    let builder = builder.danger_accept_invalid_certs(config.danger_accept_invalid_certs);
+    // rustls wins if both backends are enabled, since it has to be asked for explicitly
+    #[cfg(feature = "rustls")]
+    let builder = builder.use_rustls_tls();
+    Ok(builder.build()?)
+}
+
+/// Perform an HTTP GET with a blocking client, retrying transient failures
+///
+/// Redirects are followed up to [`MAX_REDIRECTS`] times. With
+/// [`LookupConfig::block_private_addresses`], a target resolving to a private
+/// address fails with `PeppolError::BlockedAddress` before it is requested,
+/// so an SMP can't bounce a lookup to an internal service.
+///
+/// If no connection can be made to `url` (including TLS handshake failures),
+/// each attempt falls back to `fallback`. An SMP that answers is not asked
+/// again at the fallback URL.
+///
+/// Only 2xx bodies are returned. A 404 is reported as
+/// `PeppolError::NoServiceMetadata`, any other 4xx or 5xx as a
+/// `PeppolError::Http` carrying the status.
+///
+/// With a `deadline`, each attempt's timeout is cut to the time left, and
+/// running out (or a retry that would wait past it) fails with
+/// `PeppolError::DeadlineExceeded`.
+#[cfg(feature = "blocking")]
+pub(crate) fn get_blocking(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    fallback: Option<&str>,
+    config: &LookupConfig,
+    deadline: Option<Instant>,
+) -> Result<Response, PeppolError> {
+    let started = Instant::now();
+    let result = send_blocking(client, url, fallback, config, deadline);
+    telemetry::smp_request(started, &result);
+    result
+}
+
+#[cfg(feature = "blocking")]
+fn send_blocking(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    fallback: Option<&str>,
+    config: &LookupConfig,
+    deadline: Option<Instant>,
+) -> Result<Response, PeppolError> {
+    let retry = &config.retry;
+    let send = |url: &str| -> Result<reqwest::Result<reqwest::blocking::Response>, PeppolError> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let request = match deadline {
+                Some(deadline) => client.get(&url).timeout(remaining(deadline)?),
+                None => client.get(&url),
+            };
+            let response = match request.send() {
+                Ok(response) => response,
+                Err(err) => return Ok(Err(err)),
+            };
+            let Some(target) = redirect_target(response.status(), response.headers(), response.url()) else {
+                return Ok(Ok(response));
+            };
+            debug!(%url, %target, "following HTTP redirect");
+            check_url_host(&target, config)?;
+            url = target;
+        }
+        Err(too_many_redirects(&url))
+    };
+
+    let mut attempt = 1;
+    loop {
+        let result = match (send(url)?, fallback) {
+            (Err(err), Some(fallback)) if err.is_connect() => {
+                warn!(%url, %fallback, error = %err, "SMP connection failed, falling back");
+                send(fallback)?
+            }
+            (result, _) => result,
+        };
+
+        let transient = match &result {
+            Ok(response) => is_transient_status(response.status()),
+            Err(err) => is_transient(err),
+        };
+        if !transient || attempt >= retry.max_attempts {
+            let response = result?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(PeppolError::NoServiceMetadata(response.url().to_string()));
+            }
+            // Error pages are not SMP responses, so other 4xx and 5xx never reach the parser
+            let response = response.error_for_status()?;
+            let server = server_header(response.headers());
+            let next = next_link(response.headers(), response.url());
+            let max_age = max_age(response.headers());
+            return Ok(Response { body: xml::decode(&response.bytes()?)?, server, next, max_age });
+        }
+
+        let backoff = retry.backoff(attempt);
+        if deadline.is_some_and(|deadline| !remaining(deadline).is_ok_and(|remaining| remaining > backoff)) {
+            return Err(PeppolError::DeadlineExceeded);
+        }
+        warn!(%url, attempt, ?backoff, "transient SMP failure, retrying");
+        std::thread::sleep(backoff);
+        attempt += 1;
+    }
+}
+
+/// Async variant of [`get_blocking`]
+pub(crate) async fn get(
+    client: &reqwest::Client,
+    url: &str,
+    fallback: Option<&str>,
+    config: &LookupConfig,
+) -> Result<Response, PeppolError> {
+    let started = Instant::now();
+    let result = send(client, url, fallback, config).await;
+    telemetry::smp_request(started, &result);
+    result
+}
+
+async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    fallback: Option<&str>,
+    config: &LookupConfig,
+) -> Result<Response, PeppolError> {
+    let retry = &config.retry;
+    let mut attempt = 1;
+    loop {
+        let result = match (send_following_redirects(client, url, config).await?, fallback) {
+            (Err(err), Some(fallback)) if err.is_connect() => {
+                warn!(%url, %fallback, error = %err, "SMP connection failed, falling back");
+                send_following_redirects(client, fallback, config).await?
+            }
+            (result, _) => result,
+        };
+
+        let transient = match &result {
+            Ok(response) => is_transient_status(response.status()),
+            Err(err) => is_transient(err),
+        };
+        if !transient || attempt >= retry.max_attempts {
+            let response = result?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(PeppolError::NoServiceMetadata(response.url().to_string()));
+            }
+            // Error pages are not SMP responses, so other 4xx and 5xx never reach the parser
+            let response = response.error_for_status()?;
+            let server = server_header(response.headers());
+            let next = next_link(response.headers(), response.url());
+            let max_age = max_age(response.headers());
+            return Ok(Response { body: xml::decode(&response.bytes().await?)?, server, next, max_age });
+        }
+
+        let backoff = retry.backoff(attempt);
+        warn!(%url, attempt, ?backoff, "transient SMP failure, retrying");
+        tokio::time::sleep(backoff).await;
+        attempt += 1;
+    }
+}
+
+/// GET `url`, following redirects to targets `config` lets through
+///
+/// The outer error is a blocked or endless redirect, the inner one a failed request.
+async fn send_following_redirects(
+    client: &reqwest::Client,
+    url: &str,
+    config: &LookupConfig,
+) -> Result<reqwest::Result<reqwest::Response>, PeppolError> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let response = match client.get(&url).send().await {
+            Ok(response) => response,
+            Err(err) => return Ok(Err(err)),
+        };
+        let Some(target) = redirect_target(response.status(), response.headers(), response.url()) else {
+            return Ok(Ok(response));
+        };
+        debug!(%url, %target, "following HTTP redirect");
+        check_url_host_async(&target, config).await?;
+        url = target;
+    }
+    Err(too_many_redirects(&url))
+}
+
+/// Where a redirect response points, resolved against the URL it came from
+///
+/// `None` for responses that aren't redirects, or that don't say where to.
+fn redirect_target(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, url: &reqwest::Url) -> Option<String> {
+    if !matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = headers.get(reqwest::header::LOCATION)?.to_str().ok()?;
+    url.join(location.trim()).ok().map(String::from)
+}
+
+fn too_many_redirects(url: &str) -> PeppolError {
+    PeppolError::Xml(format!("more than {} HTTP redirects, last to {}", MAX_REDIRECTS, url))
+}
+
+/// The time left until `deadline`, or `PeppolError::DeadlineExceeded` if it has passed
+#[cfg(feature = "blocking")]
+pub(crate) fn remaining(deadline: Instant) -> Result<Duration, PeppolError> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or(PeppolError::DeadlineExceeded)
+}
+
+fn server_header(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let server = headers.get(reqwest::header::SERVER)?.to_str().ok()?.trim();
+    (!server.is_empty()).then(|| server.to_string())
+}
+
+/// How long a response may be cached, per RFC 9111
+///
+/// `Cache-Control: no-store`, `no-cache` and `max-age` take precedence over
+/// `Expires`, which is measured from the `Date` header if there is one. An
+/// `Expires` that can't be parsed means the response is already stale.
+/// Returns `None` if neither header is present.
+fn max_age(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let directives = headers
+        .get_all(reqwest::header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase());
+    let mut max_age = None;
+    for directive in directives {
+        if directive == "no-store" || directive == "no-cache" {
+            return Some(Duration::ZERO);
+        }
+        if let Some(seconds) = directive.strip_prefix("max-age=") {
+            max_age = Some(seconds.trim_matches('"').parse().map_or(Duration::ZERO, Duration::from_secs));
+        }
+    }
+    if max_age.is_some() {
+        return max_age;
+    }
+
+    let http_date = |name| {
+        let value = headers.get(name)?.to_str().ok()?;
+        OffsetDateTime::parse(value.trim(), &Rfc2822).ok()
+    };
+    headers.get(reqwest::header::EXPIRES)?;
+    let Some(expires) = http_date(reqwest::header::EXPIRES) else {
+        return Some(Duration::ZERO);
+    };
+    let date = http_date(reqwest::header::DATE).unwrap_or_else(OffsetDateTime::now_utc);
+    Some((expires - date).try_into().unwrap_or(Duration::ZERO))
+}
+
+/// The target of a `Link` header with `rel="next"`, resolved against the response URL
+fn next_link(headers: &reqwest::header::HeaderMap, url: &reqwest::Url) -> Option<String> {
+    headers
+        .get_all(reqwest::header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+            let is_next = params.split(';').any(|param| {
+                param.trim().strip_prefix("rel=").is_some_and(|rel| rel.trim_matches('"').split_whitespace().any(|rel| rel == "next"))
+            });
+            if is_next {
+                url.join(target).ok().map(String::from)
+            } else {
+                None
+            }
+        })
+}
@@ -0,0 +1,189 @@
+//! PEPPOL uses two key services to enable document exchange:
+//!
+//! 1. SML (Service Metadata Locator):
+//!    - Acts as a DNS-based directory service
+//!    - Maps a participant's ID to their SMP provider
+//!    - Uses DNS lookup to find where a participant's metadata is hosted
+//!    - Similar to how email's MX records help find mail servers
+//!
+//! 2. SMP (Service Metadata Publisher):
+//!    - Hosts metadata about a participant's capabilities
+//!    - Tells you what document types they can receive
+//!    - Provides technical details needed for sending documents
+//!    - Acts like a participant's business card in the network
+//!
+//! This crate demonstrates how to:
+//! 1. Use SML to find where a participant's metadata is hosted
+//! 2. Query their SMP to discover what documents they can receive
+//! 3. Check for PEPPOL BIS Billing 3.0 support
+//! 4. Fetch the endpoint used to deliver a specific document type
+//!
+//! With the default `blocking` feature:
+//!
+//! ```no_run
+//! # #[cfg(feature = "blocking")] {
+//! use peppol_lookup::lookup::{sml_lookup, smp_lookup};
+//! use peppol_lookup::{Participant, SmlEnvironment};
+//!
+//! let participant: Participant = "0192:921605900".parse()?;
+//! let record = sml_lookup(&participant, SmlEnvironment::Production)?;
+//! let document_types = smp_lookup(&record.hostname, &participant)?;
+//! println!("{:?}", document_types);
+//! # }
+//! # Ok::<(), peppol_lookup::PeppolError>(())
+//! ```
+//!
+//! All lookup functions return [`PeppolError`] on failure, so callers can tell
+//! a participant that isn't registered apart from an SMP that is unreachable.
+//!
+//! Async callers can use `lookup::smp_lookup_async` instead, and disable the
+//! default `blocking` feature to avoid pulling in reqwest's blocking runtime.
+//!
+//! The networking code (reqwest, hickory-resolver and tokio) sits behind the
+//! default `lookup` feature. With `default-features = false`, only the
+//! identifier types ([`Participant`], [`DocumentTypeIdentifier`], ...), the
+//! result types and the hostname hashing ([`participant_hostname`],
+//! [`HashScheme`]) are compiled, for callers that compute SML hostnames
+//! without doing any lookups:
+//!
+//! ```
+//! use peppol_lookup::{participant_hostname, Participant};
+//!
+//! let participant: Participant = "0192:921605900".parse()?;
+//! assert_eq!(
+//!     participant_hostname(participant.icd(), participant.identifier(), participant.scheme(), "edelivery.tech.ec.europa.eu"),
+//!     "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.edelivery.tech.ec.europa.eu",
+//! );
+//! # Ok::<(), peppol_lookup::PeppolError>(())
+//! ```
+//!
+//! # Cancellation
+//!
+//! The async functions do nothing on their own between polls, so cancelling a
+//! lookup is dropping its future, e.g. with `tokio::time::timeout`, a
+//! `tokio::select!` on your own cancellation signal, or by aborting the task
+//! that runs it. No tasks are spawned that outlive the future: dropping it
+//! closes the in-flight HTTP connection, drops the DNS resolver created for
+//! the call along with its pending queries, and skips any retry backoff still
+//! to come. Nothing is left half-written, since the functions only read.
+//!
+//! ```no_run
+//! # #[cfg(feature = "lookup")]
+//! # async fn example(participant: peppol_lookup::Participant, cancel: impl std::future::Future<Output = ()>) {
+//! use peppol_lookup::{lookup_async, SmlEnvironment};
+//!
+//! tokio::select! {
+//!     document_types = lookup_async(&participant, SmlEnvironment::Production) => println!("{:?}", document_types),
+//!     () = cancel => println!("cancelled"),
+//! }
+//! # }
+//! ```
+
+#[cfg(feature = "lookup")]
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "blocking")]
+pub mod batch;
+#[cfg(feature = "lookup")]
+pub mod cache;
+pub mod certificate;
+#[cfg(feature = "blocking")]
+pub mod client;
+#[cfg(feature = "lookup")]
+pub mod config;
+#[cfg(feature = "directory")]
+pub mod directory;
+pub mod diff;
+pub mod document;
+#[cfg(feature = "lookup")]
+mod dns;
+#[cfg(feature = "lookup")]
+mod doh;
+pub mod endpoint;
+pub mod environment;
+pub mod error;
+pub mod explanation;
+pub mod extension;
+#[cfg(all(fuzzing, feature = "lookup"))]
+#[doc(hidden)]
+pub mod fuzz;
+pub mod hash;
+#[cfg(feature = "lookup")]
+mod http;
+#[cfg(feature = "lookup")]
+pub mod lookup;
+pub mod metadata;
+pub mod participant;
+pub mod prelude;
+pub mod redirect;
+#[cfg(feature = "lookup")]
+pub mod resolver;
+pub mod service_group;
+#[cfg(feature = "lookup")]
+pub mod signature;
+pub mod sml;
+#[cfg(feature = "blocking")]
+pub mod smp_client;
+#[cfg(feature = "lookup")]
+mod telemetry;
+pub mod warning;
+#[cfg(feature = "lookup")]
+mod xml;
+
+#[cfg(feature = "blocking")]
+pub use batch::{lookup_many, lookup_many_unordered, BatchIter};
+#[cfg(feature = "lookup")]
+pub use cache::{CacheStats, CachedLookup, LookupCache};
+pub use certificate::{Certificate, CertificateStatus, DEFAULT_EXPIRY_WARNING};
+#[cfg(feature = "blocking")]
+pub use client::{LookupClient, LookupClientBuilder};
+#[cfg(feature = "lookup")]
+pub use config::{LookupConfig, RetryPolicy, SmpScheme, DEFAULT_POOL_IDLE_TIMEOUT, DEFAULT_USER_AGENT};
+#[cfg(feature = "directory")]
+pub use directory::{DirectoryMatch, DirectoryQuery};
+pub use diff::{CertificateChange, EndpointChange, MetadataDiff};
+pub use document::{
+    active_document_types, document_name, documents_for_process, with_names, DocumentType, DocumentTypeIdentifier, ProcessId,
+};
+pub use endpoint::{Endpoint, KNOWN_TRANSPORT_PROFILES};
+pub use environment::SmlEnvironment;
+pub use error::PeppolError;
+pub use explanation::Explanation;
+pub use extension::{BusinessCard, Extension};
+pub use hash::{participant_hostname, participant_md5, HashScheme};
+pub use metadata::{DocumentMetadata, ParticipantMetadata};
+pub use participant::Participant;
+pub use redirect::Redirect;
+#[cfg(feature = "lookup")]
+pub use resolver::{DnsResolver, Resolver};
+pub use service_group::ServiceGroup;
+#[cfg(feature = "lookup")]
+pub use signature::TrustStore;
+pub use sml::SmlRecord;
+#[cfg(feature = "blocking")]
+pub use smp_client::{HttpSmpClient, SmpClient, SmpResponse};
+pub use warning::LookupWarning;
+
+#[cfg(feature = "lookup")]
+pub use lookup::{
+    business_card_async, business_card_with_config_async, detect_smp_url, detect_smp_url_with_config, discover_smp_url, discover_smp_url_with_config, document_types_async, document_types_set_async,
+    document_types_set_with_config_async, document_types_with_config_async, explain, explain_with_config, explain_with_dns, explain_with_dns_async,
+    find_document_type_async, find_document_type_with_config_async,
+    get_endpoint_async, get_endpoint_with_config_async, get_endpoints_async, get_endpoints_with_config_async, get_redirect_async, get_redirect_with_config_async,
+    is_participant, list_participants_async, list_participants_with_config_async, lookup_all_metadata_async, lookup_all_metadata_with_config_async, lookup_async,
+    lookup_in_environments_async, lookup_in_environments_with_config_async,
+    lookup_with_config_async, service_group_async, service_group_with_config_async, sml_lookup, sml_lookup_async,
+    sml_lookup_naptr, sml_lookup_naptr_with_config, sml_lookup_with_config, sml_lookup_with_config_async,
+    smp_lookup_async, smp_lookup_raw_async, smp_lookup_raw_with_config_async, smp_lookup_with_config_async,
+};
+#[cfg(feature = "blocking")]
+pub use lookup::{
+    business_card, business_card_with_config, document_types, document_types_set, document_types_set_with_config, document_types_with_config, find_document_type,
+    find_document_type_with_config, get_endpoint,
+    get_endpoint_with_config, get_endpoints, get_endpoints_with_config, get_redirect, get_redirect_with_config, list_participants, list_participants_with_config,
+    lookup, lookup_all_metadata,
+    lookup_all_metadata_with_config, lookup_in_environments, lookup_in_environments_with_config, lookup_with_config,
+    service_group, service_group_with_config, smp_lookup, smp_lookup_raw, smp_lookup_raw_with_config,
+    smp_lookup_with_config,
+};
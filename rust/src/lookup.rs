@@ -0,0 +1,1548 @@
+//! SML and SMP lookup functions.
+
+use std::collections::HashSet;
+use std::time::Instant;
+
+use time::OffsetDateTime;
+
+use crate::config::{LookupConfig, SmpScheme};
+use crate::document::{split_scheme, DocumentType, DocumentTypeIdentifier, DOCUMENT_SCHEME};
+use crate::dns::{check_url_host_async, hostname_exists, naptr_smp_url, resolve_canonical_name_async};
+#[cfg(feature = "blocking")]
+use crate::dns::check_url_host;
+use crate::endpoint::Endpoint;
+use crate::environment::SmlEnvironment;
+use crate::error::PeppolError;
+use crate::explanation::Explanation;
+use crate::extension::BusinessCard;
+use crate::hash::{participant_md5, HashScheme};
+use crate::http;
+use crate::metadata::{DocumentMetadata, ParticipantMetadata};
+use crate::participant::Participant;
+use crate::redirect::Redirect;
+use crate::resolver::{DnsResolver, Resolver};
+use crate::service_group::ServiceGroup;
+use crate::signature::verify_signature;
+#[cfg(feature = "blocking")]
+use crate::smp_client::{HttpSmpClient, SmpClient};
+use crate::sml::SmlRecord;
+use crate::telemetry;
+use crate::warning::{collect_warnings, LookupWarning};
+use crate::xml::{
+    check_schema, document_identifier_from_href, parse_business_card, parse_document_type, parse_document_types, parse_endpoint, parse_endpoints, parse_participant_list,
+    parse_redirect, parse_service_group_extensions, parse_service_references,
+};
+
+pub use crate::document::{BIS_BILLING_CREDITNOTE, BIS_BILLING_INVOICE};
+
+/// Maximum number of SMP `Redirect`s followed for one service metadata lookup
+pub const MAX_SMP_REDIRECTS: usize = 3;
+
+/// Maximum number of pages [`list_participants`] follows
+pub const MAX_LIST_PAGES: usize = 1000;
+
+/// Step 1: Use SML (Service Metadata Locator) to find a participant's SMP hostname
+///
+/// The SML is like a phone book for the PEPPOL network. Given a participant's ID:
+/// 1. Create an MD5 hash of their ID (e.g., "0192:921605900")
+/// 2. Use the hash to construct a DNS hostname
+/// 3. If the hostname exists, the participant is registered in PEPPOL
+/// 4. The hostname tells us where to find their metadata (SMP)
+///
+/// The hostname is a CNAME alias; the chain is followed to the SMP host that
+/// actually serves the metadata, and both names are returned.
+///
+/// The `environment` selects which SML is queried; production and test
+/// participants are registered separately.
+///
+/// Returns the alias and SMP hostname if found, `PeppolError::NotAParticipant` if not found
+pub fn sml_lookup(participant: &Participant, environment: SmlEnvironment) -> Result<SmlRecord, PeppolError> {
+    sml_lookup_with_config(participant, environment.clone(), &LookupConfig::for_environment(environment))
+}
+
+/// Like [`sml_lookup`], using the DNS servers and timeout from `config`
+///
+/// A missing record (NXDOMAIN) is reported as `PeppolError::NotAParticipant`;
+/// real resolution failures such as SERVFAIL or a timeout as `PeppolError::Dns`.
+pub fn sml_lookup_with_config(participant: &Participant, environment: SmlEnvironment, config: &LookupConfig) -> Result<SmlRecord, PeppolError> {
+    sml_lookup_with_resolver(&DnsResolver::new(config), participant, &environment)
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn sml_lookup_with_resolver(
+    resolver: &dyn Resolver,
+    participant: &Participant,
+    environment: &SmlEnvironment,
+) -> Result<SmlRecord, PeppolError> {
+    // Create MD5 hash of participant ID and construct hostname
+    let alias = HashScheme::Md5.hostname(participant, environment.domain());
+    debug!(hostname = %alias, "querying SML");
+
+    // Check if hostname exists and follow it to the SMP host
+    let started = Instant::now();
+    let record = resolver
+        .resolve(&alias)
+        .and_then(|record| record.ok_or_else(|| PeppolError::NotAParticipant(participant.to_string())));
+    telemetry::sml_lookup(started, record.as_ref().map(|_| true));
+    record
+}
+
+/// Async variant of [`sml_lookup`] using hickory's async resolver
+///
+/// Together with [`smp_lookup_async`] the whole lookup runs on the async
+/// runtime, without `spawn_blocking`. Must be called from within a tokio runtime.
+pub async fn sml_lookup_async(participant: &Participant, environment: SmlEnvironment) -> Result<SmlRecord, PeppolError> {
+    sml_lookup_with_config_async(participant, environment.clone(), &LookupConfig::for_environment(environment)).await
+}
+
+/// Async variant of [`sml_lookup_with_config`] using hickory's async resolver
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn sml_lookup_with_config_async(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    config: &LookupConfig,
+) -> Result<SmlRecord, PeppolError> {
+    let alias = HashScheme::Md5.hostname(participant, environment.domain());
+    debug!(hostname = %alias, "querying SML");
+
+    let started = Instant::now();
+    let record = match resolve_canonical_name_async(&alias, config).await {
+        Ok(Some(resolved)) => Ok(SmlRecord {
+            alias,
+            hostname: resolved.name,
+            ttl: Some(resolved.ttl),
+            addresses: resolved.addresses,
+        }),
+        Ok(None) => Err(PeppolError::NotAParticipant(participant.to_string())),
+        Err(err) => Err(err),
+    };
+    telemetry::sml_lookup(started, record.as_ref().map(|_| true));
+    record
+}
+
+/// Check whether a participant is registered in the SML, without querying the SMP
+///
+/// This only performs the DNS existence check: `Ok(false)` means the SML has
+/// no record for the participant (NXDOMAIN). Real DNS failures are returned
+/// as `PeppolError::Dns` rather than reported as `false`.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub fn is_participant(participant: &Participant, environment: SmlEnvironment) -> Result<bool, PeppolError> {
+    let hostname = HashScheme::Md5.hostname(participant, environment.domain());
+    debug!(%hostname, "checking SML");
+    let started = Instant::now();
+    let exists = hostname_exists(&hostname, &LookupConfig::for_environment(environment));
+    telemetry::sml_lookup(started, exists.as_ref().copied());
+    exists
+}
+
+/// Step 1 (alternative): Discover the SMP URL using the given hash scheme
+///
+/// With [`HashScheme::Md5`] this is the legacy lookup: the `b-<md5>` hostname
+/// is checked with [`sml_lookup`] and the SMP is served at `http://<hostname>`.
+///
+/// With [`HashScheme::Sha256`] the BDXL lookup is used instead: the SHA-256
+/// hostname is queried for a U-NAPTR record, whose regexp yields the SMP URL.
+/// This is required for networks that have migrated off MD5 CNAMEs.
+///
+/// With [`HashScheme::Auto`] the MD5 lookup is tried first and the BDXL one
+/// only if the SML has no MD5 record; see [`detect_smp_url`].
+///
+/// The returned URL can be passed to [`smp_lookup`] in place of a hostname.
+pub fn discover_smp_url(participant: &Participant, environment: SmlEnvironment, hash_scheme: HashScheme) -> Result<String, PeppolError> {
+    discover_smp_url_with_config(participant, environment.clone(), hash_scheme, &LookupConfig::for_environment(environment))
+}
+
+/// Like [`discover_smp_url`], using the DNS servers and timeout from `config`
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub fn discover_smp_url_with_config(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    hash_scheme: HashScheme,
+    config: &LookupConfig,
+) -> Result<String, PeppolError> {
+    match hash_scheme {
+        HashScheme::Md5 => sml_lookup_with_config(participant, environment, config)
+            .map(|record| format!("http://{}", record.hostname)),
+        HashScheme::Sha256 => {
+            let hostname = hash_scheme.hostname(participant, environment.domain());
+            debug!(%hostname, "querying SML for NAPTR record");
+            let started = Instant::now();
+            let smp_url = naptr_smp_url(&hostname, config)
+                .and_then(|smp_url| smp_url.ok_or_else(|| PeppolError::NotAParticipant(participant.to_string())));
+            telemetry::sml_lookup(started, smp_url.as_ref().map(|_| true));
+            smp_url
+        }
+        HashScheme::Auto => detect_smp_url_with_config(participant, environment, config).map(|(smp_url, _)| smp_url),
+    }
+}
+
+/// Discover the SMP URL without knowing the network's hash scheme
+///
+/// Tries the legacy MD5 CNAME lookup first and, if the SML has no record for
+/// it, the SHA-256 NAPTR lookup. Returns the SMP URL together with the scheme
+/// that found it ([`HashScheme::Md5`] or [`HashScheme::Sha256`]), or
+/// `PeppolError::NotAParticipant` if neither did. DNS failures other than a
+/// missing record are returned as they are, without trying the other scheme.
+pub fn detect_smp_url(participant: &Participant, environment: SmlEnvironment) -> Result<(String, HashScheme), PeppolError> {
+    detect_smp_url_with_config(participant, environment.clone(), &LookupConfig::for_environment(environment))
+}
+
+/// Like [`detect_smp_url`], using the DNS servers and timeout from `config`
+pub fn detect_smp_url_with_config(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    config: &LookupConfig,
+) -> Result<(String, HashScheme), PeppolError> {
+    match discover_smp_url_with_config(participant, environment.clone(), HashScheme::Md5, config) {
+        Err(PeppolError::NotAParticipant(_)) => {
+            debug!("no MD5 record, trying the SHA-256 NAPTR lookup");
+            discover_smp_url_with_config(participant, environment, HashScheme::Sha256, config)
+                .map(|smp_url| (smp_url, HashScheme::Sha256))
+        }
+        result => result.map(|smp_url| (smp_url, HashScheme::Md5)),
+    }
+}
+
+/// Step 1 (BDXL): Find the participant's SMP URL in its U-NAPTR record
+///
+/// BDXL publishes the SMP base URL in a NAPTR record on the participant's
+/// SHA-256 hostname instead of relying on the `b-<md5>` CNAME convention, so
+/// SMPs can live at any URL and path. The `Meta:SMP` record with the lowest
+/// order and preference is used, and its regexp applied to the hostname.
+///
+/// This is [`discover_smp_url`] with [`HashScheme::Sha256`]. Returns
+/// `PeppolError::NotAParticipant` if there is no such record.
+pub fn sml_lookup_naptr(participant: &Participant, environment: SmlEnvironment) -> Result<String, PeppolError> {
+    discover_smp_url(participant, environment, HashScheme::Sha256)
+}
+
+/// Like [`sml_lookup_naptr`], using the DNS servers and timeout from `config`
+pub fn sml_lookup_naptr_with_config(participant: &Participant, environment: SmlEnvironment, config: &LookupConfig) -> Result<String, PeppolError> {
+    discover_smp_url_with_config(participant, environment, HashScheme::Sha256, config)
+}
+
+/// Step 2: Query SMP (Service Metadata Publisher) to get supported document types
+///
+/// The SMP is like a business card in the PEPPOL network. It tells us:
+/// 1. What types of documents the participant can receive
+/// 2. Technical details needed for sending documents
+/// 3. Specific document format versions they support
+///
+/// This is similar to how DNS MX records tell you where to send email,
+/// but SMP also includes what "types" of messages you can send.
+///
+/// `smp_hostname` is normally [`SmlRecord::hostname`] from [`sml_lookup`], but a full
+/// base URL such as one from [`discover_smp_url`] is accepted as well.
+///
+/// Like every function here without a `config` or environment parameter, this
+/// uses [`LookupConfig::default`], the production configuration: a bare
+/// hostname is looked up over HTTPS only. Test network SMPs often only serve
+/// HTTP, so query them with
+/// `smp_lookup_with_config(.., &LookupConfig::for_environment(SmlEnvironment::Test))`;
+/// see [`LookupConfig::smp_scheme`]. Redirects are followed.
+#[cfg(feature = "blocking")]
+pub fn smp_lookup(smp_hostname: &str, participant: &Participant) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> {
+    smp_lookup_with_config(smp_hostname, participant, &LookupConfig::default())
+}
+
+/// Like [`smp_lookup`], using the HTTP timeouts from `config`
+///
+/// An SMP that doesn't answer within the timeouts fails with a
+/// `PeppolError::Http` timeout instead of blocking forever.
+#[cfg(feature = "blocking")]
+pub fn smp_lookup_with_config(smp_hostname: &str, participant: &Participant, config: &LookupConfig) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> {
+    Ok(service_group_with_config(smp_hostname, participant, config)?.document_types)
+}
+
+/// Async variant of [`smp_lookup`] using reqwest's async client
+///
+/// Performs the same SMP query and parsing without blocking a thread, so many
+/// participants can be resolved concurrently from a single async runtime.
+pub async fn smp_lookup_async(smp_hostname: &str, participant: &Participant) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> {
+    smp_lookup_with_config_async(smp_hostname, participant, &LookupConfig::default()).await
+}
+
+/// Async variant of [`smp_lookup_with_config`] using reqwest's async client
+pub async fn smp_lookup_with_config_async(smp_hostname: &str, participant: &Participant, config: &LookupConfig) -> Result<Vec<DocumentTypeIdentifier>, PeppolError> {
+    Ok(service_group_with_config_async(smp_hostname, participant, config).await?.document_types)
+}
+
+/// Like [`smp_lookup`], collecting the document types into a `HashSet`
+///
+/// For callers checking many document types against one participant.
+/// Membership is exact, customization included; parse the identifiers to
+/// check once, e.g. `"busdox-docid-qns::urn:...::Invoice##urn:...".parse()?`.
+/// To accept any customization of a document, use
+/// [`DocumentTypeIdentifier::matches`] on the list instead.
+#[cfg(feature = "blocking")]
+pub fn document_types_set(smp_hostname: &str, participant: &Participant) -> Result<HashSet<DocumentTypeIdentifier>, PeppolError> {
+    Ok(smp_lookup(smp_hostname, participant)?.into_iter().collect())
+}
+
+/// Like [`document_types_set`], using the HTTP settings from `config`
+#[cfg(feature = "blocking")]
+pub fn document_types_set_with_config(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<HashSet<DocumentTypeIdentifier>, PeppolError> {
+    Ok(smp_lookup_with_config(smp_hostname, participant, config)?.into_iter().collect())
+}
+
+/// Async variant of [`document_types_set`] using reqwest's async client
+pub async fn document_types_set_async(smp_hostname: &str, participant: &Participant) -> Result<HashSet<DocumentTypeIdentifier>, PeppolError> {
+    Ok(smp_lookup_async(smp_hostname, participant).await?.into_iter().collect())
+}
+
+/// Async variant of [`document_types_set_with_config`] using reqwest's async client
+pub async fn document_types_set_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<HashSet<DocumentTypeIdentifier>, PeppolError> {
+    Ok(smp_lookup_with_config_async(smp_hostname, participant, config).await?.into_iter().collect())
+}
+
+/// Like [`smp_lookup`], also returning the service group XML exactly as the SMP sent it
+///
+/// Meant for diagnosing disagreements between this crate's parsing and an
+/// SMP. A response that doesn't parse still fails with `PeppolError::Xml`.
+#[cfg(feature = "blocking")]
+pub fn smp_lookup_raw(smp_hostname: &str, participant: &Participant) -> Result<(Vec<DocumentTypeIdentifier>, String), PeppolError> {
+    smp_lookup_raw_with_config(smp_hostname, participant, &LookupConfig::default())
+}
+
+/// Like [`smp_lookup_raw`], using the HTTP settings from `config`
+#[cfg(feature = "blocking")]
+pub fn smp_lookup_raw_with_config(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<(Vec<DocumentTypeIdentifier>, String), PeppolError> {
+    smp_lookup_raw_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, config)
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn smp_lookup_raw_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<(Vec<DocumentTypeIdentifier>, String), PeppolError> {
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let response = smp.get(&url, fallback.as_deref())?;
+    check_strict(&response, config)?;
+    Ok((service_group_document_types(&response, config)?.0, response))
+}
+
+/// Async variant of [`smp_lookup_raw`] using reqwest's async client
+pub async fn smp_lookup_raw_async(smp_hostname: &str, participant: &Participant) -> Result<(Vec<DocumentTypeIdentifier>, String), PeppolError> {
+    smp_lookup_raw_with_config_async(smp_hostname, participant, &LookupConfig::default()).await
+}
+
+/// Async variant of [`smp_lookup_raw_with_config`] using reqwest's async client
+pub async fn smp_lookup_raw_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<(Vec<DocumentTypeIdentifier>, String), PeppolError> {
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let response = http::get(&http::client(config)?, &url, fallback.as_deref(), config).await?.body;
+    check_strict(&response, config)?;
+    Ok((service_group_document_types(&response, config)?.0, response))
+}
+
+/// Like [`smp_lookup`], also reporting the SMP software from the `Server` header
+#[cfg(feature = "blocking")]
+pub fn service_group(smp_hostname: &str, participant: &Participant) -> Result<ServiceGroup, PeppolError> {
+    service_group_with_config(smp_hostname, participant, &LookupConfig::default())
+}
+
+/// Like [`service_group`], using the HTTP settings from `config`
+#[cfg(feature = "blocking")]
+pub fn service_group_with_config(smp_hostname: &str, participant: &Participant, config: &LookupConfig) -> Result<ServiceGroup, PeppolError> {
+    service_group_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn service_group_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<ServiceGroup, PeppolError> {
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+
+    // Perform HTTP GET request
+    let response = smp.get_response(&url, fallback.as_deref())?;
+    check_strict(&response.body, config)?;
+    debug!(smp_software = ?response.server, max_age = ?response.max_age, "SMP answered");
+
+    let (document_types, warnings) = service_group_document_types(&response.body, config)?;
+    Ok(ServiceGroup {
+        document_types,
+        smp_software: response.server,
+        extensions: parse_service_group_extensions(&response.body)?,
+        max_age: response.max_age,
+        warnings,
+    })
+}
+
+/// Async variant of [`service_group`] using reqwest's async client
+pub async fn service_group_async(smp_hostname: &str, participant: &Participant) -> Result<ServiceGroup, PeppolError> {
+    service_group_with_config_async(smp_hostname, participant, &LookupConfig::default()).await
+}
+
+/// Async variant of [`service_group_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn service_group_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<ServiceGroup, PeppolError> {
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+
+    // Perform HTTP GET request
+    let response = http::get(&http::client(config)?, &url, fallback.as_deref(), config).await?;
+    check_strict(&response.body, config)?;
+    debug!(smp_software = ?response.server, max_age = ?response.max_age, "SMP answered");
+
+    let (document_types, warnings) = service_group_document_types(&response.body, config)?;
+    Ok(ServiceGroup {
+        document_types,
+        smp_software: response.server,
+        extensions: parse_service_group_extensions(&response.body)?,
+        max_age: response.max_age,
+        warnings,
+    })
+}
+
+/// List the participants an SMP hosts, for SMP operators reconciling their data
+///
+/// Calls the SMP's participant listing endpoint, `<smp>/list/<scheme>`, and
+/// returns the participant of every `ServiceGroupReference` in the
+/// `ServiceGroupReferenceList` it answers with. Listings split into pages are
+/// followed through their `Link: <...>; rel="next"` header, up to
+/// [`MAX_LIST_PAGES`] pages; a next page on another host is refused if it
+/// is a private address (see [`LookupConfig::block_private_addresses`]). References that don't end in a valid participant
+/// identifier are skipped.
+///
+/// This is an administrative endpoint rather than part of the PEPPOL lookup:
+/// many SMPs require authentication for it (failing with a 401
+/// `PeppolError::Http`) or don't offer it at all (`PeppolError::NoServiceMetadata`).
+#[cfg(feature = "blocking")]
+pub fn list_participants(smp_base_url: &str, scheme: &str) -> Result<Vec<Participant>, PeppolError> {
+    list_participants_with_config(smp_base_url, scheme, &LookupConfig::default())
+}
+
+/// Like [`list_participants`], using the HTTP settings from `config`
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(%scheme)))]
+pub fn list_participants_with_config(smp_base_url: &str, scheme: &str, config: &LookupConfig) -> Result<Vec<Participant>, PeppolError> {
+    let client = http::blocking_client(config)?;
+    let (mut url, mut fallback) = participant_list_url(smp_base_url, scheme, config.smp_scheme);
+    let mut participants = Vec::new();
+    for _ in 0..MAX_LIST_PAGES {
+        debug!(%url, "listing SMP participants");
+        let response = http::get_blocking(&client, &url, fallback.as_deref(), config, None)?;
+        check_strict(&response.body, config)?;
+        participants.extend(parse_participant_list(&response.body)?);
+        match response.next {
+            Some(next) if next != url => {
+                check_link(&url, &next, config)?;
+                (url, fallback) = (next, None);
+            }
+            _ => return Ok(participants),
+        }
+    }
+    Err(PeppolError::Xml(format!("participant list has more than {} pages", MAX_LIST_PAGES)))
+}
+
+/// Async variant of [`list_participants`] using reqwest's async client
+pub async fn list_participants_async(smp_base_url: &str, scheme: &str) -> Result<Vec<Participant>, PeppolError> {
+    list_participants_with_config_async(smp_base_url, scheme, &LookupConfig::default()).await
+}
+
+/// Async variant of [`list_participants_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(%scheme)))]
+pub async fn list_participants_with_config_async(
+    smp_base_url: &str,
+    scheme: &str,
+    config: &LookupConfig,
+) -> Result<Vec<Participant>, PeppolError> {
+    let client = http::client(config)?;
+    let (mut url, mut fallback) = participant_list_url(smp_base_url, scheme, config.smp_scheme);
+    let mut participants = Vec::new();
+    for _ in 0..MAX_LIST_PAGES {
+        debug!(%url, "listing SMP participants");
+        let response = http::get(&client, &url, fallback.as_deref(), config).await?;
+        check_strict(&response.body, config)?;
+        participants.extend(parse_participant_list(&response.body)?);
+        match response.next {
+            Some(next) if next != url => {
+                check_link_async(&url, &next, config).await?;
+                (url, fallback) = (next, None);
+            }
+            _ => return Ok(participants),
+        }
+    }
+    Err(PeppolError::Xml(format!("participant list has more than {} pages", MAX_LIST_PAGES)))
+}
+
+/// The first page of an SMP's participant list, and its plain HTTP fallback
+fn participant_list_url(smp_base_url: &str, scheme: &str, smp_scheme: SmpScheme) -> (String, Option<String>) {
+    let (base_url, fallback) = smp_base_urls(smp_base_url, smp_scheme);
+    let path = format!("/list/{}", encode_path(scheme));
+    (format!("{}{}", base_url, path), fallback.map(|fallback| format!("{}{}", fallback, path)))
+}
+
+/// Step 2 (detailed): Fetch each document type together with its processes
+///
+/// [`smp_lookup`] only reads the service group. This follows every
+/// `ServiceMetadataReference` to its `SignedServiceMetadata`, which also lists
+/// the processes each document type is registered for. That costs one extra
+/// request per document type.
+///
+/// Signatures are verified as [`LookupConfig::default`] sets up, which needs
+/// the OpenPeppol SMP CA certificates, so use
+/// [`document_types_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn document_types(smp_hostname: &str, participant: &Participant) -> Result<Vec<DocumentType>, PeppolError> {
+    let config = LookupConfig::default();
+    document_types_with_config(smp_hostname, participant, &config)
+}
+
+/// Like [`document_types`], using the timeouts and signature settings from `config`
+#[cfg(feature = "blocking")]
+pub fn document_types_with_config(smp_hostname: &str, participant: &Participant, config: &LookupConfig) -> Result<Vec<DocumentType>, PeppolError> {
+    document_types_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn document_types_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<Vec<DocumentType>, PeppolError> {
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let service_group = smp.get(&url, fallback.as_deref())?;
+    check_strict(&service_group, config)?;
+
+    service_references(&service_group, &url, config)?
+        .iter()
+        .map(|href| {
+            check_link(&url, href, config)?;
+            parse_document_type(&service_metadata_blocking(smp, href, None, config)?)
+        })
+        .collect()
+}
+
+/// Async variant of [`document_types`] using reqwest's async client
+pub async fn document_types_async(smp_hostname: &str, participant: &Participant) -> Result<Vec<DocumentType>, PeppolError> {
+    let config = LookupConfig::default();
+    document_types_with_config_async(smp_hostname, participant, &config).await
+}
+
+/// Async variant of [`document_types_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn document_types_with_config_async(smp_hostname: &str, participant: &Participant, config: &LookupConfig) -> Result<Vec<DocumentType>, PeppolError> {
+    let client = http::client(config)?;
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let service_group = http::get(&client, &url, fallback.as_deref(), config).await?.body;
+    check_strict(&service_group, config)?;
+
+    let mut document_types = Vec::new();
+    for href in service_references(&service_group, &url, config)? {
+        check_link_async(&url, &href, config).await?;
+        let response = service_metadata(&client, &href, None, config).await?;
+        document_types.push(parse_document_type(&response)?);
+    }
+    Ok(document_types)
+}
+
+/// Fetch service metadata until a document type satisfies `predicate`
+///
+/// Like [`document_types`], but the references are followed one at a time and
+/// the first document type `predicate` accepts is returned without fetching
+/// the rest, e.g. the first one with an AS4 endpoint. Returns `None` if none
+/// does. Errors fetching a reference are returned as soon as they happen.
+///
+/// Signatures are verified as [`LookupConfig::default`] sets up, which needs
+/// the OpenPeppol SMP CA certificates, so use
+/// [`find_document_type_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn find_document_type(
+    smp_hostname: &str,
+    participant: &Participant,
+    predicate: impl FnMut(&DocumentType) -> bool,
+) -> Result<Option<DocumentType>, PeppolError> {
+    let config = LookupConfig::default();
+    find_document_type_with_config(smp_hostname, participant, &config, predicate)
+}
+
+/// Like [`find_document_type`], using the timeouts, limits and signature settings from `config`
+#[cfg(feature = "blocking")]
+pub fn find_document_type_with_config(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+    mut predicate: impl FnMut(&DocumentType) -> bool,
+) -> Result<Option<DocumentType>, PeppolError> {
+    find_document_type_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, config, &mut predicate)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn find_document_type_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+    predicate: &mut dyn FnMut(&DocumentType) -> bool,
+) -> Result<Option<DocumentType>, PeppolError> {
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let service_group = smp.get(&url, fallback.as_deref())?;
+    check_strict(&service_group, config)?;
+
+    for href in service_references(&service_group, &url, config)? {
+        check_link(&url, &href, config)?;
+        let document_type = parse_document_type(&service_metadata_blocking(smp, &href, None, config)?)?;
+        if predicate(&document_type) {
+            return Ok(Some(document_type));
+        }
+    }
+    Ok(None)
+}
+
+/// Async variant of [`find_document_type`] using reqwest's async client
+pub async fn find_document_type_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    predicate: impl FnMut(&DocumentType) -> bool,
+) -> Result<Option<DocumentType>, PeppolError> {
+    let config = LookupConfig::default();
+    find_document_type_with_config_async(smp_hostname, participant, &config, predicate).await
+}
+
+/// Async variant of [`find_document_type_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn find_document_type_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+    mut predicate: impl FnMut(&DocumentType) -> bool,
+) -> Result<Option<DocumentType>, PeppolError> {
+    let client = http::client(config)?;
+    let (url, fallback) = smp_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let service_group = http::get(&client, &url, fallback.as_deref(), config).await?.body;
+    check_strict(&service_group, config)?;
+
+    for href in service_references(&service_group, &url, config)? {
+        check_link_async(&url, &href, config).await?;
+        let document_type = parse_document_type(&service_metadata(&client, &href, None, config).await?)?;
+        if predicate(&document_type) {
+            return Ok(Some(document_type));
+        }
+    }
+    Ok(None)
+}
+
+/// Step 3: Fetch the endpoint a participant uses to receive a document type
+///
+/// Each `ServiceMetadataReference` in the service group points at a
+/// `SignedServiceMetadata` document. That document holds the technical details
+/// needed to actually send: the access point URL, the transport profile and
+/// when the service is active.
+///
+/// `doc_type` must be the full document identifier, including the
+/// customization part after `##`.
+///
+/// If the SMP answers with a `Redirect` to another SMP, it is followed (up to
+/// [`MAX_SMP_REDIRECTS`] times).
+///
+/// Signatures are verified as [`LookupConfig::default`] sets up, which needs
+/// the OpenPeppol SMP CA certificates, so use
+/// [`get_endpoint_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn get_endpoint(smp_hostname: &str, participant: &Participant, doc_type: &str) -> Result<Endpoint, PeppolError> {
+    let config = LookupConfig::default();
+    get_endpoint_with_config(smp_hostname, participant, doc_type, &config)
+}
+
+/// Like [`get_endpoint`], verifying the response signature if `config` asks for it
+///
+/// With `verify_signature` set, a response whose signature doesn't match or
+/// isn't issued by a certificate in `config.trust_store` fails with
+/// `PeppolError::Signature`. With a `transport_preference`, the endpoint
+/// whose profile ranks best is returned rather than the first.
+#[cfg(feature = "blocking")]
+pub fn get_endpoint_with_config(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Endpoint, PeppolError> {
+    get_endpoint_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, doc_type, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn get_endpoint_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Endpoint, PeppolError> {
+    let (url, fallback) = service_metadata_url(smp_hostname, participant, doc_type, config.smp_scheme);
+    debug!(%url, "fetching service metadata");
+
+    // Perform HTTP GET request
+    let response = service_metadata_blocking(smp, &url, fallback.as_deref(), config)?;
+
+    preferred_endpoint(&response, config)
+}
+
+/// Async variant of [`get_endpoint`] using reqwest's async client
+pub async fn get_endpoint_async(smp_hostname: &str, participant: &Participant, doc_type: &str) -> Result<Endpoint, PeppolError> {
+    let config = LookupConfig::default();
+    get_endpoint_with_config_async(smp_hostname, participant, doc_type, &config).await
+}
+
+/// Async variant of [`get_endpoint_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn get_endpoint_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Endpoint, PeppolError> {
+    let (url, fallback) = service_metadata_url(smp_hostname, participant, doc_type, config.smp_scheme);
+    debug!(%url, "fetching service metadata");
+
+    // Perform HTTP GET request
+    let response = service_metadata(&http::client(config)?, &url, fallback.as_deref(), config).await?;
+
+    preferred_endpoint(&response, config)
+}
+
+/// Like [`get_endpoint`], returning every endpoint published for the document type
+///
+/// An SMP may list several endpoints for one document type, for different
+/// transport profiles or as failover access points; [`get_endpoint`] returns
+/// only the first. The endpoints are in the order the SMP lists them, across
+/// all processes, so callers can pick e.g. the AS4 endpoint or fall back to the
+/// next one. With a [`LookupConfig::transport_preference`] they are sorted by
+/// it instead.
+///
+/// Signatures are verified as [`LookupConfig::default`] sets up, which needs
+/// the OpenPeppol SMP CA certificates, so use
+/// [`get_endpoints_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn get_endpoints(smp_hostname: &str, participant: &Participant, doc_type: &str) -> Result<Vec<Endpoint>, PeppolError> {
+    let config = LookupConfig::default();
+    get_endpoints_with_config(smp_hostname, participant, doc_type, &config)
+}
+
+/// Like [`get_endpoints`], verifying the response signature if `config` asks for it
+#[cfg(feature = "blocking")]
+pub fn get_endpoints_with_config(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Vec<Endpoint>, PeppolError> {
+    get_endpoints_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, doc_type, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn get_endpoints_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Vec<Endpoint>, PeppolError> {
+    let (url, fallback) = service_metadata_url(smp_hostname, participant, doc_type, config.smp_scheme);
+    debug!(%url, "fetching service metadata");
+
+    let response = service_metadata_blocking(smp, &url, fallback.as_deref(), config)?;
+
+    let mut endpoints = parse_endpoints(&response)?;
+    config.sort_endpoints(&mut endpoints);
+    Ok(endpoints)
+}
+
+/// Async variant of [`get_endpoints`] using reqwest's async client
+pub async fn get_endpoints_async(smp_hostname: &str, participant: &Participant, doc_type: &str) -> Result<Vec<Endpoint>, PeppolError> {
+    let config = LookupConfig::default();
+    get_endpoints_with_config_async(smp_hostname, participant, doc_type, &config).await
+}
+
+/// Async variant of [`get_endpoints_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn get_endpoints_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Vec<Endpoint>, PeppolError> {
+    let (url, fallback) = service_metadata_url(smp_hostname, participant, doc_type, config.smp_scheme);
+    debug!(%url, "fetching service metadata");
+
+    let response = service_metadata(&http::client(config)?, &url, fallback.as_deref(), config).await?;
+
+    let mut endpoints = parse_endpoints(&response)?;
+    config.sort_endpoints(&mut endpoints);
+    Ok(endpoints)
+}
+
+/// Check whether an SMP redirects a document type to another SMP
+///
+/// Returns the `Redirect` from the participant's service metadata for
+/// `doc_type` without following it, or `None` if the SMP serves the metadata
+/// itself. This is for callers implementing the redirect trust model on their
+/// own: the target's signing certificate must match the redirect's
+/// `certificate_uid` (see [`Redirect::is_signed_by`]). [`get_endpoint`] already
+/// follows redirects.
+///
+/// Signatures are verified as [`LookupConfig::default`] sets up, which needs
+/// the OpenPeppol SMP CA certificates, so use
+/// [`get_redirect_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn get_redirect(smp_hostname: &str, participant: &Participant, doc_type: &str) -> Result<Option<Redirect>, PeppolError> {
+    let config = LookupConfig::default();
+    get_redirect_with_config(smp_hostname, participant, doc_type, &config)
+}
+
+/// Like [`get_redirect`], verifying the response signature if `config` asks for it
+#[cfg(feature = "blocking")]
+pub fn get_redirect_with_config(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Option<Redirect>, PeppolError> {
+    get_redirect_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, doc_type, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn get_redirect_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Option<Redirect>, PeppolError> {
+    let (url, fallback) = service_metadata_url(smp_hostname, participant, doc_type, config.smp_scheme);
+    debug!(%url, "fetching service metadata");
+    let response = smp.get(&url, fallback.as_deref())?;
+    check_service_metadata(&response, None, config)
+}
+
+/// Async variant of [`get_redirect`] using reqwest's async client
+pub async fn get_redirect_async(smp_hostname: &str, participant: &Participant, doc_type: &str) -> Result<Option<Redirect>, PeppolError> {
+    let config = LookupConfig::default();
+    get_redirect_with_config_async(smp_hostname, participant, doc_type, &config).await
+}
+
+/// Async variant of [`get_redirect_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn get_redirect_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    config: &LookupConfig,
+) -> Result<Option<Redirect>, PeppolError> {
+    let (url, fallback) = service_metadata_url(smp_hostname, participant, doc_type, config.smp_scheme);
+    debug!(%url, "fetching service metadata");
+    let response = http::get(&http::client(config)?, &url, fallback.as_deref(), config).await?.body;
+    check_service_metadata(&response, None, config)
+}
+
+/// Fetch a participant's OpenPeppol business card from their SMP
+///
+/// Many SMPs publish the business card they send to the Directory at
+/// `/businesscard/<participant>`, giving a readable label for a participant
+/// ID even if the Directory hasn't indexed them. Returns the first business
+/// entity on the card, or `None` if the SMP has no card for the participant
+/// or doesn't offer the endpoint (a 404).
+///
+/// Business cards aren't signed, so [`LookupConfig::verify_signature`] doesn't
+/// apply.
+#[cfg(feature = "blocking")]
+pub fn business_card(smp_hostname: &str, participant: &Participant) -> Result<Option<BusinessCard>, PeppolError> {
+    business_card_with_config(smp_hostname, participant, &LookupConfig::default())
+}
+
+/// Like [`business_card`], using the HTTP settings from `config`
+#[cfg(feature = "blocking")]
+pub fn business_card_with_config(smp_hostname: &str, participant: &Participant, config: &LookupConfig) -> Result<Option<BusinessCard>, PeppolError> {
+    business_card_with_client(&HttpSmpClient::new(config)?, smp_hostname, participant, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn business_card_with_client(
+    smp: &dyn SmpClient,
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<Option<BusinessCard>, PeppolError> {
+    let (url, fallback) = business_card_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "fetching business card");
+    match smp.get(&url, fallback.as_deref()) {
+        Ok(response) => parse_business_card(&response).map(Some),
+        Err(PeppolError::NoServiceMetadata(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Async variant of [`business_card`] using reqwest's async client
+pub async fn business_card_async(smp_hostname: &str, participant: &Participant) -> Result<Option<BusinessCard>, PeppolError> {
+    business_card_with_config_async(smp_hostname, participant, &LookupConfig::default()).await
+}
+
+/// Async variant of [`business_card_with_config`] using reqwest's async client
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn business_card_with_config_async(
+    smp_hostname: &str,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<Option<BusinessCard>, PeppolError> {
+    let (url, fallback) = business_card_url(smp_hostname, participant, config.smp_scheme);
+    debug!(%url, "fetching business card");
+    match http::get(&http::client(config)?, &url, fallback.as_deref(), config).await {
+        Ok(response) => parse_business_card(&response.body).map(Some),
+        Err(PeppolError::NoServiceMetadata(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Find a participant's SMP and fetch their document types, in one call
+///
+/// Chains [`sml_lookup`] and [`document_types`], so scripts don't have to pass
+/// the SMP hostname between the two steps. An unregistered participant is
+/// reported as `PeppolError::NotAParticipant`.
+///
+/// If [`LookupConfig::time_budget`] is set, both steps share it, and a
+/// lookup that doesn't finish in time fails with `PeppolError::DeadlineExceeded`.
+///
+/// Signatures are verified as [`LookupConfig::for_environment`] sets up: in
+/// production that needs the OpenPeppol SMP CA certificates, so use
+/// [`lookup_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn lookup(participant: &Participant, environment: SmlEnvironment) -> Result<Vec<DocumentType>, PeppolError> {
+    let config = LookupConfig::for_environment(environment.clone());
+    lookup_with_config(participant, environment, &config)
+}
+
+/// Like [`lookup`], using the DNS, HTTP and signature settings from `config`
+#[cfg(feature = "blocking")]
+pub fn lookup_with_config(participant: &Participant, environment: SmlEnvironment, config: &LookupConfig) -> Result<Vec<DocumentType>, PeppolError> {
+    let Some(budget) = config.time_budget else {
+        let record = sml_lookup_with_config(participant, environment, config)?;
+        return document_types_with_config(&record.hostname, participant, config);
+    };
+
+    let deadline = Instant::now() + budget;
+    let result = sml_lookup_with_config(participant, environment, &within_deadline(config, deadline)?).and_then(|record| {
+        let smp = HttpSmpClient::new(&within_deadline(config, deadline)?)?.with_deadline(deadline);
+        document_types_with_client(&smp, &record.hostname, participant, config)
+    });
+    match result {
+        // Timeouts were cut to the budget, so failing past the deadline means running out of it
+        Err(PeppolError::Dns(_) | PeppolError::Http(_)) if Instant::now() >= deadline => Err(PeppolError::DeadlineExceeded),
+        result => result,
+    }
+}
+
+/// `config` with its DNS and HTTP timeouts cut to the time left until `deadline`
+#[cfg(feature = "blocking")]
+fn within_deadline(config: &LookupConfig, deadline: Instant) -> Result<LookupConfig, PeppolError> {
+    let remaining = http::remaining(deadline)?;
+    Ok(LookupConfig {
+        dns_timeout: config.dns_timeout.min(remaining),
+        connect_timeout: config.connect_timeout.min(remaining),
+        request_timeout: config.request_timeout.min(remaining),
+        ..config.clone()
+    })
+}
+
+/// Async variant of [`lookup`]
+pub async fn lookup_async(participant: &Participant, environment: SmlEnvironment) -> Result<Vec<DocumentType>, PeppolError> {
+    let config = LookupConfig::for_environment(environment.clone());
+    lookup_with_config_async(participant, environment, &config).await
+}
+
+/// Async variant of [`lookup_with_config`]
+pub async fn lookup_with_config_async(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    config: &LookupConfig,
+) -> Result<Vec<DocumentType>, PeppolError> {
+    let lookup = async {
+        let record = sml_lookup_with_config_async(participant, environment, config).await?;
+        document_types_with_config_async(&record.hostname, participant, config).await
+    };
+    match config.time_budget {
+        Some(budget) => tokio::time::timeout(budget, lookup).await.map_err(|_| PeppolError::DeadlineExceeded)?,
+        None => lookup.await,
+    }
+}
+
+/// Run [`lookup`] in each of `environments`, e.g. to see whether a participant
+/// is registered in production, on the test network, or both
+///
+/// Returns one result per environment, in the given order. A participant
+/// missing from an environment shows up as `PeppolError::NotAParticipant`
+/// there without affecting the others.
+#[cfg(feature = "blocking")]
+pub fn lookup_in_environments(
+    participant: &Participant,
+    environments: &[SmlEnvironment],
+) -> Vec<(SmlEnvironment, Result<Vec<DocumentType>, PeppolError>)> {
+    environments
+        .iter()
+        .map(|environment| (environment.clone(), lookup(participant, environment.clone())))
+        .collect()
+}
+
+/// Like [`lookup_in_environments`], using the same `config` in every environment
+#[cfg(feature = "blocking")]
+pub fn lookup_in_environments_with_config(
+    participant: &Participant,
+    environments: &[SmlEnvironment],
+    config: &LookupConfig,
+) -> Vec<(SmlEnvironment, Result<Vec<DocumentType>, PeppolError>)> {
+    environments
+        .iter()
+        .map(|environment| (environment.clone(), lookup_with_config(participant, environment.clone(), config)))
+        .collect()
+}
+
+/// Async variant of [`lookup_in_environments`]
+pub async fn lookup_in_environments_async(
+    participant: &Participant,
+    environments: &[SmlEnvironment],
+) -> Vec<(SmlEnvironment, Result<Vec<DocumentType>, PeppolError>)> {
+    let mut results = Vec::with_capacity(environments.len());
+    for environment in environments {
+        results.push((environment.clone(), lookup_async(participant, environment.clone()).await));
+    }
+    results
+}
+
+/// Async variant of [`lookup_in_environments_with_config`]
+pub async fn lookup_in_environments_with_config_async(
+    participant: &Participant,
+    environments: &[SmlEnvironment],
+    config: &LookupConfig,
+) -> Vec<(SmlEnvironment, Result<Vec<DocumentType>, PeppolError>)> {
+    let mut results = Vec::with_capacity(environments.len());
+    for environment in environments {
+        results.push((environment.clone(), lookup_with_config_async(participant, environment.clone(), config).await));
+    }
+    results
+}
+
+/// Look up everything about a participant in one call
+///
+/// Runs the SML lookup, reads the service group and follows every
+/// `ServiceMetadataReference`, collecting each document type's processes and
+/// endpoints (URL, transport profile, certificate and its expiry). Every
+/// request is bounded by the timeouts in the configuration.
+///
+/// A document type whose service metadata can't be fetched or parsed is
+/// recorded in [`ParticipantMetadata::documents`] with its error, so one
+/// broken service doesn't fail the whole call. An unregistered participant
+/// or an unreadable service group still fails it.
+///
+/// Signatures are verified as [`LookupConfig::for_environment`] sets up: in
+/// production that needs the OpenPeppol SMP CA certificates, so use
+/// [`lookup_all_metadata_with_config`] with a trust store (or with `verify_signature` off).
+#[cfg(feature = "blocking")]
+pub fn lookup_all_metadata(participant: &Participant, environment: SmlEnvironment) -> Result<ParticipantMetadata, PeppolError> {
+    let config = LookupConfig::for_environment(environment.clone());
+    lookup_all_metadata_with_config(participant, environment, &config)
+}
+
+/// Like [`lookup_all_metadata`], using the DNS, HTTP and signature settings from `config`
+#[cfg(feature = "blocking")]
+pub fn lookup_all_metadata_with_config(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    config: &LookupConfig,
+) -> Result<ParticipantMetadata, PeppolError> {
+    let sml_record = sml_lookup_with_config(participant, environment, config)?;
+    lookup_all_metadata_with_client(&HttpSmpClient::new(config)?, sml_record, participant, config)
+}
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub(crate) fn lookup_all_metadata_with_client(
+    smp: &dyn SmpClient,
+    sml_record: SmlRecord,
+    participant: &Participant,
+    config: &LookupConfig,
+) -> Result<ParticipantMetadata, PeppolError> {
+    let (url, fallback) = smp_url(&sml_record.hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let (service_group, smp_software) = smp.get_with_server(&url, fallback.as_deref())?;
+    check_strict(&service_group, config)?;
+
+    let extensions = parse_service_group_extensions(&service_group)?;
+    let documents = service_references(&service_group, &url, config)?
+        .into_iter()
+        .map(|href| {
+            let result = check_link(&url, &href, config)
+                .and_then(|()| service_metadata_blocking(smp, &href, None, config))
+                .and_then(|response| parse_document_type(&response));
+            document_metadata(href, result)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(ParticipantMetadata {
+        participant: participant.clone(),
+        sml_record,
+        smp_software,
+        extensions,
+        warnings: document_warnings(&documents),
+        documents,
+    })
+}
+
+/// Async variant of [`lookup_all_metadata`]
+pub async fn lookup_all_metadata_async(participant: &Participant, environment: SmlEnvironment) -> Result<ParticipantMetadata, PeppolError> {
+    let config = LookupConfig::for_environment(environment.clone());
+    lookup_all_metadata_with_config_async(participant, environment, &config).await
+}
+
+/// Async variant of [`lookup_all_metadata_with_config`]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(participant = %participant)))]
+pub async fn lookup_all_metadata_with_config_async(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    config: &LookupConfig,
+) -> Result<ParticipantMetadata, PeppolError> {
+    let sml_record = sml_lookup_with_config_async(participant, environment, config).await?;
+
+    let client = http::client(config)?;
+    let (url, fallback) = smp_url(&sml_record.hostname, participant, config.smp_scheme);
+    debug!(%url, "querying SMP");
+    let service_group = http::get(&client, &url, fallback.as_deref(), config).await?;
+    check_strict(&service_group.body, config)?;
+
+    let extensions = parse_service_group_extensions(&service_group.body)?;
+    let mut documents = Vec::new();
+    for href in service_references(&service_group.body, &url, config)? {
+        let result = match check_link_async(&url, &href, config).await {
+            Ok(()) => match service_metadata(&client, &href, None, config).await {
+                Ok(response) => parse_document_type(&response),
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(err),
+        };
+        documents.push(document_metadata(href, result));
+    }
+
+    Ok(ParticipantMetadata {
+        participant: participant.clone(),
+        sml_record,
+        smp_software: service_group.server,
+        extensions,
+        warnings: document_warnings(&documents),
+        documents,
+    })
+}
+
+/// Describe what a lookup of `participant` would do, without any network calls
+///
+/// Computes the MD5 hash and SML name, and builds the service group URL and
+/// the service metadata URL of each of `document_types` (full identifiers,
+/// as passed to [`get_endpoint`]). No DNS query is made, so the URLs use the
+/// SML name where a lookup would use the SMP host it points at; see
+/// [`explain_with_dns`] to resolve it first.
+pub fn explain(participant: &Participant, environment: SmlEnvironment, document_types: &[&str]) -> Explanation {
+    explain_with_config(participant, environment.clone(), document_types, &LookupConfig::for_environment(environment))
+}
+
+/// Like [`explain`], building the URLs with the `smp_scheme` from `config`
+pub fn explain_with_config(participant: &Participant, environment: SmlEnvironment, document_types: &[&str], config: &LookupConfig) -> Explanation {
+    explanation(participant, &environment, None, document_types, config)
+}
+
+/// Like [`explain_with_config`], doing the SML step to find the SMP host
+///
+/// Only DNS is queried; the SMP is not contacted. An unregistered participant
+/// is reported as `PeppolError::NotAParticipant`, as [`sml_lookup`] does.
+pub fn explain_with_dns(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    document_types: &[&str],
+    config: &LookupConfig,
+) -> Result<Explanation, PeppolError> {
+    let sml_record = sml_lookup_with_config(participant, environment.clone(), config)?;
+    Ok(explanation(participant, &environment, Some(&sml_record.hostname), document_types, config))
+}
+
+/// Async variant of [`explain_with_dns`] using hickory's async resolver
+pub async fn explain_with_dns_async(
+    participant: &Participant,
+    environment: SmlEnvironment,
+    document_types: &[&str],
+    config: &LookupConfig,
+) -> Result<Explanation, PeppolError> {
+    let sml_record = sml_lookup_with_config_async(participant, environment.clone(), config).await?;
+    Ok(explanation(participant, &environment, Some(&sml_record.hostname), document_types, config))
+}
+
+/// Build an [`Explanation`], using `smp_hostname` if the SML step was done
+pub(crate) fn explanation(
+    participant: &Participant,
+    environment: &SmlEnvironment,
+    smp_hostname: Option<&str>,
+    document_types: &[&str],
+    config: &LookupConfig,
+) -> Explanation {
+    let sml_hostname = HashScheme::Md5.hostname(participant, environment.domain());
+    let host = smp_hostname.unwrap_or(&sml_hostname);
+    let (smp_url, smp_fallback_url) = smp_url(host, participant, config.smp_scheme);
+    let document_urls = document_types
+        .iter()
+        .map(|doc_type| service_metadata_url(host, participant, doc_type, config.smp_scheme).0)
+        .collect();
+
+    Explanation {
+        participant: participant.clone(),
+        hashed_id: format!("{}:{}", participant.icd(), participant.identifier()).to_lowercase(),
+        md5: participant_md5(participant.icd(), participant.identifier()),
+        smp_hostname: smp_hostname.map(str::to_string),
+        sml_hostname,
+        smp_url,
+        smp_fallback_url,
+        document_urls,
+    }
+}
+
+/// The endpoint to use from a `SignedServiceMetadata` response
+///
+/// The first endpoint, or with a [`LookupConfig::transport_preference`] the
+/// best match for it.
+fn preferred_endpoint(response: &str, config: &LookupConfig) -> Result<Endpoint, PeppolError> {
+    if config.transport_preference.is_empty() {
+        return parse_endpoint(response);
+    }
+    let mut endpoints = parse_endpoints(response)?;
+    config.sort_endpoints(&mut endpoints);
+    Ok(endpoints.swap_remove(0))
+}
+
+/// The document types in a service group `response` that `config` lets
+/// through, and the references skipped because they couldn't be read
+fn service_group_document_types(response: &str, config: &LookupConfig) -> Result<(Vec<DocumentTypeIdentifier>, Vec<LookupWarning>), PeppolError> {
+    let (document_types, warnings) = parse_document_types(response)?;
+    #[cfg(feature = "tracing")]
+    for warning in &warnings {
+        warn!(%warning, "skipping service metadata reference");
+    }
+    Ok((filter_document_types(document_types, config), warnings))
+}
+
+/// The document types [`LookupConfig::document_filter`] lets through
+///
+/// At most [`LookupConfig::max_references`] of them are kept.
+fn filter_document_types(document_types: Vec<DocumentTypeIdentifier>, config: &LookupConfig) -> Vec<DocumentTypeIdentifier> {
+    document_types
+        .into_iter()
+        .filter(|identifier| config.includes_document(identifier))
+        .take(config.max_references.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// The service metadata references in the service group fetched from `url`
+///
+/// Each `href` is resolved against `url` first, then filtered by
+/// [`LookupConfig::document_filter`] and cut to [`LookupConfig::max_references`].
+fn service_references(service_group: &str, url: &str, config: &LookupConfig) -> Result<Vec<String>, PeppolError> {
+    let references = parse_service_references(service_group)?
+        .iter()
+        .map(|href| resolve_reference(url, href))
+        .collect();
+    let mut references = filter_references(references, config);
+    references.truncate(config.max_references.unwrap_or(usize::MAX));
+    Ok(references)
+}
+
+/// Refuse a link in the SMP response from `url` that points at a private address, like a `Redirect`
+///
+/// Used for service metadata references and the next pages of participant
+/// lists. Links on the response's own origin (scheme, host and port) go to
+/// the SMP that was already trusted with it, so only the others are checked.
+#[cfg(feature = "blocking")]
+fn check_link(url: &str, href: &str, config: &LookupConfig) -> Result<(), PeppolError> {
+    if leaves_origin(url, href) {
+        check_url_host(href, config)?;
+    }
+    Ok(())
+}
+
+/// Async variant of [`check_link`]
+async fn check_link_async(url: &str, href: &str, config: &LookupConfig) -> Result<(), PeppolError> {
+    if leaves_origin(url, href) {
+        check_url_host_async(href, config).await?;
+    }
+    Ok(())
+}
+
+/// Whether `href` points outside the origin of `url`
+fn leaves_origin(url: &str, href: &str) -> bool {
+    match (reqwest::Url::parse(url), reqwest::Url::parse(href)) {
+        (Ok(url), Ok(href)) => url.origin() != href.origin(),
+        _ => true,
+    }
+}
+
+/// Resolve a service metadata `href` against the service group's URL
+///
+/// Some SMPs leave the scheme out (`smp.example.com/...`) or give a path
+/// relative to the service group; both are completed from `base_url`.
+/// Absolute hrefs are returned unchanged.
+fn resolve_reference(base_url: &str, href: &str) -> String {
+    let href = href.trim();
+    let Ok(base) = reqwest::Url::parse(base_url) else {
+        return href.to_string();
+    };
+    if href.contains("://") {
+        return href.to_string();
+    }
+
+    let authority = match (base.host_str(), base.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return href.to_string(),
+    };
+    let first_segment = href.split('/').next().unwrap_or_default();
+    if first_segment.eq_ignore_ascii_case(&authority) {
+        return format!("{}://{}", base.scheme(), href);
+    }
+
+    // `iso6523-actorid-upis::...` would otherwise parse as a URL with that scheme
+    let relative = if href.starts_with('/') { href.to_string() } else { format!("./{}", href) };
+    base.join(&relative).map(String::from).unwrap_or_else(|_| href.to_string())
+}
+
+/// The service metadata references [`LookupConfig::document_filter`] lets through
+///
+/// References whose `href` doesn't name a document type are kept, since
+/// there's no telling what they point to without fetching them.
+fn filter_references(references: Vec<String>, config: &LookupConfig) -> Vec<String> {
+    if config.document_filter.is_empty() {
+        return references;
+    }
+    references
+        .into_iter()
+        .filter(|href| document_identifier_from_href(href).is_none_or(|identifier| config.includes_document(&identifier)))
+        .collect()
+}
+
+/// The warnings for the documents whose service metadata could be read
+fn document_warnings(documents: &[DocumentMetadata]) -> Vec<LookupWarning> {
+    collect_warnings(documents.iter().filter_map(|document| document.result.as_ref().ok()), OffsetDateTime::now_utc())
+}
+
+fn document_metadata(href: String, result: Result<DocumentType, PeppolError>) -> DocumentMetadata {
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        warn!(%href, error = %err, "could not read service metadata");
+    }
+    DocumentMetadata {
+        identifier: document_identifier_from_href(&href),
+        href,
+        result,
+    }
+}
+
+/// Check `response` against the SMP schema if [`LookupConfig::strict`] is set
+fn check_strict(response: &str, config: &LookupConfig) -> Result<(), PeppolError> {
+    if config.strict {
+        check_schema(response)?;
+    }
+    Ok(())
+}
+
+/// Fetch a `SignedServiceMetadata` document, following `Redirect`s to other SMPs
+///
+/// Each response's signature is verified if `config` asks for it. A redirect
+/// may name the `CertificateUID` the target SMP signs with; when verifying,
+/// the target's signing certificate must match it.
+#[cfg(feature = "blocking")]
+fn service_metadata_blocking(
+    smp: &dyn SmpClient,
+    url: &str,
+    fallback: Option<&str>,
+    config: &LookupConfig,
+) -> Result<String, PeppolError> {
+    let mut response = smp.get(url, fallback)?;
+    let mut certificate_uid = None;
+    for _ in 0..=MAX_SMP_REDIRECTS {
+        match check_service_metadata(&response, certificate_uid.as_deref(), config)? {
+            Some(redirect) => {
+                debug!(href = %redirect.href, "following SMP redirect");
+                check_url_host(&redirect.href, config)?;
+                response = smp.get(&redirect.href, None)?;
+                certificate_uid = redirect.certificate_uid;
+            }
+            None => return Ok(response),
+        }
+    }
+    Err(too_many_redirects())
+}
+
+/// Async variant of [`service_metadata_blocking`]
+async fn service_metadata(
+    client: &reqwest::Client,
+    url: &str,
+    fallback: Option<&str>,
+    config: &LookupConfig,
+) -> Result<String, PeppolError> {
+    let mut response = http::get(client, url, fallback, config).await?.body;
+    let mut certificate_uid = None;
+    for _ in 0..=MAX_SMP_REDIRECTS {
+        match check_service_metadata(&response, certificate_uid.as_deref(), config)? {
+            Some(redirect) => {
+                debug!(href = %redirect.href, "following SMP redirect");
+                check_url_host_async(&redirect.href, config).await?;
+                response = http::get(client, &redirect.href, None, config).await?.body;
+                certificate_uid = redirect.certificate_uid;
+            }
+            None => return Ok(response),
+        }
+    }
+    Err(too_many_redirects())
+}
+
+/// Verify a service metadata response if configured, and return its redirect if it has one
+fn check_service_metadata(
+    response: &str,
+    certificate_uid: Option<&str>,
+    config: &LookupConfig,
+) -> Result<Option<Redirect>, PeppolError> {
+    check_strict(response, config)?;
+    if config.verify_signature {
+        let trust_store = config.signature_trust_store();
+        if trust_store.is_empty() {
+            return Err(PeppolError::Config(
+                "signature verification is on, but no SMP CA certificates are trusted: add the OpenPeppol SMP CA \
+                 certificates to LookupConfig::trust_store or turn verify_signature off"
+                    .to_string(),
+            ));
+        }
+        let signer = verify_signature(response, &trust_store)?;
+        if let Some(certificate_uid) = certificate_uid {
+            if !signer.matches_uid(certificate_uid) {
+                return Err(PeppolError::Signature(format!(
+                    "redirected SMP is not signed by certificate {}",
+                    certificate_uid
+                )));
+            }
+        }
+    }
+    parse_redirect(response)
+}
+
+fn too_many_redirects() -> PeppolError {
+    PeppolError::Xml(format!("more than {} SMP redirects", MAX_SMP_REDIRECTS))
+}
+
+/// Construct SMP URL
+///
+/// Format: [scheme]://[SMP hostname]/[identifier scheme]::[participant identifier]
+///
+/// If `smp_hostname` already includes a scheme it is used as the base URL.
+/// Otherwise the scheme comes from `smp_scheme`, and the second URL is the
+/// plain HTTP fallback if one should be tried.
+fn smp_url(smp_hostname: &str, participant: &Participant, smp_scheme: SmpScheme) -> (String, Option<String>) {
+    let participant_path = participant_path(participant);
+    let (base_url, fallback) = smp_base_urls(smp_hostname, smp_scheme);
+    (
+        format!("{}/{}", base_url, participant_path),
+        fallback.map(|fallback| format!("{}/{}", fallback, participant_path)),
+    )
+}
+
+/// The URL of a participant's business card on an SMP, and its plain HTTP fallback
+fn business_card_url(smp_hostname: &str, participant: &Participant, smp_scheme: SmpScheme) -> (String, Option<String>) {
+    let path = format!("/businesscard/{}", participant_path(participant));
+    let (base_url, fallback) = smp_base_urls(smp_hostname, smp_scheme);
+    (format!("{}{}", base_url, path), fallback.map(|fallback| format!("{}{}", fallback, path)))
+}
+
+/// The participant identifier as an SMP URL path segment, e.g. `iso6523-actorid-upis::0192:921605900`
+fn participant_path(participant: &Participant) -> String {
+    format!(
+        "{}::{}:{}",
+        encode_path(participant.scheme()),
+        encode_path(participant.icd()),
+        encode_path(participant.identifier())
+    )
+}
+
+/// The SMP base URL for `smp_hostname`, and the plain HTTP one to fall back to
+///
+/// A hostname that already includes a scheme is used as given, without a
+/// trailing slash.
+pub(crate) fn smp_base_urls(smp_hostname: &str, smp_scheme: SmpScheme) -> (String, Option<String>) {
+    if smp_hostname.contains("://") {
+        return (smp_hostname.trim_end_matches('/').to_string(), None);
+    }
+
+    let https_url = format!("https://{}", smp_hostname);
+    let http_url = format!("http://{}", smp_hostname);
+    match smp_scheme {
+        SmpScheme::HttpsWithHttpFallback => (https_url, Some(http_url)),
+        SmpScheme::Https => (https_url, None),
+        SmpScheme::Http => (http_url, None),
+    }
+}
+
+/// Percent-encode an identifier for use in an SMP URL path
+///
+/// Everything but unreserved characters and `:` is encoded, as the SMP REST
+/// binding expects: `:` is a valid path character, and strict SMPs reject
+/// identifiers whose separators arrive as `%3A`. `#`, `/`, spaces and other
+/// special characters are always encoded.
+fn encode_path(value: &str) -> String {
+    urlencoding::encode(value).replace("%3A", ":")
+}
+
+/// Construct the signed service metadata URL for one document type
+///
+/// Format: [SMP URL]/services/[document scheme]::[document identifier]
+///
+/// `doc_type` may carry its own `<scheme>::` prefix, as written by
+/// [`DocumentTypeIdentifier`]'s `Display`; otherwise `busdox-docid-qns` is used.
+fn service_metadata_url(
+    smp_hostname: &str,
+    participant: &Participant,
+    doc_type: &str,
+    smp_scheme: SmpScheme,
+) -> (String, Option<String>) {
+    let (scheme, value) = split_scheme(doc_type);
+    let service_path = format!("/services/{}::{}", encode_path(scheme.unwrap_or(DOCUMENT_SCHEME)), encode_path(value));
+    let (url, fallback) = smp_url(smp_hostname, participant, smp_scheme);
+    (url + &service_path, fallback.map(|fallback| fallback + &service_path))
+}
@@ -0,0 +1,17 @@
+//! Logging macros that compile to nothing without the `tracing` feature.
+
+/// Emit a `tracing` debug event if the `tracing` feature is enabled
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Emit a `tracing` warning if the `tracing` feature is enabled
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
@@ -1,131 +1,307 @@
-//! PEPPOL uses two key services to enable document exchange:
+//! Command-line PEPPOL participant lookup, defaulting to Snapbooks AS.
 //!
-//! 1. SML (Service Metadata Locator):
-//!    - Acts as a DNS-based directory service
-//!    - Maps a participant's ID to their SMP provider
-//!    - Uses DNS lookup to find where a participant's metadata is hosted
-//!    - Similar to how email's MX records help find mail servers
-//!
-//! 2. SMP (Service Metadata Publisher):
-//!    - Hosts metadata about a participant's capabilities
-//!    - Tells you what document types they can receive
-//!    - Provides technical details needed for sending documents
-//!    - Acts like a participant's business card in the network
-//!
-//! This example demonstrates how to:
-//! 1. Use SML to find where a participant's metadata is hosted
-//! 2. Query their SMP to discover what documents they can receive
-//! 3. Check for PEPPOL BIS Billing 3.0 support
+//! See the library documentation for an explanation of the SML and SMP steps.
 
-use md5::{Md5, Digest};
-use regex::Regex;
+use clap::{Parser, ValueEnum};
+use peppol_lookup::lookup::{document_types_with_config, lookup_all_metadata_with_config, sml_lookup_with_config, smp_lookup_with_config};
+use peppol_lookup::{DocumentType, LookupConfig, Participant, PeppolError, SmlEnvironment};
+use serde_json::json;
 use std::error::Error;
-use std::net::ToSocketAddrs;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-// Test environment SML domain
-const SML_DOMAIN: &str = "edelivery.tech.ec.europa.eu";
+/// Look up a participant in the PEPPOL network
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Participant IDs to look up, as `icd:identifier` (default: Snapbooks AS, 0192:921605900)
+    participants: Vec<Participant>,
 
-// PEPPOL BIS Billing 3.0 document identifiers
-const BIS_BILLING_INVOICE: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice";
-const BIS_BILLING_CREDITNOTE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote";
+    /// Also look up the participant IDs in FILE, one per line
+    ///
+    /// Blank lines and lines starting with `#` are skipped. In CSV files only
+    /// the first column is read, and a header line is skipped. With `--format json`, one JSON object is
+    /// printed per line (JSON Lines) as each lookup finishes.
+    #[arg(long, value_name = "FILE")]
+    input: Option<PathBuf>,
 
-/// Step 1: Use SML (Service Metadata Locator) to find a participant's SMP hostname
-///
-/// The SML is like a phone book for the PEPPOL network. Given a participant's ID:
-/// 1. Create an MD5 hash of their ID (e.g., "0192:921605900")
-/// 2. Use the hash to construct a DNS hostname
-/// 3. If the hostname exists, the participant is registered in PEPPOL
-/// 4. The hostname tells us where to find their metadata (SMP)
-///
-/// Returns the SMP hostname if found, None if not found
-fn sml_lookup(icd: &str, identifier: &str, sml_domain: &str) -> Option<String> {
-    // Create MD5 hash of participant ID
-    let participant_id = format!("{}:{}", icd, identifier);
-    let mut hasher = Md5::new();
-    hasher.update(participant_id.as_bytes());
-    let md5_hash = format!("{:x}", hasher.finalize());
-    
-    // Construct hostname
-    let hostname = format!("b-{}.iso6523-actorid-upis.{}", md5_hash, sml_domain);
-    
-    // Check if hostname exists
-    // Try to resolve hostname by attempting to convert it to a socket address
-    match (hostname.as_str(), 0).to_socket_addrs() {
-        Ok(_) => Some(hostname),
-        Err(_) => None,
+    /// Which SML to query
+    #[arg(long, value_enum, default_value_t = Environment::Production)]
+    environment: Environment,
+
+    /// SML domain of a private network, e.g. `localhost.sml.internal`
+    ///
+    /// Overrides `--environment`. Falls back to the `PEPPOL_SML_DOMAIN`
+    /// environment variable.
+    #[arg(long, env = "PEPPOL_SML_DOMAIN", value_name = "DOMAIN")]
+    sml_domain: Option<String>,
+
+    /// Verify SMP signatures against the SMP CA certificates in FILE (PEM)
+    ///
+    /// Without it signatures aren't verified, since no CA certificates ship
+    /// with the tool.
+    #[arg(long, value_name = "FILE")]
+    smp_ca: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Only check whether the participants support this document identifier
+    ///
+    /// Exits with 0 if every participant supports it and 1 otherwise. Nothing
+    /// is printed unless `--verbose` is given. Include the customization after
+    /// `##` to check for a specific one.
+    #[arg(long, value_name = "DOC-ID")]
+    check_document: Option<String>,
+
+    /// Explain the result of `--check-document`
+    #[arg(long, short)]
+    verbose: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Human-readable text
+    Text,
+    /// A single JSON object, for scripts and CI pipelines
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Environment {
+    /// The production PEPPOL network
+    Production,
+    /// The PEPPOL test network
+    Test,
+}
+
+impl From<Environment> for SmlEnvironment {
+    fn from(environment: Environment) -> Self {
+        match environment {
+            Environment::Production => SmlEnvironment::Production,
+            Environment::Test => SmlEnvironment::Test,
+        }
     }
 }
 
-/// Step 2: Query SMP (Service Metadata Publisher) to get supported document types
-///
-/// The SMP is like a business card in the PEPPOL network. It tells us:
-/// 1. What types of documents the participant can receive
-/// 2. Technical details needed for sending documents
-/// 3. Specific document format versions they support
-///
-/// This is similar to how DNS MX records tell you where to send email,
-/// but SMP also includes what "types" of messages you can send.
-fn smp_lookup(smp_hostname: &str, icd: &str, identifier: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    // Construct SMP URL
-    // Format: http://[SMP hostname]/[identifier scheme]::[participant identifier]
-    let participant_id = format!("{}:{}", icd, identifier);
-    let url = format!("http://{}/iso6523-actorid-upis::{}", 
-        smp_hostname,
-        urlencoding::encode(&participant_id));
-    
-    // Perform HTTP GET request
-    let client = reqwest::blocking::Client::new();
-    let response = client.get(&url).send()?.text()?;
-    
-    // Extract document types from ServiceMetadataReference href attributes
-    let mut document_types = Vec::new();
-    
-    // Match ServiceMetadataReference href attributes
-    let re = Regex::new(r#"ServiceMetadataReference[^>]*href="([^"]*)"[^>]*>"#)?;
-    for cap in re.captures_iter(&response) {
-        let href = urlencoding::decode(&cap[1])?.to_string();
-        if href.contains("busdox-docid-qns::") {
-            let parts: Vec<&str> = href.split("busdox-docid-qns::").collect();
-            if parts.len() > 1 {
-                let doc_type = parts[1].split('#').next().unwrap_or("");
-                document_types.push(doc_type.to_string());
+impl Args {
+    /// The SML to query: `--sml-domain`, then `PEPPOL_SML_DOMAIN`, then `--environment`
+    fn sml_environment(&self) -> SmlEnvironment {
+        match self.sml_domain.as_deref().map(str::trim) {
+            Some(domain) if !domain.is_empty() => SmlEnvironment::custom(domain),
+            _ => self.environment.into(),
+        }
+    }
+
+    /// The lookup configuration, verifying signatures if `--smp-ca` is given
+    fn lookup_config(&self, environment: SmlEnvironment) -> Result<LookupConfig, String> {
+        let mut config = LookupConfig::for_environment(environment);
+        match &self.smp_ca {
+            Some(path) => {
+                let pem = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                config.trust_store.add_pem(&pem).map_err(|err| format!("{}: {}", path.display(), err))?;
+                config.verify_signature = true;
             }
+            None => config.verify_signature = false,
         }
+        Ok(config)
     }
-    
-    Ok(document_types)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // Snapbooks AS (Norwegian organization number)
-    let icd = "0192";
-    let identifier = "921605900";
-    
-    // Step 1: Perform SML lookup to get SMP hostname
-    let smp_hostname = match sml_lookup(icd, identifier, SML_DOMAIN) {
-        Some(hostname) => hostname,
-        None => {
-            println!("Not a PEPPOL participant: {}:{}", icd, identifier);
-            return Ok(());
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let environment = args.sml_environment();
+    let config = match args.lookup_config(environment.clone()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut participants = args.participants;
+    if let Some(input) = &args.input {
+        match read_participants(input) {
+            Ok(from_file) => participants.extend(from_file),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if participants.is_empty() {
+        // Snapbooks AS (Norwegian organization number)
+        participants.push(Participant::new("0192", "921605900").expect("valid participant ID"));
+    }
+    // JSON Lines for input files, which may be too long to collect into one array
+    let json_lines = args.input.is_some();
+
+    if let Some(doc_id) = &args.check_document {
+        let mut supported = true;
+        for participant in &participants {
+            match supports_document(participant, environment.clone(), doc_id, &config) {
+                Ok(true) if args.verbose => println!("{} supports {}", participant, doc_id),
+                Ok(true) => {}
+                Ok(false) => {
+                    if args.verbose {
+                        println!("{} does not support {}", participant, doc_id);
+                    }
+                    supported = false;
+                }
+                Err(err) => {
+                    if args.verbose {
+                        println!("{}: {}", participant, err);
+                    }
+                    supported = false;
+                }
+            }
+        }
+        return if supported { ExitCode::SUCCESS } else { ExitCode::FAILURE };
+    }
+
+    let mut failed = false;
+    let mut json_results = Vec::new();
+    for (index, participant) in participants.iter().enumerate() {
+        if args.format == Format::Text && participants.len() > 1 {
+            if index > 0 {
+                println!();
+            }
+            println!("== {} ==", participant);
+        }
+        let result = match args.format {
+            Format::Text => print_text(participant, environment.clone(), &config),
+            Format::Json if json_lines => {
+                lookup_json(participant, environment.clone(), &config).map(|result| println!("{}", serde_json::to_string(&result).unwrap_or_default()))
+            }
+            Format::Json => lookup_json(participant, environment.clone(), &config).map(|result| json_results.push(result)),
+        };
+        if let Err(err) = result {
+            eprintln!("Error: {}: {}", participant, err);
+            failed = true;
+        }
+    }
+
+    if args.format == Format::Json && !json_lines {
+        // A single participant gives a single object, several an array
+        let output = match json_results.len() {
+            1 => json_results.remove(0),
+            _ => serde_json::Value::Array(json_results),
+        };
+        println!("{}", serde_json::to_string_pretty(&output).unwrap_or_default());
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Read the participant IDs in `path`, one per line
+///
+/// Blank lines and `#` comments are skipped, and only the first column of a
+/// CSV line is read. A first line without an `icd:identifier` is taken for a
+/// CSV header and skipped too; any other invalid ID is reported with its line
+/// number.
+fn read_participants(path: &Path) -> Result<Vec<Participant>, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut participants = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let id = line.split(',').next().unwrap_or_default().trim().trim_matches('"');
+        if id.is_empty() || id.starts_with('#') {
+            continue;
+        }
+        if index == 0 && !id.contains(':') {
+            continue;
         }
+        let participant = id.parse().map_err(|err| format!("{}:{}: {}", path.display(), index + 1, err))?;
+        participants.push(participant);
+    }
+    Ok(participants)
+}
+
+/// Whether `participant` can receive `doc_id`
+///
+/// Without a customization (`##...`) any customization of the document counts;
+/// see [`DocumentTypeIdentifier::matches`](peppol_lookup::DocumentTypeIdentifier::matches).
+fn supports_document(participant: &Participant, environment: SmlEnvironment, doc_id: &str, config: &LookupConfig) -> Result<bool, PeppolError> {
+    let record = match sml_lookup_with_config(participant, environment, config) {
+        Ok(record) => record,
+        Err(PeppolError::NotAParticipant(_)) => return Ok(false),
+        Err(err) => return Err(err),
     };
-    println!("SMP hostname: {}", smp_hostname);
-    
-    // Step 2: Get supported document identifiers
-    let document_types = smp_lookup(&smp_hostname, icd, identifier)?;
-    println!("\nSupported document identifiers:");
-    for doc_type in &document_types {
-        println!("- {}", doc_type);
+
+    match smp_lookup_with_config(&record.hostname, participant, config) {
+        Ok(document_types) => Ok(document_types.iter().any(|document_type| document_type.matches(doc_id))),
+        Err(PeppolError::NoServiceMetadata(_)) => Ok(false),
+        Err(err) => Err(err),
     }
-    
+}
+
+/// Print the lookup result as human-readable text
+fn print_text(participant: &Participant, environment: SmlEnvironment, config: &LookupConfig) -> Result<(), Box<dyn Error>> {
+    // Steps 1 and 2: find the SMP and read every document type it publishes
+    let metadata = match lookup_all_metadata_with_config(participant, environment, config) {
+        Ok(metadata) => metadata,
+        Err(PeppolError::NotAParticipant(participant_id)) => {
+            println!("Not a PEPPOL participant: {}", participant_id);
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    print!("{}", metadata);
+
     // Check for PEPPOL BIS Billing 3.0 documents
     println!("\nPEPPOL BIS Billing 3.0 Support:");
-    if document_types.contains(&BIS_BILLING_INVOICE.to_string()) {
+    if metadata.supports_bis_billing_invoice() {
+        println!("- Supports Invoice");
+    }
+    if metadata.supports_bis_billing_credit_note() {
+        println!("- Supports Credit Note");
+    }
+
+    println!("\nPINT Support:");
+    if metadata.supports_pint_invoice() {
         println!("- Supports Invoice");
     }
-    if document_types.contains(&BIS_BILLING_CREDITNOTE.to_string()) {
+    if metadata.supports_pint_credit_note() {
         println!("- Supports Credit Note");
     }
-    
+
     Ok(())
 }
+
+/// The lookup result as a JSON object, including each document's processes
+fn lookup_json(participant: &Participant, environment: SmlEnvironment, config: &LookupConfig) -> Result<serde_json::Value, Box<dyn Error>> {
+    let record = match sml_lookup_with_config(participant, environment, config) {
+        Ok(record) => record,
+        Err(PeppolError::NotAParticipant(_)) => {
+            return Ok(json!({ "participant": participant.to_string(), "registered": false }));
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let document_types = document_types_with_config(&record.hostname, participant, config)?;
+    Ok(json!({
+        "participant": participant.to_string(),
+        "registered": true,
+        "sml_alias": record.alias,
+        "smp_hostname": record.hostname,
+        "smp_addresses": record.addresses,
+        "document_types": document_types.iter().map(document_type_json).collect::<Vec<_>>(),
+    }))
+}
+
+fn document_type_json(document_type: &DocumentType) -> serde_json::Value {
+    json!({
+        "scheme": document_type.scheme,
+        "value": document_type.value,
+        "name": document_type.name(),
+        "processes": document_type
+            .processes
+            .iter()
+            .map(|process| json!({ "scheme": process.scheme, "value": process.value }))
+            .collect::<Vec<_>>(),
+    })
+}
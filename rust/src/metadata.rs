@@ -0,0 +1,250 @@
+//! Everything a lookup can find out about a participant, in one struct.
+
+use std::fmt;
+
+use crate::diff::{self, MetadataDiff};
+use crate::document::{DocumentType, DocumentTypeIdentifier, BIS_BILLING_CREDITNOTE, BIS_BILLING_INVOICE};
+use crate::error::PeppolError;
+use crate::extension::{BusinessCard, Extension};
+use crate::participant::Participant;
+use crate::sml::SmlRecord;
+use crate::warning::LookupWarning;
+
+/// A participant's profile: where their SMP is, and the full metadata of
+/// every document type it publishes
+///
+/// Returned by [`lookup_all_metadata`](crate::lookup::lookup_all_metadata).
+/// Each document type is fetched separately, so one broken or unreachable
+/// service doesn't hide the others; see [`DocumentMetadata::result`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParticipantMetadata {
+    /// The participant that was looked up
+    pub participant: Participant,
+    /// The SML record pointing at the participant's SMP
+    pub sml_record: SmlRecord,
+    /// The SMP's `Server` response header, if it sent one
+    pub smp_software: Option<String>,
+    /// Extensions on the service group, such as an OpenPeppol business card
+    pub extensions: Vec<Extension>,
+    /// One entry per `ServiceMetadataReference` in the service group, in order
+    pub documents: Vec<DocumentMetadata>,
+    /// Non-fatal issues with the documents' endpoints, as of the lookup
+    ///
+    /// Expired or soon expiring certificates, expired endpoints and unknown
+    /// transport profiles; see [`LookupWarning`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warnings: Vec<LookupWarning>,
+}
+
+impl ParticipantMetadata {
+    /// The document types whose service metadata was fetched and parsed
+    pub fn document_types(&self) -> impl Iterator<Item = &DocumentType> {
+        self.documents.iter().filter_map(|document| document.result.as_ref().ok())
+    }
+
+    /// The first business card among the service group's and documents' extensions
+    ///
+    /// Some SMPs embed the participant's registered name and country code this way.
+    pub fn business_card(&self) -> Option<&BusinessCard> {
+        self.extensions
+            .iter()
+            .chain(self.document_types().flat_map(|document_type| &document_type.extensions))
+            .find_map(|extension| extension.business_card.as_ref())
+    }
+
+    /// Whether the participant can receive `document`
+    ///
+    /// Checked against the document types whose service metadata was read, so
+    /// a registration the SMP couldn't serve doesn't count. Wildcard
+    /// registrations accept the documents they cover; see
+    /// [`DocumentTypeIdentifier::accepts`].
+    pub fn supports(&self, document: &DocumentTypeIdentifier) -> bool {
+        self.document_types().any(|document_type| document_type.identifier().accepts(document))
+    }
+
+    /// Whether the participant can receive invoices, as PEPPOL BIS Billing 3.0 or PINT
+    pub fn supports_invoice(&self) -> bool {
+        self.supports_root(BIS_BILLING_INVOICE, |_| true)
+    }
+
+    /// Whether the participant can receive credit notes, as PEPPOL BIS Billing 3.0 or PINT
+    pub fn supports_credit_note(&self) -> bool {
+        self.supports_root(BIS_BILLING_CREDITNOTE, |_| true)
+    }
+
+    /// Whether the participant can receive PEPPOL BIS Billing 3.0 invoices
+    ///
+    /// Any customization of the invoice counts except the PINT ones; see
+    /// [`supports_pint_invoice`](Self::supports_pint_invoice) for those.
+    pub fn supports_bis_billing_invoice(&self) -> bool {
+        self.supports_root(BIS_BILLING_INVOICE, |identifier| !identifier.is_pint())
+    }
+
+    /// Whether the participant can receive PEPPOL BIS Billing 3.0 credit notes; PINT ones don't count
+    pub fn supports_bis_billing_credit_note(&self) -> bool {
+        self.supports_root(BIS_BILLING_CREDITNOTE, |identifier| !identifier.is_pint())
+    }
+
+    /// Whether the participant can receive PINT invoices, in any jurisdiction
+    pub fn supports_pint_invoice(&self) -> bool {
+        self.supports_root(BIS_BILLING_INVOICE, DocumentTypeIdentifier::is_pint)
+    }
+
+    /// Whether the participant can receive PINT credit notes, in any jurisdiction
+    pub fn supports_pint_credit_note(&self) -> bool {
+        self.supports_root(BIS_BILLING_CREDITNOTE, DocumentTypeIdentifier::is_pint)
+    }
+
+    /// Whether a readable document type has the given root and passes `profile`
+    fn supports_root(&self, root: &str, profile: impl Fn(&DocumentTypeIdentifier) -> bool) -> bool {
+        self.document_types()
+            .map(DocumentType::identifier)
+            .any(|identifier| identifier.matches(root) && profile(&identifier))
+    }
+
+    /// What changed from this snapshot to the newer `other` one
+    ///
+    /// For monitoring partners between lookups. Registrations are compared
+    /// even if their service metadata couldn't be read, so a failing SMP
+    /// doesn't look like removed document types; their endpoints are only
+    /// compared when both snapshots could read them.
+    pub fn diff(&self, other: &Self) -> MetadataDiff {
+        diff::diff(self, other)
+    }
+
+    /// The documents whose service metadata couldn't be fetched or parsed
+    pub fn failures(&self) -> impl Iterator<Item = &DocumentMetadata> {
+        self.documents.iter().filter(|document| document.result.is_err())
+    }
+}
+
+/// A multi-line summary for terminals and logs
+///
+/// Lists the SML and SMP details, then the document types grouped by the
+/// process they are registered for, each with its endpoints and certificate
+/// expiry, and finally the documents that couldn't be read.
+impl fmt::Display for ParticipantMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Participant: {}", self.participant)?;
+        writeln!(f, "SMP hostname: {}", self.sml_record.alias)?;
+        if self.sml_record.hostname != self.sml_record.alias {
+            writeln!(f, "Resolved SMP host: {}", self.sml_record.hostname)?;
+        }
+        if !self.sml_record.addresses.is_empty() {
+            let addresses: Vec<_> = self.sml_record.addresses.iter().map(ToString::to_string).collect();
+            writeln!(f, "SMP addresses: {}", addresses.join(", "))?;
+        }
+        if let Some(smp_software) = &self.smp_software {
+            writeln!(f, "SMP software: {}", smp_software)?;
+        }
+        if let Some(business_card) = self.business_card() {
+            let name = business_card.name.as_deref().unwrap_or("(no name)");
+            match &business_card.country_code {
+                Some(country_code) => writeln!(f, "Business card: {} ({})", name, country_code)?,
+                None => writeln!(f, "Business card: {}", name)?,
+            }
+        }
+
+        // Processes in order of first appearance, each with its document types
+        let mut processes: Vec<(&str, Vec<&DocumentType>)> = Vec::new();
+        for document_type in self.document_types() {
+            if document_type.processes.is_empty() {
+                add_to_process(&mut processes, "(no process)", document_type);
+            }
+            for process in &document_type.processes {
+                add_to_process(&mut processes, &process.value, document_type);
+            }
+        }
+
+        for (process, document_types) in &processes {
+            writeln!(f, "\nProcess {}:", process)?;
+            for document_type in document_types {
+                match document_type.name() {
+                    Some(name) => writeln!(f, "- {} ({})", document_type.value, name)?,
+                    None => writeln!(f, "- {}", document_type.value)?,
+                }
+                for endpoint in &document_type.endpoints {
+                    write!(f, "    {} {}", endpoint.transport_profile, endpoint.url)?;
+                    match &endpoint.certificate {
+                        Some(certificate) => writeln!(f, " (certificate expires {})", certificate.not_after.date())?,
+                        None => writeln!(f)?,
+                    }
+                }
+            }
+        }
+
+        if !self.warnings.is_empty() {
+            writeln!(f, "\nWarnings:")?;
+            for warning in &self.warnings {
+                writeln!(f, "- {}", warning)?;
+            }
+        }
+
+        let mut failures = self.failures().peekable();
+        if failures.peek().is_some() {
+            writeln!(f, "\nUnreadable documents:")?;
+            for failure in failures {
+                let document = failure.identifier.as_ref().map_or(failure.href.clone(), |identifier| identifier.value.clone());
+                if let Err(err) = &failure.result {
+                    writeln!(f, "- {}: {}", document, err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn add_to_process<'a>(processes: &mut Vec<(&'a str, Vec<&'a DocumentType>)>, process: &'a str, document_type: &'a DocumentType) {
+    match processes.iter_mut().find(|(value, _)| *value == process) {
+        Some((_, document_types)) => document_types.push(document_type),
+        None => processes.push((process, vec![document_type])),
+    }
+}
+
+/// The service metadata of one document type, or why it couldn't be read
+///
+/// With the `serde` feature, a failed `result` is serialized as its message
+/// and read back as `PeppolError::Deserialized`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocumentMetadata {
+    /// The `ServiceMetadataReference` URL from the service group
+    pub href: String,
+    /// The document identifier decoded from `href`, if it has the usual form
+    pub identifier: Option<DocumentTypeIdentifier>,
+    /// The document type with its processes and endpoints
+    #[cfg_attr(feature = "serde", serde(with = "serde_result"))]
+    pub result: Result<DocumentType, PeppolError>,
+}
+
+/// `Ok` as `{"document": ...}`, `Err` as `{"error": "<message>"}`
+#[cfg(feature = "serde")]
+mod serde_result {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::document::DocumentType;
+    use crate::error::PeppolError;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Outcome<T> {
+        Document(T),
+        Error(String),
+    }
+
+    pub(super) fn serialize<S: Serializer>(result: &Result<DocumentType, PeppolError>, serializer: S) -> Result<S::Ok, S::Error> {
+        match result {
+            Ok(document_type) => Outcome::Document(document_type),
+            Err(err) => Outcome::Error(err.to_string()),
+        }
+        .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Result<DocumentType, PeppolError>, D::Error> {
+        Ok(match Outcome::deserialize(deserializer)? {
+            Outcome::Document(document_type) => Ok(document_type),
+            Outcome::Error(message) => Err(PeppolError::Deserialized(message)),
+        })
+    }
+}
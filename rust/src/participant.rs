@@ -0,0 +1,314 @@
+//! PEPPOL participant identifiers.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::error::PeppolError;
+
+/// Default identifier scheme, used for PEPPOL participant identifiers
+pub const PARTICIPANT_SCHEME: &str = "iso6523-actorid-upis";
+
+/// ICD (ISO 6523 International Code Designator) values accepted by PEPPOL
+/// as participant identifier schemes
+const KNOWN_ICDS: &[&str] = &[
+    "0002", "0007", "0009", "0037", "0060", "0088", "0096", "0097", "0106", "0130",
+    "0135", "0142", "0147", "0151", "0170", "0183", "0184", "0188", "0190", "0191",
+    "0192", "0193", "0194", "0195", "0196", "0198", "0199", "0200", "0201", "0202",
+    "0203", "0204", "0205", "0208", "0209", "0210", "0211", "0212", "0213", "0215",
+    "0216", "0217", "0218", "0221", "0225", "0230", "0235", "0240", "0244", "9901",
+    "9910", "9913", "9914", "9915", "9918", "9919", "9920", "9922", "9923", "9924",
+    "9925", "9926", "9927", "9928", "9929", "9930", "9931", "9932", "9933", "9934",
+    "9935", "9936", "9937", "9938", "9939", "9940", "9941", "9942", "9943", "9944",
+    "9945", "9946", "9947", "9948", "9949", "9950", "9951", "9952", "9953", "9955",
+    "9957", "9959",
+];
+
+/// ICDs for national organization numbers, by ISO 3166 country code
+const ORG_NUMBER_ICDS: &[(&str, &str)] = &[
+    ("BE", "0208"), // Enterprise number (Crossroads Bank for Enterprises)
+    ("DK", "0184"), // CVR number
+    ("EE", "0191"), // Registry code (Centre of Registers and Information Systems)
+    ("FI", "0037"), // Business ID (Y-tunnus)
+    ("FR", "0002"), // SIRENE
+    ("IS", "0196"), // Kennitala
+    ("IT", "0210"), // Codice fiscale
+    ("LT", "0200"), // Legal entity code
+    ("NL", "0106"), // Chamber of Commerce (KvK) number
+    ("NO", "0192"), // Organization number (Enhetsregisteret)
+    ("SE", "0007"), // Organisationsnummer
+];
+
+/// ICDs for VAT numbers, by ISO 3166 country code, with the prefix the VAT number carries
+const VAT_NUMBER_ICDS: &[(&str, &str, &str)] = &[
+    ("AT", "9914", "AT"),
+    ("BE", "9925", "BE"),
+    ("BG", "9926", "BG"),
+    ("CH", "9927", "CHE"),
+    ("CY", "9928", "CY"),
+    ("CZ", "9929", "CZ"),
+    ("DE", "9930", "DE"),
+    ("EE", "9931", "EE"),
+    ("ES", "9920", "ES"),
+    ("FR", "9957", "FR"),
+    ("GB", "9932", "GB"),
+    ("GR", "9933", "EL"),
+    ("HR", "9934", "HR"),
+    ("IE", "9935", "IE"),
+    ("LT", "9937", "LT"),
+    ("LU", "9938", "LU"),
+    ("LV", "9939", "LV"),
+    ("MT", "9943", "MT"),
+    ("NL", "9944", "NL"),
+    ("PL", "9945", "PL"),
+    ("PT", "9946", "PT"),
+    ("RO", "9947", "RO"),
+    ("SE", "9955", "SE"),
+    ("SI", "9949", "SI"),
+    ("SK", "9950", "SK"),
+];
+
+/// A PEPPOL participant, identified by an ICD and an identifier within it
+///
+/// For example Snapbooks AS is `0192:921605900`: ICD `0192` (Norwegian
+/// organization numbers) and identifier `921605900`, in the
+/// `iso6523-actorid-upis` scheme.
+///
+/// Identifiers may be alphanumeric, e.g. VAT numbers such as
+/// `9930:DE123456789`. They keep the case they were given in, but compare
+/// and hash ignoring case, like the SML hostname does, so `9930:de123456789`
+/// is the same participant.
+#[derive(Debug, Clone)]
+pub struct Participant {
+    scheme: String,
+    icd: String,
+    identifier: String,
+}
+
+impl Participant {
+    /// Create a participant from its ICD and identifier
+    ///
+    /// The ICD must be a known 4-digit code. Surrounding whitespace is removed
+    /// from both parts.
+    pub fn new(icd: &str, identifier: &str) -> Result<Self, PeppolError> {
+        Self::with_scheme(PARTICIPANT_SCHEME, icd, identifier)
+    }
+
+    /// Create a participant in an identifier scheme other than `iso6523-actorid-upis`
+    ///
+    /// The scheme is used in the SML hostname (`b-<hash>.<scheme>.<sml domain>`)
+    /// and the SMP path, and is lowercased as the spec requires. The ICD is
+    /// only checked against the PEPPOL code list, and the identifier's check
+    /// digit (see [`validate_checksum`]), in the default scheme.
+    pub fn with_scheme(scheme: &str, icd: &str, identifier: &str) -> Result<Self, PeppolError> {
+        let scheme = scheme.trim().to_lowercase();
+        let icd = icd.trim();
+        let identifier = identifier.trim();
+
+        if scheme.is_empty() {
+            return Err(PeppolError::InvalidIdentifier("identifier scheme is empty".to_string()));
+        }
+        // Neither could be told apart from the identifier when parsed back
+        if scheme.contains(':') {
+            return Err(PeppolError::InvalidIdentifier(format!("identifier scheme {:?} contains ':'", scheme)));
+        }
+        if icd.contains(':') {
+            return Err(PeppolError::InvalidIdentifier(format!("ICD {:?} contains ':'", icd)));
+        }
+        if scheme == PARTICIPANT_SCHEME && !KNOWN_ICDS.contains(&icd) {
+            return Err(PeppolError::InvalidIdentifier(format!("unknown ICD {:?}", icd)));
+        }
+        if icd.is_empty() {
+            return Err(PeppolError::InvalidIdentifier("ICD is empty".to_string()));
+        }
+        if identifier.is_empty() {
+            return Err(PeppolError::InvalidIdentifier("identifier is empty".to_string()));
+        }
+        if scheme == PARTICIPANT_SCHEME {
+            validate_checksum(icd, identifier)?;
+        }
+
+        Ok(Participant {
+            scheme,
+            icd: icd.to_string(),
+            identifier: identifier.to_string(),
+        })
+    }
+
+    /// A participant identified by a national organization number
+    ///
+    /// `country` is the ISO 3166 code, e.g. `NO` for a Norwegian organization
+    /// number (ICD `0192`) or `BE` for a Belgian enterprise number (`0208`).
+    /// Spaces, dots and dashes in the number are removed.
+    pub fn from_org_number(country: &str, number: &str) -> Result<Self, PeppolError> {
+        let country = country.trim().to_uppercase();
+        let (_, icd) = ORG_NUMBER_ICDS
+            .iter()
+            .find(|(code, _)| *code == country)
+            .ok_or_else(|| PeppolError::InvalidIdentifier(format!("no known ICD for organization numbers in {:?}", country)))?;
+        Self::new(icd, &normalize_number(number))
+    }
+
+    /// A participant identified by a VAT number, e.g. `NO921605900MVA` or `BE0123456789`
+    ///
+    /// The country prefix of the VAT number is optional. Most countries have a
+    /// dedicated VAT ICD, and the identifier keeps the prefix (`9925:BE0123456789`).
+    /// Norway and Denmark register participants by organization number, so a
+    /// Norwegian VAT number `NO921605900MVA` becomes `0192:921605900` and a
+    /// Danish one `DK12345678` becomes `0184:12345678`.
+    pub fn from_vat_number(country: &str, number: &str) -> Result<Self, PeppolError> {
+        let country = country.trim().to_uppercase();
+        let number = normalize_number(number).to_uppercase();
+
+        match country.as_str() {
+            "NO" => {
+                let number = number.strip_prefix("NO").unwrap_or(&number);
+                let number = number.strip_suffix("MVA").unwrap_or(number);
+                return Self::from_org_number("NO", number);
+            }
+            "DK" => return Self::from_org_number("DK", number.strip_prefix("DK").unwrap_or(&number)),
+            _ => {}
+        }
+
+        let (_, icd, prefix) = VAT_NUMBER_ICDS
+            .iter()
+            .find(|(code, _, _)| *code == country)
+            .ok_or_else(|| PeppolError::InvalidIdentifier(format!("no known ICD for VAT numbers in {:?}", country)))?;
+        let number = number.strip_prefix(prefix).unwrap_or(&number);
+        Self::new(icd, &format!("{}{}", prefix, number))
+    }
+
+    /// The identifier scheme, e.g. `iso6523-actorid-upis`
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The ICD, e.g. `0192`
+    pub fn icd(&self) -> &str {
+        &self.icd
+    }
+
+    /// The identifier within the ICD, e.g. `921605900`
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+}
+
+impl PartialEq for Participant {
+    fn eq(&self, other: &Self) -> bool {
+        self.scheme == other.scheme
+            && fold_case(&self.icd).eq(fold_case(&other.icd))
+            && fold_case(&self.identifier).eq(fold_case(&other.identifier))
+    }
+}
+
+impl Eq for Participant {}
+
+impl Hash for Participant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.scheme.hash(state);
+        // The ICD can't contain ':', so it separates the two parts
+        for c in fold_case(&self.icd).chain([':']).chain(fold_case(&self.identifier)) {
+            c.hash(state);
+        }
+    }
+}
+
+/// The characters of `value` lowercased, as the SML hostname hash sees them
+fn fold_case(value: &str) -> impl Iterator<Item = char> + '_ {
+    value.chars().flat_map(char::to_lowercase)
+}
+
+/// Parses `icd:identifier`, optionally prefixed with a scheme such as `iso6523-actorid-upis::`
+///
+/// Only a prefix without any `:` counts as a scheme, so identifiers that
+/// contain `::` themselves parse as such.
+impl FromStr for Participant {
+    type Err = PeppolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (scheme, s) = match s.split_once("::") {
+            Some((scheme, rest)) if !scheme.contains(':') => (scheme, rest),
+            _ => (PARTICIPANT_SCHEME, s),
+        };
+        let (icd, identifier) = s
+            .split_once(':')
+            .ok_or_else(|| PeppolError::InvalidIdentifier(format!("expected icd:identifier, got {:?}", s)))?;
+        Participant::with_scheme(scheme, icd, identifier)
+    }
+}
+
+/// Formats as `icd:identifier`, prefixed with `<scheme>::` outside the default scheme
+///
+/// The result parses back to the same participant. An identifier starting
+/// with `:` would make `icd::identifier` look like a scheme prefix, so it
+/// gets the scheme prefix as well.
+impl fmt::Display for Participant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scheme != PARTICIPANT_SCHEME || self.identifier.starts_with(':') {
+            write!(f, "{}::", self.scheme)?;
+        }
+        write!(f, "{}:{}", self.icd, self.identifier)
+    }
+}
+
+/// Serialized as `<scheme>::<icd>:<identifier>`, which [`FromStr`] reads back
+#[cfg(feature = "serde")]
+impl serde::Serialize for Participant {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{}::{}:{}", self.scheme, self.icd, self.identifier))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Participant {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Check the check digit of an identifier whose ICD defines one
+///
+/// Catches typos before any DNS query is made. Currently checks Norwegian
+/// organization numbers (ICD `0192`): nine digits, the last a MOD11 check
+/// digit over the first eight. Identifiers under other ICDs are accepted
+/// as they are.
+pub fn validate_checksum(icd: &str, identifier: &str) -> Result<(), PeppolError> {
+    match icd {
+        "0192" => validate_mod11(identifier, &[3, 2, 7, 6, 5, 4, 3, 2]),
+        _ => Ok(()),
+    }
+}
+
+/// Validate `weights.len()` digits followed by their MOD11 check digit
+fn validate_mod11(identifier: &str, weights: &[u32]) -> Result<(), PeppolError> {
+    let invalid = |reason: &str| PeppolError::InvalidIdentifier(format!("{:?} {}", identifier, reason));
+
+    let digits = identifier
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<Vec<_>>>()
+        .filter(|digits| digits.len() == weights.len() + 1)
+        .ok_or_else(|| invalid(&format!("is not a {}-digit number", weights.len() + 1)))?;
+
+    let sum: u32 = digits.iter().zip(weights).map(|(digit, weight)| digit * weight).sum();
+    // A remainder of 1 would need check digit 10, so such numbers are never issued
+    let check_digit = match 11 - sum % 11 {
+        11 => 0,
+        10 => return Err(invalid("has no valid check digit")),
+        check_digit => check_digit,
+    };
+    if digits[weights.len()] != check_digit {
+        return Err(invalid("has the wrong check digit"));
+    }
+    Ok(())
+}
+
+/// Remove the separators people commonly write in national numbers
+fn normalize_number(number: &str) -> String {
+    number
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '.' | '-'))
+        .collect()
+}
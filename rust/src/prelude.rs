@@ -0,0 +1,27 @@
+//! The types most lookups need, for a single glob import.
+//!
+//! ```no_run
+//! # #[cfg(feature = "blocking")] {
+//! use peppol_lookup::prelude::*;
+//!
+//! let participant: Participant = "0192:921605900".parse()?;
+//! let client = LookupClient::builder().environment(SmlEnvironment::Production).build()?;
+//! let metadata: ParticipantMetadata = client.lookup_all_metadata(&participant)?;
+//! println!("{}", metadata);
+//! # }
+//! # Ok::<(), peppol_lookup::PeppolError>(())
+//! ```
+//!
+//! Only the common types are here; functions and less used types are
+//! imported from their modules as usual.
+
+#[cfg(feature = "blocking")]
+pub use crate::client::LookupClient;
+#[cfg(feature = "lookup")]
+pub use crate::config::LookupConfig;
+pub use crate::document::{DocumentType, DocumentTypeIdentifier};
+pub use crate::endpoint::Endpoint;
+pub use crate::environment::SmlEnvironment;
+pub use crate::error::PeppolError;
+pub use crate::metadata::ParticipantMetadata;
+pub use crate::participant::Participant;
@@ -0,0 +1,29 @@
+//! SMP redirects to the SMP that actually hosts a participant.
+
+use crate::certificate::Certificate;
+
+/// A `Redirect` from one SMP to another
+///
+/// Instead of the service metadata itself, an SMP may answer with a redirect
+/// to a different SMP. Per the OASIS BDX SMP spec, the target must sign its
+/// response with the certificate identified by `certificate_uid`; callers that
+/// follow redirects themselves should check this with [`Redirect::is_signed_by`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Redirect {
+    /// URL of the service metadata on the target SMP
+    pub href: String,
+    /// Subject unique identifier of the target SMP's signing certificate
+    pub certificate_uid: Option<String>,
+}
+
+impl Redirect {
+    /// Whether the target's signing certificate matches `certificate_uid`
+    ///
+    /// A redirect without a `CertificateUID` can't be checked and returns `false`.
+    pub fn is_signed_by(&self, certificate: &Certificate) -> bool {
+        self.certificate_uid
+            .as_deref()
+            .is_some_and(|certificate_uid| certificate.matches_uid(certificate_uid))
+    }
+}
@@ -0,0 +1,45 @@
+//! The DNS step of a lookup, behind a trait so it can be replaced.
+
+use crate::config::LookupConfig;
+use crate::dns::resolve_canonical_name;
+use crate::error::PeppolError;
+use crate::sml::SmlRecord;
+
+/// Resolves SML hostnames to the SMP host they point at
+///
+/// [`DnsResolver`] is the real implementation. Tests can implement this to
+/// return canned answers without touching the network, and pass it to
+/// [`LookupClientBuilder::resolver`](crate::client::LookupClientBuilder::resolver).
+pub trait Resolver: Send + Sync {
+    /// Resolve `alias`, the hashed participant hostname in the SML
+    ///
+    /// Returns `Ok(None)` if the name doesn't exist, meaning the participant
+    /// isn't registered. Real resolution failures are `PeppolError::Dns`.
+    fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError>;
+}
+
+/// Resolves SML hostnames through DNS, following the CNAME chain
+///
+/// Uses the DNS servers and timeout from the [`LookupConfig`] it was created with.
+#[derive(Debug, Clone)]
+pub struct DnsResolver {
+    config: LookupConfig,
+}
+
+impl DnsResolver {
+    /// A resolver using the DNS settings from `config`
+    pub fn new(config: &LookupConfig) -> Self {
+        DnsResolver { config: config.clone() }
+    }
+}
+
+impl Resolver for DnsResolver {
+    fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError> {
+        Ok(resolve_canonical_name(alias, &self.config)?.map(|resolved| SmlRecord {
+            alias: alias.to_string(),
+            hostname: resolved.name,
+            ttl: Some(resolved.ttl),
+            addresses: resolved.addresses,
+        }))
+    }
+}
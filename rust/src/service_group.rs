@@ -0,0 +1,30 @@
+//! Results of reading an SMP service group.
+
+use std::time::Duration;
+
+use crate::document::DocumentTypeIdentifier;
+use crate::extension::Extension;
+use crate::warning::LookupWarning;
+
+/// A participant's service group, as served by their SMP
+///
+/// Besides the document types, this records which SMP software served it,
+/// which helps correlate parsing quirks with specific SMP implementations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceGroup {
+    /// The document type identifiers the participant accepts
+    pub document_types: Vec<DocumentTypeIdentifier>,
+    /// The SMP's `Server` response header, e.g. `phoss-SMP/7.1.0`, if it sent one
+    pub smp_software: Option<String>,
+    /// Extensions on the service group, such as an OpenPeppol business card
+    pub extensions: Vec<Extension>,
+    /// How long the SMP allows the service group to be cached, from its
+    /// `Cache-Control` or `Expires` header, if it sent either
+    pub max_age: Option<Duration>,
+    /// References that were skipped because their `href` couldn't be read
+    ///
+    /// See [`LookupWarning::InvalidServiceReference`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warnings: Vec<LookupWarning>,
+}
@@ -0,0 +1,496 @@
+//! Verification of the XMLDSig signature on `SignedServiceMetadata` responses.
+//!
+//! SMPs sign every service metadata document so a man-in-the-middle cannot
+//! substitute a forged endpoint. Verification follows the XMLDSig core rules
+//! for the enveloped signatures SMPs produce:
+//!
+//! 1. The signed document (minus the `Signature` element) is canonicalized and
+//!    its digest compared to the `DigestValue` of the reference
+//! 2. The canonicalized `SignedInfo` is checked against the `SignatureValue`
+//!    using the certificate in `KeyInfo`
+//! 3. That certificate must chain to one of the trusted SMP CA certificates
+
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::{digest, signature};
+use roxmltree::{Document, Node, NodeType};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::parse_x509_certificate;
+
+use crate::certificate::Certificate;
+use crate::error::PeppolError;
+
+const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+const ENVELOPED_SIGNATURE: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+const C14N: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315";
+const C14N_WITH_COMMENTS: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315#WithComments";
+const EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const EXC_C14N_WITH_COMMENTS: &str = "http://www.w3.org/2001/10/xml-exc-c14n#WithComments";
+
+/// Certificates trusted to issue SMP signing certificates
+///
+/// For the PEPPOL network these are the OpenPeppol SMP CA certificates for
+/// the environment being queried (production or test).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustStore {
+    anchors: Vec<Certificate>,
+}
+
+impl TrustStore {
+    /// Create an empty trust store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust a CA certificate
+    pub fn add(&mut self, certificate: Certificate) {
+        self.anchors.push(certificate);
+    }
+
+    /// Trust every certificate in a PEM bundle
+    pub fn add_pem(&mut self, pem: &str) -> Result<(), PeppolError> {
+        self.anchors.extend(Certificate::all_from_pem(pem)?);
+        Ok(())
+    }
+
+    /// The trusted CA certificates
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.anchors
+    }
+
+    /// Whether no CA certificates have been added
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+}
+
+/// Verify the enveloped signature of a `SignedServiceMetadata` document
+///
+/// Returns the certificate the document was signed with. Fails with
+/// `PeppolError::Signature` if the digest or signature don't match, or if the
+/// signing certificate isn't issued by a certificate in `trust_store`. The
+/// `Signature` must be a child of the `SignedServiceMetadata` root and
+/// reference at least the whole document. It isn't part of what it signs, so
+/// callers must only read the root's `ServiceMetadata`, never the signature's
+/// own content.
+pub fn verify_signature(xml: &str, trust_store: &TrustStore) -> Result<Certificate, PeppolError> {
+    let document = Document::parse(xml).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "SignedServiceMetadata" {
+        return Err(signature_error(&format!("expected SignedServiceMetadata, found {}", root.tag_name().name())));
+    }
+    let signature = dsig_children(root, "Signature")
+        .next()
+        .ok_or_else(|| signature_error("no Signature element found"))?;
+    let signed_info = dsig_child(signature, "SignedInfo")?;
+
+    let mut references = 0;
+    for reference in dsig_children(signed_info, "Reference") {
+        verify_reference(&document, signature, reference)?;
+        references += 1;
+    }
+    // Without a reference the signature covers nothing of the document
+    if references == 0 {
+        return Err(signature_error("SignedInfo has no Reference"));
+    }
+
+    let method = Canonicalization::from_algorithm(algorithm(dsig_child(signed_info, "CanonicalizationMethod")?)?)?;
+    let canonical_signed_info = canonicalize(signed_info, None, method);
+    let signature_value = decode_base64(dsig_child(signature, "SignatureValue")?)?;
+
+    let mut certificates = dsig_child(signature, "KeyInfo")?
+        .descendants()
+        .filter(|node| node.has_tag_name((DSIG_NS, "X509Certificate")))
+        .map(|node| Certificate::from_base64(node.text().unwrap_or_default()));
+    let signer = certificates
+        .next()
+        .ok_or_else(|| signature_error("KeyInfo has no X509Certificate"))??;
+    let intermediates = certificates.collect::<Result<Vec<_>, _>>()?;
+
+    let signature_method = algorithm(dsig_child(signed_info, "SignatureMethod")?)?;
+    verify_signature_value(signature_method, &signer, canonical_signed_info.as_bytes(), &signature_value)?;
+    verify_chain(&signer, &intermediates, trust_store)?;
+
+    Ok(signer)
+}
+
+/// Check the digest of a same-document `Reference`
+fn verify_reference(document: &Document, signature: Node, reference: Node) -> Result<(), PeppolError> {
+    if let Some(uri) = reference.attribute("URI").filter(|uri| !uri.is_empty()) {
+        return Err(signature_error(&format!("unsupported Reference URI {}", uri)));
+    }
+
+    // Without a canonicalization transform, the node-set is serialized with
+    // inclusive C14N. A same-document reference never includes comments.
+    let mut method = Canonicalization { exclusive: false, with_comments: false };
+    let mut enveloped = false;
+    if let Ok(transforms) = dsig_child(reference, "Transforms") {
+        for transform in dsig_children(transforms, "Transform") {
+            match algorithm(transform)? {
+                ENVELOPED_SIGNATURE => enveloped = true,
+                other => {
+                    method = Canonicalization::from_algorithm(other)?;
+                    method.with_comments = false;
+                }
+            }
+        }
+    }
+    if !enveloped {
+        return Err(signature_error("Reference is missing the enveloped-signature transform"));
+    }
+
+    let canonical = canonicalize(document.root(), Some(signature), method);
+    let algorithm = match algorithm(dsig_child(reference, "DigestMethod")?)? {
+        "http://www.w3.org/2000/09/xmldsig#sha1" => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        "http://www.w3.org/2001/04/xmlenc#sha256" => &digest::SHA256,
+        "http://www.w3.org/2001/04/xmldsig-more#sha384" => &digest::SHA384,
+        "http://www.w3.org/2001/04/xmlenc#sha512" => &digest::SHA512,
+        other => return Err(signature_error(&format!("unsupported DigestMethod {}", other))),
+    };
+    let expected = decode_base64(dsig_child(reference, "DigestValue")?)?;
+
+    if digest::digest(algorithm, canonical.as_bytes()).as_ref() != expected.as_slice() {
+        return Err(signature_error("digest of the signed document does not match"));
+    }
+    Ok(())
+}
+
+/// Check `SignatureValue` against the signer's public key
+fn verify_signature_value(method: &str, signer: &Certificate, message: &[u8], value: &[u8]) -> Result<(), PeppolError> {
+    let algorithm: &dyn signature::VerificationAlgorithm = match method {
+        "http://www.w3.org/2000/09/xmldsig#rsa-sha1" => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+        "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+        "http://www.w3.org/2001/04/xmldsig-more#rsa-sha384" => &signature::RSA_PKCS1_2048_8192_SHA384,
+        "http://www.w3.org/2001/04/xmldsig-more#rsa-sha512" => &signature::RSA_PKCS1_2048_8192_SHA512,
+        "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256" => &signature::ECDSA_P256_SHA256_FIXED,
+        "http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha384" => &signature::ECDSA_P384_SHA384_FIXED,
+        other => return Err(signature_error(&format!("unsupported SignatureMethod {}", other))),
+    };
+
+    let parsed = parse(signer)?;
+    signature::UnparsedPublicKey::new(algorithm, &parsed.public_key().subject_public_key.data)
+        .verify(message, value)
+        .map_err(|_| signature_error("SignatureValue does not match SignedInfo"))
+}
+
+/// Check that `signer` chains to a trusted certificate, via `intermediates` if needed
+///
+/// Every issuer in the chain, the trusted one included, must be a CA
+/// certificate allowed to sign certificates, so an SMP's own certificate
+/// can't vouch for another.
+fn verify_chain(signer: &Certificate, intermediates: &[Certificate], trust_store: &TrustStore) -> Result<(), PeppolError> {
+    if trust_store.is_empty() {
+        return Err(signature_error("no trusted SMP CA certificates configured"));
+    }
+
+    let mut current = signer.clone();
+    // Each intermediate can be used at most once, which bounds the walk
+    for _ in 0..=intermediates.len() {
+        ensure_valid_now(&current)?;
+        if let Some(anchor) = trust_store.certificates().iter().find(|anchor| issued_by(&current, anchor)) {
+            ensure_can_issue(anchor)?;
+            return ensure_valid_now(anchor);
+        }
+        match intermediates.iter().find(|intermediate| issued_by(&current, intermediate)) {
+            Some(intermediate) => {
+                ensure_can_issue(intermediate)?;
+                current = intermediate.clone();
+            }
+            None => break,
+        }
+    }
+
+    Err(signature_error(&format!(
+        "signing certificate {} is not issued by a trusted SMP CA",
+        signer.subject
+    )))
+}
+
+/// Whether `issuer` issued and signed `certificate`
+fn issued_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+    if certificate.issuer != issuer.subject {
+        return false;
+    }
+    match (parse(certificate), parse(issuer)) {
+        (Ok(certificate), Ok(issuer)) => certificate.verify_signature(Some(issuer.public_key())).is_ok(),
+        _ => false,
+    }
+}
+
+/// Check that `certificate` is a CA (basicConstraints `CA:TRUE`) and, if it
+/// restricts its key usage, that it may sign certificates (`keyCertSign`)
+fn ensure_can_issue(certificate: &Certificate) -> Result<(), PeppolError> {
+    let parsed = parse(certificate)?;
+    let invalid = |_| PeppolError::Certificate(format!("certificate {} has invalid extensions", certificate.subject));
+    let is_ca = parsed.basic_constraints().map_err(invalid)?.is_some_and(|constraints| constraints.value.ca);
+    let can_sign_certificates = parsed.key_usage().map_err(invalid)?.is_none_or(|usage| usage.value.key_cert_sign());
+    if is_ca && can_sign_certificates {
+        Ok(())
+    } else {
+        Err(signature_error(&format!("certificate {} is not a CA allowed to issue certificates", certificate.subject)))
+    }
+}
+
+fn ensure_valid_now(certificate: &Certificate) -> Result<(), PeppolError> {
+    if parse(certificate)?.validity().is_valid() {
+        Ok(())
+    } else {
+        Err(signature_error(&format!("certificate {} is not currently valid", certificate.subject)))
+    }
+}
+
+fn parse(certificate: &Certificate) -> Result<X509Certificate<'_>, PeppolError> {
+    parse_x509_certificate(certificate.der())
+        .map(|(_, parsed)| parsed)
+        .map_err(|err| PeppolError::Certificate(err.to_string()))
+}
+
+/// A canonicalization algorithm, as selected by its URI
+#[derive(Debug, Clone, Copy)]
+struct Canonicalization {
+    exclusive: bool,
+    with_comments: bool,
+}
+
+impl Canonicalization {
+    fn from_algorithm(uri: &str) -> Result<Self, PeppolError> {
+        let (exclusive, with_comments) = match uri {
+            C14N => (false, false),
+            C14N_WITH_COMMENTS => (false, true),
+            EXC_C14N => (true, false),
+            EXC_C14N_WITH_COMMENTS => (true, true),
+            other => return Err(signature_error(&format!("unsupported canonicalization {}", other))),
+        };
+        Ok(Canonicalization { exclusive, with_comments })
+    }
+}
+
+/// Serialize `node` as canonical XML, leaving out the `exclude` subtree
+///
+/// `node` is either the document root (whole-document references) or an
+/// element (`SignedInfo`), which is canonicalized as a document subset.
+fn canonicalize(node: Node, exclude: Option<Node>, method: Canonicalization) -> String {
+    let mut canonicalizer = Canonicalizer {
+        input: node.document().input_text(),
+        exclude: exclude.map(|node| node.id()),
+        method,
+        output: String::new(),
+    };
+
+    if node.node_type() == NodeType::Root {
+        let mut before_root = true;
+        for child in node.children() {
+            match child.node_type() {
+                NodeType::Element => {
+                    canonicalizer.element(child, &BTreeMap::new());
+                    before_root = false;
+                }
+                NodeType::PI | NodeType::Comment => {
+                    if child.is_comment() && !method.with_comments {
+                        continue;
+                    }
+                    if !before_root {
+                        canonicalizer.output.push('\n');
+                    }
+                    canonicalizer.node(child, &BTreeMap::new());
+                    if before_root {
+                        canonicalizer.output.push('\n');
+                    }
+                }
+                _ => {}
+            }
+        }
+    } else {
+        canonicalizer.node(node, &BTreeMap::new());
+    }
+
+    canonicalizer.output
+}
+
+struct Canonicalizer<'a> {
+    input: &'a str,
+    exclude: Option<roxmltree::NodeId>,
+    method: Canonicalization,
+    output: String,
+}
+
+/// Namespace declarations already rendered by output ancestors, keyed by prefix
+/// (`""` for the default namespace)
+type Rendered<'a> = BTreeMap<&'a str, &'a str>;
+
+impl<'a> Canonicalizer<'a> {
+    fn node(&mut self, node: Node<'a, 'a>, rendered: &Rendered<'a>) {
+        match node.node_type() {
+            NodeType::Element => self.element(node, rendered),
+            NodeType::Text => escape_text(node.text().unwrap_or_default(), &mut self.output),
+            NodeType::Comment if self.method.with_comments => {
+                self.output.push_str("<!--");
+                self.output.push_str(node.text().unwrap_or_default());
+                self.output.push_str("-->");
+            }
+            NodeType::PI => {
+                if let Some(pi) = node.pi() {
+                    self.output.push_str("<?");
+                    self.output.push_str(pi.target);
+                    if let Some(value) = pi.value.filter(|value| !value.is_empty()) {
+                        self.output.push(' ');
+                        self.output.push_str(value);
+                    }
+                    self.output.push_str("?>");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn element(&mut self, node: Node<'a, 'a>, rendered: &Rendered<'a>) {
+        if Some(node.id()) == self.exclude {
+            return;
+        }
+
+        let qname = element_qname(self.input, node);
+        let mut rendered = rendered.clone();
+        let mut declarations = Vec::new();
+        for (prefix, uri) in self.namespaces_to_render(node, qname) {
+            let already_rendered = rendered.get(prefix).copied().unwrap_or("");
+            if already_rendered != uri {
+                declarations.push((prefix, uri));
+                rendered.insert(prefix, uri);
+            }
+        }
+        declarations.sort();
+
+        let mut attributes: Vec<_> = node.attributes().collect();
+        attributes.sort_by_key(|attribute| (attribute.namespace().unwrap_or(""), attribute.name()));
+
+        self.output.push('<');
+        self.output.push_str(qname);
+        for (prefix, uri) in declarations {
+            self.output.push_str(" xmlns");
+            if !prefix.is_empty() {
+                self.output.push(':');
+                self.output.push_str(prefix);
+            }
+            self.output.push_str("=\"");
+            escape_attribute(uri, &mut self.output);
+            self.output.push('"');
+        }
+        for attribute in attributes {
+            self.output.push(' ');
+            self.output.push_str(&self.input[attribute.range_qname()]);
+            self.output.push_str("=\"");
+            escape_attribute(attribute.value(), &mut self.output);
+            self.output.push('"');
+        }
+        self.output.push('>');
+
+        for child in node.children() {
+            self.node(child, &rendered);
+        }
+
+        self.output.push_str("</");
+        self.output.push_str(qname);
+        self.output.push('>');
+    }
+
+    /// Candidate namespace declarations for `node`, as (prefix, URI) pairs
+    ///
+    /// Inclusive C14N considers every namespace in scope; exclusive C14N only
+    /// those visibly used by the element or its attributes. An empty URI for
+    /// the default namespace stands for `xmlns=""`.
+    fn namespaces_to_render(&self, node: Node<'a, 'a>, qname: &'a str) -> Vec<(&'a str, &'a str)> {
+        let in_scope = |prefix: &str| {
+            node.namespaces()
+                .find(|namespace| namespace.name().unwrap_or("") == prefix)
+                .map(|namespace| namespace.uri())
+                .unwrap_or("")
+        };
+
+        if !self.method.exclusive {
+            let mut namespaces: Vec<_> = node
+                .namespaces()
+                .filter(|namespace| namespace.name() != Some("xml"))
+                .map(|namespace| (namespace.name().unwrap_or(""), namespace.uri()))
+                .collect();
+            if node.default_namespace().is_none() {
+                namespaces.push(("", ""));
+            }
+            return namespaces;
+        }
+
+        let mut prefixes = vec![qname.split_once(':').map(|(prefix, _)| prefix).unwrap_or("")];
+        for attribute in node.attributes() {
+            if let Some((prefix, _)) = self.input[attribute.range_qname()].split_once(':') {
+                if prefix != "xml" && !prefixes.contains(&prefix) {
+                    prefixes.push(prefix);
+                }
+            }
+        }
+        prefixes.into_iter().map(|prefix| (prefix, in_scope(prefix))).collect()
+    }
+}
+
+/// The qualified name of an element as written in the input, e.g. `ds:Signature`
+fn element_qname<'a>(input: &'a str, node: Node) -> &'a str {
+    let tag = &input[node.range().start + 1..];
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+fn escape_text(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\r' => output.push_str("&#xD;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+fn escape_attribute(value: &str, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '"' => output.push_str("&quot;"),
+            '\t' => output.push_str("&#x9;"),
+            '\n' => output.push_str("&#xA;"),
+            '\r' => output.push_str("&#xD;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+fn dsig_children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.has_tag_name((DSIG_NS, name)))
+}
+
+fn dsig_child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Result<Node<'a, 'input>, PeppolError> {
+    node.children()
+        .find(|child| child.has_tag_name((DSIG_NS, name)))
+        .ok_or_else(|| signature_error(&format!("missing {} element", name)))
+}
+
+fn algorithm<'a>(node: Node<'a, '_>) -> Result<&'a str, PeppolError> {
+    node.attribute("Algorithm")
+        .ok_or_else(|| signature_error(&format!("{} has no Algorithm", node.tag_name().name())))
+}
+
+fn decode_base64(node: Node) -> Result<Vec<u8>, PeppolError> {
+    let cleaned: String = node.text().unwrap_or_default().chars().filter(|c| !c.is_whitespace()).collect();
+    STANDARD
+        .decode(cleaned)
+        .map_err(|err| signature_error(&format!("invalid base64 in {}: {}", node.tag_name().name(), err)))
+}
+
+fn signature_error(reason: &str) -> PeppolError {
+    PeppolError::Signature(reason.to_string())
+}
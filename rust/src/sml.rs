@@ -0,0 +1,48 @@
+//! Results of the SML step.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+#[cfg(feature = "lookup")]
+use crate::config::SmpScheme;
+#[cfg(feature = "lookup")]
+use crate::lookup::smp_base_urls;
+
+/// Where a participant's SMP was found
+///
+/// The SML publishes each participant under an alias derived from the hash of
+/// their ID. That alias is a CNAME pointing at the SMP that actually hosts
+/// their metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmlRecord {
+    /// The `b-<md5>.<scheme>.<sml domain>` name that was queried
+    pub alias: String,
+    /// The SMP hostname at the end of the CNAME chain
+    ///
+    /// Equal to `alias` if the SML answered with an address directly.
+    pub hostname: String,
+    /// How long the DNS answer may be cached, if the resolver reported it
+    pub ttl: Option<Duration>,
+    /// The IPv4 and IPv6 addresses the SML name resolved to
+    ///
+    /// These are where requests to the SMP go, for correlating with firewall
+    /// logs. Empty if the resolver didn't report them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub addresses: Vec<IpAddr>,
+}
+
+#[cfg(feature = "lookup")]
+impl SmlRecord {
+    /// The base URL of the SMP, e.g. `https://smp.example.com`
+    ///
+    /// For requests to SMP endpoints this crate doesn't wrap: append the REST
+    /// path, such as `/businesscard/<participant>`. The scheme is the one
+    /// lookups try first with `smp_scheme`; with the opt-in
+    /// [`SmpScheme::HttpsWithHttpFallback`] that is HTTPS, and the `http://`
+    /// equivalent is what they fall back to. A hostname that is already a
+    /// full URL (e.g. from NAPTR discovery) is returned without a trailing slash.
+    pub fn smp_base_url(&self, smp_scheme: SmpScheme) -> String {
+        smp_base_urls(&self.hostname, smp_scheme).0
+    }
+}
@@ -0,0 +1,204 @@
+//! The HTTP step of a lookup, behind a trait so it can be replaced.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+
+use crate::config::LookupConfig;
+use crate::error::PeppolError;
+use crate::http;
+
+/// Fetches documents from SMPs
+///
+/// [`HttpSmpClient`] is the real implementation. Tests can implement this to
+/// serve fixture XML without a server, and pass it to
+/// [`LookupClientBuilder::smp_client`](crate::client::LookupClientBuilder::smp_client).
+pub trait SmpClient: Send + Sync {
+    /// Fetch the body of `url`
+    ///
+    /// `fallback` is a plain HTTP variant of `url` to try if no connection can
+    /// be made to it. Implementations that don't connect anywhere can ignore it.
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError>;
+
+    /// Like [`get`](Self::get), also returning the response's `Server` header
+    ///
+    /// Used to report the SMP software in a [`ServiceGroup`](crate::service_group::ServiceGroup).
+    /// The default implementation reports no header.
+    fn get_with_server(&self, url: &str, fallback: Option<&str>) -> Result<(String, Option<String>), PeppolError> {
+        Ok((self.get(url, fallback)?, None))
+    }
+
+    /// Like [`get`](Self::get), also returning the headers lookups use
+    ///
+    /// Used to report the SMP software and to decide how long a cached
+    /// document list stays fresh. The default implementation takes the body
+    /// and `Server` header from [`get_with_server`](Self::get_with_server),
+    /// and reports no cache lifetime.
+    fn get_response(&self, url: &str, fallback: Option<&str>) -> Result<SmpResponse, PeppolError> {
+        let (body, server) = self.get_with_server(url, fallback)?;
+        Ok(SmpResponse { body, server, max_age: None })
+    }
+}
+
+/// The body of an SMP response, with the headers lookups use
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmpResponse {
+    /// The response body
+    pub body: String,
+    /// The `Server` header, which usually names the SMP software
+    pub server: Option<String>,
+    /// How long the response may be cached, from `Cache-Control` or `Expires`
+    pub max_age: Option<Duration>,
+}
+
+/// Fetches SMP documents over HTTP with a reused blocking reqwest client
+///
+/// Uses the timeouts, pool and TLS settings, retry policy, concurrency limit
+/// and rate limit from the [`LookupConfig`] it was created with. Clones share
+/// the connection pool and the limits.
+#[derive(Debug, Clone)]
+pub struct HttpSmpClient {
+    client: reqwest::blocking::Client,
+    config: Arc<LookupConfig>,
+    deadline: Option<Instant>,
+    limit: Option<Arc<Limit>>,
+    rate_limit: Option<Arc<RateLimit>>,
+}
+
+impl HttpSmpClient {
+    /// A client using the HTTP settings from `config`
+    pub fn new(config: &LookupConfig) -> Result<Self, PeppolError> {
+        Ok(HttpSmpClient {
+            client: http::blocking_client(config)?,
+            config: Arc::new(config.clone()),
+            deadline: None,
+            limit: config.max_concurrent_requests.map(|max| Arc::new(Limit::new(max))),
+            rate_limit: config.smp_requests_per_second.map(|max| Arc::new(RateLimit::new(max))),
+        })
+    }
+
+    /// Fail requests with `PeppolError::DeadlineExceeded` once `deadline` has passed
+    ///
+    /// Each request's timeout is cut to the time left until then.
+    pub(crate) fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// GET `url`, waiting first if the request rate or the number of requests in flight is limited
+    fn send(&self, url: &str, fallback: Option<&str>) -> Result<http::Response, PeppolError> {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.wait(url, self.deadline)?;
+        }
+        let _permit = self.limit.as_ref().map(|limit| limit.acquire(self.deadline)).transpose()?;
+        http::get_blocking(&self.client, url, fallback, &self.config, self.deadline)
+    }
+}
+
+impl SmpClient for HttpSmpClient {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        Ok(self.send(url, fallback)?.body)
+    }
+
+    fn get_with_server(&self, url: &str, fallback: Option<&str>) -> Result<(String, Option<String>), PeppolError> {
+        let response = self.send(url, fallback)?;
+        Ok((response.body, response.server))
+    }
+
+    fn get_response(&self, url: &str, fallback: Option<&str>) -> Result<SmpResponse, PeppolError> {
+        let response = self.send(url, fallback)?;
+        Ok(SmpResponse {
+            body: response.body,
+            server: response.server,
+            max_age: response.max_age,
+        })
+    }
+}
+
+/// A counting semaphore bounding the requests in flight through one client
+#[derive(Debug)]
+struct Limit {
+    max: usize,
+    in_flight: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A slot taken from a [`Limit`], given back when dropped
+struct Permit<'a>(&'a Limit);
+
+impl Limit {
+    fn new(max: usize) -> Self {
+        Limit {
+            // A limit of 0 would block every request forever
+            max: max.max(1),
+            in_flight: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait for a free slot, failing with `PeppolError::DeadlineExceeded` once `deadline` passes
+    fn acquire(&self, deadline: Option<Instant>) -> Result<Permit<'_>, PeppolError> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+        while *in_flight >= self.max {
+            in_flight = match deadline {
+                Some(deadline) => {
+                    let remaining = http::remaining(deadline)?;
+                    self.released.wait_timeout(in_flight, remaining).unwrap_or_else(PoisonError::into_inner).0
+                }
+                None => self.released.wait(in_flight).unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+        *in_flight += 1;
+        Ok(Permit(self))
+    }
+}
+
+/// Spaces out the requests to each host through one client
+#[derive(Debug)]
+struct RateLimit {
+    interval: Duration,
+    /// When the next request to each host may be sent
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimit {
+    fn new(per_second: u32) -> Self {
+        RateLimit {
+            // A rate of 0 would block every request forever
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for the next slot for the host of `url`, failing with
+    /// `PeppolError::DeadlineExceeded` if it comes after `deadline`
+    fn wait(&self, url: &str, deadline: Option<Instant>) -> Result<(), PeppolError> {
+        let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)) else {
+            return Ok(());
+        };
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            let slot = next.get(&host).map_or(now, |&next| next.max(now));
+            if deadline.is_some_and(|deadline| slot > deadline) {
+                return Err(PeppolError::DeadlineExceeded);
+            }
+            // Hosts whose slot has passed are as good as new
+            next.retain(|_, next| *next > now);
+            next.insert(host, slot + self.interval);
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+        Ok(())
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.in_flight.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        self.0.released.notify_one();
+    }
+}
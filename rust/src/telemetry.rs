@@ -0,0 +1,49 @@
+//! Metrics for the DNS and HTTP steps, recorded through the `metrics` facade.
+//!
+//! Everything here compiles to nothing without the `metrics` feature.
+
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+
+use std::time::Instant;
+
+use crate::error::PeppolError;
+
+/// Record one SML query started at `started`
+///
+/// `found` is whether the participant is registered. A
+/// `PeppolError::NotAParticipant` counts as not found rather than as an error.
+///
+/// Records `peppol_sml_lookups_total` (labelled `result` = `found`,
+/// `not_found` or `error`), `peppol_sml_errors_total` and the
+/// `peppol_sml_lookup_duration_seconds` histogram.
+pub(crate) fn sml_lookup(started: Instant, found: Result<bool, &PeppolError>) {
+    #[cfg(feature = "metrics")]
+    {
+        let result = match found {
+            Ok(true) => "found",
+            Ok(false) | Err(PeppolError::NotAParticipant(_)) => "not_found",
+            Err(_) => "error",
+        };
+        metrics::counter!("peppol_sml_lookups_total", "result" => result).increment(1);
+        if result == "error" {
+            metrics::counter!("peppol_sml_errors_total").increment(1);
+        }
+        metrics::histogram!("peppol_sml_lookup_duration_seconds").record(started.elapsed());
+    }
+}
+
+/// Record one SMP request started at `started`, including its retries
+///
+/// Records `peppol_smp_requests_total` (labelled `result` = `ok` or `error`),
+/// `peppol_smp_errors_total` and the `peppol_smp_request_duration_seconds` histogram.
+pub(crate) fn smp_request<T>(started: Instant, result: &Result<T, PeppolError>) {
+    #[cfg(feature = "metrics")]
+    {
+        let label = if result.is_ok() { "ok" } else { "error" };
+        metrics::counter!("peppol_smp_requests_total", "result" => label).increment(1);
+        if result.is_err() {
+            metrics::counter!("peppol_smp_errors_total").increment(1);
+        }
+        metrics::histogram!("peppol_smp_request_duration_seconds").record(started.elapsed());
+    }
+}
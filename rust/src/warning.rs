@@ -0,0 +1,154 @@
+//! Non-fatal issues noticed during an otherwise successful lookup.
+
+use std::fmt;
+
+use time::OffsetDateTime;
+
+#[cfg(feature = "lookup")]
+use crate::certificate::{CertificateStatus, DEFAULT_EXPIRY_WARNING};
+#[cfg(feature = "lookup")]
+use crate::document::DocumentType;
+use crate::document::DocumentTypeIdentifier;
+#[cfg(feature = "lookup")]
+use crate::endpoint::KNOWN_TRANSPORT_PROFILES;
+
+/// Something off about a participant's metadata that doesn't stop delivery yet
+///
+/// Collected in [`ParticipantMetadata::warnings`](crate::metadata::ParticipantMetadata::warnings),
+/// so operators can be told "works, but the certificate expires in 5 days"
+/// before it becomes an error. Warnings about an endpoint name it and the
+/// document type it receives; [`ServiceGroup::warnings`](crate::service_group::ServiceGroup::warnings)
+/// additionally reports service group references that had to be skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LookupWarning {
+    /// The access point's certificate has expired
+    CertificateExpired {
+        /// The document type the endpoint receives
+        document: DocumentTypeIdentifier,
+        /// The endpoint's access point URL
+        endpoint_url: String,
+        #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
+        /// The certificate's `notAfter` date
+        not_after: OffsetDateTime,
+    },
+    /// The access point's certificate expires within [`DEFAULT_EXPIRY_WARNING`](crate::certificate::DEFAULT_EXPIRY_WARNING)
+    CertificateExpiringSoon {
+        /// The document type the endpoint receives
+        document: DocumentTypeIdentifier,
+        /// The endpoint's access point URL
+        endpoint_url: String,
+        #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
+        /// The certificate's `notAfter` date
+        not_after: OffsetDateTime,
+        /// Whole days left until the certificate expires
+        days: i64,
+    },
+    /// The endpoint's service expiration date has passed
+    EndpointExpired {
+        /// The document type the endpoint receives
+        document: DocumentTypeIdentifier,
+        /// The endpoint's access point URL
+        endpoint_url: String,
+        #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
+        /// The endpoint's `ServiceExpirationDate`
+        expired_at: OffsetDateTime,
+    },
+    /// The endpoint uses a transport profile not in [`KNOWN_TRANSPORT_PROFILES`](crate::endpoint::KNOWN_TRANSPORT_PROFILES)
+    UnknownTransportProfile {
+        /// The document type the endpoint receives
+        document: DocumentTypeIdentifier,
+        /// The endpoint's access point URL
+        endpoint_url: String,
+        /// The endpoint's `transportProfile`
+        transport_profile: String,
+    },
+    /// A `ServiceMetadataReference` was skipped because its `href` can't be read
+    InvalidServiceReference {
+        /// The `href` as the SMP sent it
+        href: String,
+        /// Why it couldn't be read, e.g. percent-encoding that isn't UTF-8
+        reason: String,
+    },
+}
+
+impl fmt::Display for LookupWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupWarning::CertificateExpired { document, endpoint_url, not_after } => write!(
+                f,
+                "{}: certificate of {} expired on {}",
+                document.value,
+                endpoint_url,
+                not_after.date()
+            ),
+            LookupWarning::CertificateExpiringSoon { document, endpoint_url, not_after, days } => write!(
+                f,
+                "{}: certificate of {} expires in {} days, on {}",
+                document.value,
+                endpoint_url,
+                days,
+                not_after.date()
+            ),
+            LookupWarning::EndpointExpired { document, endpoint_url, expired_at } => write!(
+                f,
+                "{}: endpoint {} expired on {}",
+                document.value,
+                endpoint_url,
+                expired_at.date()
+            ),
+            LookupWarning::UnknownTransportProfile { document, endpoint_url, transport_profile } => write!(
+                f,
+                "{}: endpoint {} uses unknown transport profile {}",
+                document.value, endpoint_url, transport_profile
+            ),
+            LookupWarning::InvalidServiceReference { href, reason } => {
+                write!(f, "skipped service metadata reference {}: {}", href, reason)
+            }
+        }
+    }
+}
+
+/// The warnings for the endpoints of `document_types`, as of `at`
+#[cfg(feature = "lookup")]
+pub(crate) fn collect_warnings<'a>(document_types: impl IntoIterator<Item = &'a DocumentType>, at: OffsetDateTime) -> Vec<LookupWarning> {
+    let mut warnings = Vec::new();
+    for document_type in document_types {
+        let document = document_type.identifier();
+        for endpoint in &document_type.endpoints {
+            let endpoint_url = endpoint.url.clone();
+            if let Some(certificate) = &endpoint.certificate {
+                let not_after = certificate.not_after;
+                match certificate.status(at, DEFAULT_EXPIRY_WARNING) {
+                    CertificateStatus::Expired => warnings.push(LookupWarning::CertificateExpired {
+                        document: document.clone(),
+                        endpoint_url: endpoint_url.clone(),
+                        not_after,
+                    }),
+                    CertificateStatus::ExpiringSoon { days } => warnings.push(LookupWarning::CertificateExpiringSoon {
+                        document: document.clone(),
+                        endpoint_url: endpoint_url.clone(),
+                        not_after,
+                        days,
+                    }),
+                    CertificateStatus::Valid => {}
+                }
+            }
+            if let Some(expired_at) = endpoint.service_expiration_date.filter(|expiration| *expiration <= at) {
+                warnings.push(LookupWarning::EndpointExpired {
+                    document: document.clone(),
+                    endpoint_url: endpoint_url.clone(),
+                    expired_at,
+                });
+            }
+            if !KNOWN_TRANSPORT_PROFILES.contains(&endpoint.transport_profile.as_str()) {
+                warnings.push(LookupWarning::UnknownTransportProfile {
+                    document: document.clone(),
+                    endpoint_url,
+                    transport_profile: endpoint.transport_profile.clone(),
+                });
+            }
+        }
+    }
+    warnings
+}
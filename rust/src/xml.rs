@@ -0,0 +1,472 @@
+//! Parsing of SMP XML responses.
+
+use roxmltree::{Document, Node};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+
+use crate::certificate::Certificate;
+use crate::document::{DocumentType, DocumentTypeIdentifier, ProcessId};
+use crate::endpoint::Endpoint;
+use crate::extension::{BusinessCard, Extension};
+use crate::error::PeppolError;
+use crate::participant::Participant;
+use crate::redirect::Redirect;
+use crate::warning::LookupWarning;
+
+/// Namespace prefix of the OpenPeppol business card schema versions
+const BUSINESS_CARD_NAMESPACE: &str = "http://www.peppol.eu/schema/pd/businesscard/";
+
+/// The child elements each SMP element may have, by local name
+///
+/// Follows the PEPPOL SMP 1.0 schema, plus the OASIS BDXR `EndpointURI`.
+/// Elements not listed here must not have child elements, except those in
+/// [`OPAQUE_ELEMENTS`].
+const SMP_SCHEMA: &[(&str, &[&str])] = &[
+    ("ServiceGroup", &["ParticipantIdentifier", "ServiceMetadataReferenceCollection", "Extension"]),
+    ("ServiceMetadataReferenceCollection", &["ServiceMetadataReference"]),
+    ("ServiceGroupReferenceList", &["ServiceGroupReference"]),
+    ("SignedServiceMetadata", &["ServiceMetadata", "Signature"]),
+    ("ServiceMetadata", &["ServiceInformation", "Redirect"]),
+    ("Redirect", &["CertificateUID", "Extension"]),
+    ("ServiceInformation", &["ParticipantIdentifier", "DocumentIdentifier", "ProcessList", "Extension"]),
+    ("ProcessList", &["Process"]),
+    ("Process", &["ProcessIdentifier", "ServiceEndpointList", "Extension"]),
+    ("ServiceEndpointList", &["Endpoint"]),
+    (
+        "Endpoint",
+        &[
+            "EndpointReference",
+            "EndpointURI",
+            "RequireBusinessLevelSignature",
+            "MinimumAuthenticationLevel",
+            "ServiceActivationDate",
+            "ServiceExpirationDate",
+            "Certificate",
+            "ServiceDescription",
+            "TechnicalContactUrl",
+            "TechnicalInformationUrl",
+            "Extension",
+        ],
+    ),
+    ("EndpointReference", &["Address", "ReferenceParameters", "Metadata"]),
+];
+
+/// Elements whose content belongs to other schemas and isn't checked
+const OPAQUE_ELEMENTS: &[&str] = &["Extension", "Signature", "ReferenceParameters", "Metadata"];
+
+/// Root elements of the SMP responses this crate reads
+const SMP_ROOTS: &[&str] = &["ServiceGroup", "ServiceGroupReferenceList", "SignedServiceMetadata", "ServiceMetadata"];
+
+/// Decode an SMP response body into text
+///
+/// Follows the XML rules rather than assuming UTF-8: a byte order mark wins
+/// (UTF-8 or UTF-16), then the `encoding` of the XML declaration, then UTF-8.
+/// Besides UTF-8, ISO-8859-1 and US-ASCII declarations are understood. The
+/// BOM is not part of the returned text.
+pub(crate) fn decode(body: &[u8]) -> Result<String, PeppolError> {
+    if let Some(body) = body.strip_prefix(b"\xEF\xBB\xBF") {
+        return utf8(body);
+    }
+    if let Some(body) = body.strip_prefix(b"\xFF\xFE") {
+        return utf16(body, u16::from_le_bytes);
+    }
+    if let Some(body) = body.strip_prefix(b"\xFE\xFF") {
+        return utf16(body, u16::from_be_bytes);
+    }
+
+    match declared_encoding(body).map(|encoding| encoding.to_ascii_lowercase()).as_deref() {
+        None | Some("utf-8" | "utf8" | "us-ascii" | "ascii") => utf8(body),
+        // Latin-1 bytes are the first 256 Unicode code points
+        Some("iso-8859-1" | "latin1" | "latin-1") => Ok(body.iter().map(|&byte| char::from(byte)).collect()),
+        Some(encoding) => Err(PeppolError::Xml(format!("unsupported encoding {:?}", encoding))),
+    }
+}
+
+fn utf8(body: &[u8]) -> Result<String, PeppolError> {
+    String::from_utf8(body.to_vec()).map_err(|err| PeppolError::Xml(format!("response is not valid UTF-8: {}", err)))
+}
+
+fn utf16(body: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Result<String, PeppolError> {
+    let units = body.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<_, _>>()
+        .map_err(|err| PeppolError::Xml(format!("response is not valid UTF-16: {}", err)))
+}
+
+/// The `encoding` pseudo-attribute of the XML declaration at the start of `body`, if any
+fn declared_encoding(body: &[u8]) -> Option<&str> {
+    let declaration = body.strip_prefix(b"<?xml")?;
+    let end = declaration.windows(2).position(|window| window == b"?>")?;
+    let declaration = std::str::from_utf8(&declaration[..end]).ok()?;
+    let (_, rest) = declaration.split_once("encoding")?;
+    let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|&quote| quote == '"' || quote == '\'')?;
+    let (encoding, _) = rest[1..].split_once(quote)?;
+    Some(encoding)
+}
+
+/// Extract document types from a `ServiceGroup` response
+///
+/// Decodes the `href` of each reference and parses the document identifier
+/// after `/services/`, whatever its scheme (`busdox-docid-qns`,
+/// `peppol-doctype-wildcard`, ...). References that don't have one are
+/// skipped. So are references that can't be decoded, reported in the
+/// returned warnings rather than failing the whole service group.
+pub(crate) fn parse_document_types(response: &str) -> Result<(Vec<DocumentTypeIdentifier>, Vec<LookupWarning>), PeppolError> {
+    let mut document_types = Vec::new();
+    let mut warnings = Vec::new();
+    for href in parse_service_references(response)? {
+        match urlencoding::decode(&href) {
+            Ok(decoded) => document_types.extend(identifier_in_href(&decoded)),
+            Err(err) => warnings.push(LookupWarning::InvalidServiceReference { reason: err.to_string(), href }),
+        }
+    }
+
+    Ok((document_types, warnings))
+}
+
+/// The document identifier in a `ServiceMetadataReference` href, if it has one
+pub(crate) fn document_identifier_from_href(href: &str) -> Option<DocumentTypeIdentifier> {
+    identifier_in_href(&urlencoding::decode(href).ok()?)
+}
+
+fn identifier_in_href(decoded_href: &str) -> Option<DocumentTypeIdentifier> {
+    let (_, identifier) = decoded_href.rsplit_once("/services/")?;
+    identifier.parse().ok()
+}
+
+/// Extract the `href` of every service metadata reference in a `ServiceGroup`
+///
+/// Walks `ServiceGroup/ServiceMetadataReferenceCollection/ServiceMetadataReference`.
+/// Elements are matched by local name, so any namespace prefix the SMP
+/// chooses is accepted.
+pub(crate) fn parse_service_references(response: &str) -> Result<Vec<String>, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let service_group = document.root_element();
+    if service_group.tag_name().name() != "ServiceGroup" {
+        return Err(PeppolError::Xml(format!(
+            "expected ServiceGroup, found {}",
+            service_group.tag_name().name()
+        )));
+    }
+
+    Ok(children(service_group, "ServiceMetadataReferenceCollection")
+        .flat_map(|collection| children(collection, "ServiceMetadataReference"))
+        .filter_map(|reference| reference.attribute("href"))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Extract the participants of a `ServiceGroupReferenceList` response
+///
+/// Each `ServiceGroupReference` href ends in the participant's service group
+/// path, `<scheme>::<icd>:<identifier>`. References without a valid
+/// participant identifier there are skipped.
+pub(crate) fn parse_participant_list(response: &str) -> Result<Vec<Participant>, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let list = document.root_element();
+    if list.tag_name().name() != "ServiceGroupReferenceList" {
+        return Err(PeppolError::Xml(format!(
+            "expected ServiceGroupReferenceList, found {}",
+            list.tag_name().name()
+        )));
+    }
+
+    Ok(children(list, "ServiceGroupReference")
+        .filter_map(|reference| reference.attribute("href"))
+        .filter_map(|href| {
+            let participant = urlencoding::decode(href.trim_end_matches('/').rsplit('/').next()?).ok()?.parse().ok();
+            if participant.is_none() {
+                warn!(%href, "skipping participant list entry without a valid participant");
+            }
+            participant
+        })
+        .collect())
+}
+
+/// Extract the extensions of a `ServiceGroup` response
+pub(crate) fn parse_service_group_extensions(response: &str) -> Result<Vec<Extension>, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+    Ok(extensions(document.root_element()))
+}
+
+/// Extract the document type and its processes from a `SignedServiceMetadata` response
+///
+/// Reads `ServiceInformation/DocumentIdentifier` and every
+/// `ProcessList/Process/ProcessIdentifier`, and the extensions of
+/// `ServiceMetadata` and `ServiceInformation`.
+pub(crate) fn parse_document_type(response: &str) -> Result<DocumentType, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let service_information = children(service_metadata(&document)?, "ServiceInformation")
+        .next()
+        .ok_or_else(|| PeppolError::Xml("no ServiceInformation element found".to_string()))?;
+
+    let document_identifier = children(service_information, "DocumentIdentifier")
+        .next()
+        .ok_or_else(|| PeppolError::Xml("ServiceInformation has no DocumentIdentifier".to_string()))?;
+
+    let processes = children(service_information, "ProcessList")
+        .flat_map(|list| children(list, "Process"))
+        .flat_map(|process| children(process, "ProcessIdentifier"))
+        .map(|identifier| ProcessId {
+            scheme: identifier.attribute("scheme").unwrap_or_default().to_string(),
+            value: identifier.text().unwrap_or_default().trim().to_string(),
+        })
+        .collect();
+
+    let endpoints = endpoint_nodes(service_information).map(endpoint_from_node).collect::<Result<_, _>>()?;
+
+    let extensions = service_information
+        .parent_element()
+        .into_iter()
+        .chain([service_information])
+        .flat_map(extensions)
+        .collect();
+
+    Ok(DocumentType {
+        scheme: document_identifier.attribute("scheme").unwrap_or_default().to_string(),
+        value: document_identifier.text().unwrap_or_default().trim().to_string(),
+        processes,
+        endpoints,
+        extensions,
+    })
+}
+
+/// Extract the first endpoint from a `SignedServiceMetadata` response
+///
+/// Walks `ServiceMetadata/ServiceInformation/ProcessList/Process/ServiceEndpointList/Endpoint`.
+/// The endpoint URL is read from `EndpointReference/Address` (SMP 1.0), falling
+/// back to `EndpointURI` (OASIS SMP 2.0).
+pub(crate) fn parse_endpoint(response: &str) -> Result<Endpoint, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let endpoint = children(service_metadata(&document)?, "ServiceInformation")
+        .flat_map(endpoint_nodes)
+        .next()
+        .ok_or_else(|| PeppolError::Xml("no Endpoint element found".to_string()))?;
+
+    endpoint_from_node(endpoint)
+}
+
+/// Extract every endpoint from a `SignedServiceMetadata` response, in document order
+///
+/// Like [`parse_endpoint`], across all processes and service endpoint lists.
+/// Fails if there is no endpoint at all, or if any of them is malformed.
+pub(crate) fn parse_endpoints(response: &str) -> Result<Vec<Endpoint>, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let endpoints = children(service_metadata(&document)?, "ServiceInformation")
+        .flat_map(endpoint_nodes)
+        .map(endpoint_from_node)
+        .collect::<Result<Vec<_>, _>>()?;
+    if endpoints.is_empty() {
+        return Err(PeppolError::Xml("no Endpoint element found".to_string()));
+    }
+    Ok(endpoints)
+}
+
+/// The `ServiceMetadata` element of a service metadata response
+///
+/// The root must be `SignedServiceMetadata` with a single `ServiceMetadata`
+/// child, or an unsigned `ServiceMetadata`. The parsers only read that
+/// subtree: the enveloped signature's digest leaves out `ds:Signature`, so
+/// anything placed inside it (such as a `ds:Object`) is unsigned and must
+/// never be mistaken for service metadata.
+fn service_metadata<'a, 'input>(document: &'a Document<'input>) -> Result<Node<'a, 'input>, PeppolError> {
+    let root = document.root_element();
+    match root.tag_name().name() {
+        "ServiceMetadata" => Ok(root),
+        "SignedServiceMetadata" => {
+            let mut service_metadata = children(root, "ServiceMetadata");
+            match (service_metadata.next(), service_metadata.next()) {
+                (Some(service_metadata), None) => Ok(service_metadata),
+                (None, _) => Err(PeppolError::Xml("SignedServiceMetadata has no ServiceMetadata".to_string())),
+                (Some(_), Some(_)) => Err(PeppolError::Xml("SignedServiceMetadata has more than one ServiceMetadata".to_string())),
+            }
+        }
+        other => Err(PeppolError::Xml(format!("expected SignedServiceMetadata, found {}", other))),
+    }
+}
+
+/// The `ProcessList/Process/ServiceEndpointList/Endpoint` elements of a `ServiceInformation`
+fn endpoint_nodes<'a, 'input: 'a>(service_information: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    children(service_information, "ProcessList")
+        .flat_map(|list| children(list, "Process"))
+        .flat_map(|process| children(process, "ServiceEndpointList"))
+        .flat_map(|list| children(list, "Endpoint"))
+}
+
+/// Parse one `Endpoint` element
+fn endpoint_from_node(endpoint: Node) -> Result<Endpoint, PeppolError> {
+    let url = children(endpoint, "EndpointReference")
+        .flat_map(|reference| children(reference, "Address"))
+        .chain(children(endpoint, "EndpointURI"))
+        .find_map(|node| node.text())
+        .map(|url| url.trim().to_string())
+        .ok_or_else(|| PeppolError::Xml("Endpoint has no address".to_string()))?;
+
+    let transport_profile = endpoint
+        .attribute("transportProfile")
+        .ok_or_else(|| PeppolError::Xml("Endpoint has no transportProfile".to_string()))?
+        .to_string();
+
+    let certificate = child_text(endpoint, "Certificate")
+        .map(|encoded| Certificate::from_base64(&encoded))
+        .transpose()?;
+
+    Ok(Endpoint {
+        url,
+        transport_profile,
+        service_activation_date: child_date(endpoint, "ServiceActivationDate")?,
+        service_expiration_date: child_date(endpoint, "ServiceExpirationDate")?,
+        certificate,
+        require_business_level_signature: child_bool(endpoint, "RequireBusinessLevelSignature")?,
+        minimum_authentication_level: child_text(endpoint, "MinimumAuthenticationLevel"),
+        technical_contact_url: child_text(endpoint, "TechnicalContactUrl"),
+        technical_information_url: child_text(endpoint, "TechnicalInformationUrl"),
+    })
+}
+
+/// Extract the `ServiceMetadata/Redirect` from a `SignedServiceMetadata` response, if any
+pub(crate) fn parse_redirect(response: &str) -> Result<Option<Redirect>, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+
+    let Some(redirect) = children(service_metadata(&document)?, "Redirect").next() else {
+        return Ok(None);
+    };
+
+    let href = redirect
+        .attribute("href")
+        .ok_or_else(|| PeppolError::Xml("Redirect has no href".to_string()))?
+        .to_string();
+
+    Ok(Some(Redirect {
+        href,
+        certificate_uid: child_text(redirect, "CertificateUID"),
+    }))
+}
+
+/// The `Extension` children of `node`
+fn extensions(node: Node) -> Vec<Extension> {
+    children(node, "Extension")
+        .map(|extension| Extension {
+            xml: node.document().input_text()[extension.range()].to_string(),
+            business_card: business_card(extension),
+        })
+        .collect()
+}
+
+/// The first business entity of an OpenPeppol business card inside `extension`
+fn business_card(extension: Node) -> Option<BusinessCard> {
+    let entity = extension.descendants().find(|node| {
+        node.is_element()
+            && node.tag_name().name() == "BusinessEntity"
+            && node.tag_name().namespace().is_some_and(|namespace| namespace.starts_with(BUSINESS_CARD_NAMESPACE))
+    })?;
+    let names: Vec<String> = children(entity, "Name")
+        .filter_map(|name| name.text())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    Some(BusinessCard {
+        name: names.first().cloned(),
+        names,
+        country_code: child_text(entity, "CountryCode").filter(|code| !code.is_empty()),
+        // A malformed date shouldn't hide the rest of the card
+        registration_date: child_date(entity, "RegistrationDate").ok().flatten(),
+    })
+}
+
+/// Parse the response of an SMP's business card endpoint
+///
+/// The response is a standalone OpenPeppol `BusinessCard`; its first
+/// business entity is returned.
+pub(crate) fn parse_business_card(response: &str) -> Result<BusinessCard, PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "BusinessCard" {
+        return Err(PeppolError::Xml(format!("expected a BusinessCard, found {}", root.tag_name().name())));
+    }
+    business_card(root).ok_or_else(|| PeppolError::Xml("business card has no BusinessEntity".to_string()))
+}
+
+/// Trimmed text of the first child element of `node` with the given local name
+fn child_text(node: Node, name: &str) -> Option<String> {
+    children(node, name)
+        .find_map(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Parse the `xs:boolean` text of a child element (`true`, `false`, `1` or `0`)
+fn child_bool(node: Node, name: &str) -> Result<Option<bool>, PeppolError> {
+    match child_text(node, name).as_deref() {
+        None => Ok(None),
+        Some("true" | "1") => Ok(Some(true)),
+        Some("false" | "0") => Ok(Some(false)),
+        Some(text) => Err(PeppolError::Xml(format!("invalid {} {:?}", name, text))),
+    }
+}
+
+/// Parse the `xs:dateTime` (or `xs:date`) text of a child element
+///
+/// Values without a timezone are taken to be UTC, and a bare date means
+/// midnight UTC.
+fn child_date(node: Node, name: &str) -> Result<Option<OffsetDateTime>, PeppolError> {
+    let Some(text) = child_text(node, name) else {
+        return Ok(None);
+    };
+    let invalid = || PeppolError::Xml(format!("invalid {} {:?}", name, text));
+
+    if let Ok(date_time) = OffsetDateTime::parse(&text, &Rfc3339) {
+        return Ok(Some(date_time));
+    }
+    if let Ok(date_time) = PrimitiveDateTime::parse(&text, format_description!("[year]-[month]-[day]T[hour]:[minute]:[second][optional [.[subsecond]]]")) {
+        return Ok(Some(date_time.assume_utc()));
+    }
+    let date_only = text.strip_suffix('Z').unwrap_or(&text);
+    let date = Date::parse(date_only, format_description!("[year]-[month]-[day]")).map_err(|_| invalid())?;
+    Ok(Some(date.midnight().assume_utc()))
+}
+
+/// Check that an SMP response only uses elements of the SMP schema
+///
+/// Used in strict mode, before the response is parsed. Fails with
+/// `PeppolError::Xml` naming the first unexpected element and its parent.
+/// Elements are matched by local name, like the parsers do.
+pub(crate) fn check_schema(response: &str) -> Result<(), PeppolError> {
+    let document = Document::parse(response).map_err(|err| PeppolError::Xml(err.to_string()))?;
+    let root = document.root_element();
+    if !SMP_ROOTS.contains(&root.tag_name().name()) {
+        return Err(PeppolError::Xml(format!("unexpected root element {}", root.tag_name().name())));
+    }
+    check_children(root)
+}
+
+fn check_children(node: Node) -> Result<(), PeppolError> {
+    let name = node.tag_name().name();
+    if OPAQUE_ELEMENTS.contains(&name) {
+        return Ok(());
+    }
+    let allowed = SMP_SCHEMA
+        .iter()
+        .find(|(parent, _)| *parent == name)
+        .map_or(&[][..], |(_, allowed)| *allowed);
+    for child in node.children().filter(Node::is_element) {
+        if !allowed.contains(&child.tag_name().name()) {
+            return Err(PeppolError::Xml(format!("unexpected element {} in {}", child.tag_name().name(), name)));
+        }
+        check_children(child)?;
+    }
+    Ok(())
+}
+
+/// Child elements of `node` with the given local name
+fn children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
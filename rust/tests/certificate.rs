@@ -0,0 +1,67 @@
+//! Access point certificate expiry checks.
+
+use peppol_lookup::{Certificate, CertificateStatus, Endpoint, Redirect, DEFAULT_EXPIRY_WARNING};
+use time::macros::datetime;
+use time::Duration;
+
+/// Self-signed certificate valid until 2030-01-01 00:00 UTC
+const ACCESS_POINT_PEM: &str = include_str!("fixtures/access_point.pem");
+
+fn endpoint(certificate: Option<Certificate>) -> Endpoint {
+    Endpoint {
+        url: "https://ap.example.com/as4".to_string(),
+        transport_profile: "peppol-transport-as4-v2_0".to_string(),
+        service_activation_date: None,
+        service_expiration_date: None,
+        certificate,
+        require_business_level_signature: None,
+        minimum_authentication_level: None,
+        technical_contact_url: None,
+        technical_information_url: None,
+    }
+}
+
+#[test]
+fn certificate_status_follows_not_after() {
+    let certificate = Certificate::from_pem(ACCESS_POINT_PEM).unwrap();
+    assert_eq!(certificate.not_after, datetime!(2030-01-01 00:00 UTC));
+
+    assert_eq!(certificate.status(datetime!(2029-06-01 00:00 UTC), DEFAULT_EXPIRY_WARNING), CertificateStatus::Valid);
+    assert_eq!(
+        certificate.status(datetime!(2029-12-21 12:00 UTC), DEFAULT_EXPIRY_WARNING),
+        CertificateStatus::ExpiringSoon { days: 10 }
+    );
+    assert_eq!(certificate.status(datetime!(2030-01-01 00:00 UTC), DEFAULT_EXPIRY_WARNING), CertificateStatus::Expired);
+    assert_eq!(certificate.status(datetime!(2029-06-01 00:00 UTC), Duration::days(365)), CertificateStatus::ExpiringSoon { days: 214 });
+}
+
+#[test]
+fn endpoint_certificate_status_needs_a_certificate() {
+    assert_eq!(endpoint(None).certificate_status(), None);
+
+    let endpoint = endpoint(Some(Certificate::from_pem(ACCESS_POINT_PEM).unwrap()));
+    assert_eq!(
+        endpoint.certificate_status_at(datetime!(2031-01-01 00:00 UTC), DEFAULT_EXPIRY_WARNING),
+        Some(CertificateStatus::Expired)
+    );
+    assert!(endpoint.certificate_status().is_some());
+}
+
+#[test]
+fn certificate_uid_matches_the_full_subject_or_its_identifiers() {
+    // Subject `CN=PNO000104, C=NO`
+    let certificate = Certificate::from_pem(ACCESS_POINT_PEM).unwrap();
+    for uid in ["CN=PNO000104, C=NO", "CN=PNO000104,C=NO", "C=NO, CN=PNO000104", " cn = PNO000104 , c=NO ", "PNO000104"] {
+        assert!(certificate.matches_uid(uid), "{:?} should match", uid);
+    }
+}
+
+#[test]
+fn certificate_uid_never_matches_a_shared_attribute_alone() {
+    let certificate = Certificate::from_pem(ACCESS_POINT_PEM).unwrap();
+    for uid in ["NO", "C=NO", "CN=PNO000104", "CN=PNO000104, C=SE", "CN=PNO000104, C=NO, O=Other", "pno000104", ""] {
+        assert!(!certificate.matches_uid(uid), "{:?} should not match", uid);
+    }
+    let redirect = Redirect { href: "https://smp.example.com/".to_string(), certificate_uid: Some("NO".to_string()) };
+    assert!(!redirect.is_signed_by(&certificate));
+}
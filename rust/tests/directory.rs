@@ -0,0 +1,57 @@
+//! Directory search against a local HTTP server serving a fixture response.
+#![cfg(feature = "directory")]
+
+use peppol_lookup::directory::{search_directory_with_config_async, DirectoryQuery};
+use peppol_lookup::{LookupConfig, PeppolError, RetryPolicy};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const SEARCH: &str = include_str!("fixtures/directory_search.json");
+
+fn config() -> LookupConfig {
+    LookupConfig {
+        retry: RetryPolicy::none(),
+        ..LookupConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn search_returns_parsable_matches() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/search/1.0/json"))
+        .and(query_param("name", "Snapbooks AS"))
+        .and(query_param("country", "NO"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SEARCH))
+        .mount(&server)
+        .await;
+
+    let query = DirectoryQuery::name("Snapbooks AS").country("no");
+    let matches = search_directory_with_config_async(&server.uri(), &query, &config()).await.unwrap();
+
+    assert_eq!(matches.len(), 2, "the malformed participant ID is skipped");
+    assert_eq!(matches[0].participant.to_string(), "0192:921605900");
+    assert_eq!(matches[0].names, ["Snapbooks AS"]);
+    assert_eq!(matches[0].country_code.as_deref(), Some("NO"));
+    assert_eq!(matches[0].document_types.len(), 1);
+    assert_eq!(matches[1].names, ["Snapbooks Sverige AB"]);
+    assert!(matches[1].document_types.is_empty());
+}
+
+#[tokio::test]
+async fn empty_query_is_rejected() {
+    let result = search_directory_with_config_async("http://localhost", &DirectoryQuery::default(), &config()).await;
+    assert!(matches!(result, Err(PeppolError::InvalidIdentifier(_))));
+}
+
+#[tokio::test]
+async fn malformed_response_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>maintenance</html>"))
+        .mount(&server)
+        .await;
+
+    let result = search_directory_with_config_async(&server.uri(), &DirectoryQuery::name("x"), &config()).await;
+    assert!(matches!(result, Err(PeppolError::Directory(_))));
+}
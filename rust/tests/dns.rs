@@ -0,0 +1,280 @@
+//! SML resolution against a local DNS server that only publishes AAAA records,
+//! and against a local DNS-over-HTTPS resolver.
+
+#![cfg(feature = "blocking")]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hickory_resolver::proto::op::{Message, MessageType, ResponseCode};
+use hickory_resolver::proto::rr::rdata::{A, AAAA, CNAME};
+use hickory_resolver::proto::rr::{Name, RData, Record, RecordType};
+use peppol_lookup::{
+    detect_smp_url_with_config, lookup_in_environments_with_config, lookup_with_config, lookup_with_config_async, sml_lookup_with_config,
+    sml_lookup_with_config_async, HashScheme, LookupConfig, Participant, PeppolError, SmlEnvironment,
+};
+use wiremock::matchers::{header, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Start a DNS server on localhost that answers AAAA queries with `::1` if
+/// `ipv6` is set, and every other query with an empty answer
+fn start_server(ipv6: bool) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while let Ok((len, peer)) = socket.recv_from(&mut buffer) {
+            let Ok(request) = Message::from_vec(&buffer[..len]) else {
+                continue;
+            };
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(request.recursion_desired())
+                .set_recursion_available(true)
+                .add_queries(request.queries().to_vec());
+            let query = &request.queries()[0];
+            if ipv6 && query.query_type() == RecordType::AAAA {
+                response.add_answer(Record::from_rdata(query.name().clone(), 300, RData::AAAA(AAAA(Ipv6Addr::LOCALHOST))));
+                response.set_response_code(ResponseCode::NoError);
+            } else if ipv6 {
+                response.set_response_code(ResponseCode::NoError);
+            } else {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+            let _ = socket.send_to(&response.to_vec().unwrap(), peer);
+        }
+    });
+    address
+}
+
+/// Start a DNS server on localhost that never answers
+fn start_silent_server() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while socket.recv_from(&mut buffer).is_ok() {}
+    });
+    address
+}
+
+/// Start a DNS server on localhost that publishes the participant's alias as
+/// a CNAME for `pending.test`, which has no address records
+fn start_cname_server() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while let Ok((len, peer)) = socket.recv_from(&mut buffer) {
+            let Ok(request) = Message::from_vec(&buffer[..len]) else {
+                continue;
+            };
+            let query = &request.queries()[0];
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(request.recursion_desired())
+                .set_recursion_available(true)
+                .add_queries(request.queries().to_vec());
+            if query.name().to_ascii().starts_with("b-e258de9dbe1f34f17b55d5d3cc5e7a66.") {
+                let target = Name::from_ascii("pending.test.").unwrap();
+                response.add_answer(Record::from_rdata(query.name().clone(), 600, RData::CNAME(CNAME(target))));
+            }
+            let _ = socket.send_to(&response.to_vec().unwrap(), peer);
+        }
+    });
+    address
+}
+
+fn config(server: SocketAddr) -> LookupConfig {
+    LookupConfig {
+        dns_servers: vec![server],
+        dns_timeout: Duration::from_secs(2),
+        // The test server answers with ::1
+        block_private_addresses: false,
+        ..LookupConfig::default()
+    }
+}
+
+fn participant() -> Participant {
+    "0192:921605900".parse().unwrap()
+}
+
+#[test]
+fn aaaa_record_alone_means_registered() {
+    let config = config(start_server(true));
+    let record = sml_lookup_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config).unwrap();
+    assert_eq!(record.alias, "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.sml.test");
+    assert_eq!(record.hostname, record.alias);
+    assert_eq!(record.addresses, [IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+}
+
+#[test]
+fn cname_without_addresses_means_registered() {
+    let config = config(start_cname_server());
+    let record = sml_lookup_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config).unwrap();
+    assert_eq!(record.hostname, "pending.test");
+    assert!(record.addresses.is_empty());
+    assert!(record.ttl.is_some_and(|ttl| ttl <= Duration::from_secs(600)), "{:?}", record.ttl);
+}
+
+#[tokio::test]
+async fn cname_without_addresses_means_registered_in_async_lookups() {
+    let config = config(start_cname_server());
+    let record = sml_lookup_with_config_async(&participant(), SmlEnvironment::custom("sml.test"), &config).await.unwrap();
+    assert_eq!(record.hostname, "pending.test");
+    assert!(record.addresses.is_empty());
+}
+
+#[test]
+fn nxdomain_means_not_a_participant() {
+    let config = config(start_server(false));
+    let result = sml_lookup_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config);
+    assert!(matches!(result, Err(PeppolError::NotAParticipant(_))));
+}
+
+#[test]
+fn loopback_smp_is_blocked_by_default() {
+    let config = LookupConfig { block_private_addresses: true, ..config(start_server(true)) };
+    let result = sml_lookup_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config);
+    assert!(matches!(result, Err(PeppolError::BlockedAddress(_))));
+    assert!(LookupConfig::default().block_private_addresses);
+}
+
+#[test]
+fn custom_environments_allow_local_smps_by_default() {
+    let environment = SmlEnvironment::custom("sml.test");
+    let config = LookupConfig {
+        dns_servers: vec![start_server(true)],
+        dns_timeout: Duration::from_secs(2),
+        ..LookupConfig::for_environment(environment.clone())
+    };
+    assert!(!config.block_private_addresses);
+    let record = sml_lookup_with_config(&participant(), environment, &config).unwrap();
+    assert_eq!(record.addresses, [IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+    assert!(!LookupConfig::for_environment(SmlEnvironment::Test).block_private_addresses);
+}
+
+#[test]
+fn each_environment_gets_its_own_result() {
+    let config = config(start_server(false));
+    let environments = [SmlEnvironment::custom("first.test"), SmlEnvironment::custom("second.test")];
+    let results = lookup_in_environments_with_config(&participant(), &environments, &config);
+
+    assert_eq!(results.len(), 2);
+    for ((environment, result), expected) in results.iter().zip(&environments) {
+        assert_eq!(environment, expected);
+        assert!(matches!(result, Err(PeppolError::NotAParticipant(_))));
+    }
+}
+
+#[test]
+fn time_budget_cuts_a_slow_sml_step_short() {
+    let config = LookupConfig {
+        dns_timeout: Duration::from_secs(10),
+        time_budget: Some(Duration::from_millis(300)),
+        ..config(start_silent_server())
+    };
+    let started = Instant::now();
+    let result = lookup_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config);
+    assert!(matches!(result, Err(PeppolError::DeadlineExceeded)), "{:?}", result);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn time_budget_applies_to_async_lookups() {
+    let config = LookupConfig {
+        dns_timeout: Duration::from_secs(10),
+        time_budget: Some(Duration::from_millis(300)),
+        ..config(start_silent_server())
+    };
+    let started = Instant::now();
+    let result = lookup_with_config_async(&participant(), SmlEnvironment::custom("sml.test"), &config).await;
+    assert!(matches!(result, Err(PeppolError::DeadlineExceeded)), "{:?}", result);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn auto_hash_scheme_prefers_the_md5_record() {
+    let config = config(start_server(true));
+    let (smp_url, scheme) = detect_smp_url_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config).unwrap();
+    assert_eq!(smp_url, "http://b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.sml.test");
+    assert_eq!(scheme, HashScheme::Md5);
+}
+
+#[test]
+fn auto_hash_scheme_without_any_record_is_not_a_participant() {
+    let config = config(start_server(false));
+    let result = detect_smp_url_with_config(&participant(), SmlEnvironment::custom("sml.test"), &config);
+    assert!(matches!(result, Err(PeppolError::NotAParticipant(_))));
+}
+
+/// Start a DoH resolver that publishes the participant's alias as a CNAME
+/// for `smp.test`, which has an A record
+async fn start_doh_server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(header("content-type", "application/dns-message"))
+        .respond_with(|request: &wiremock::Request| {
+            let request = Message::from_vec(&request.body).unwrap();
+            let query = &request.queries()[0];
+            let smp = Name::from_ascii("smp.test.").unwrap();
+            let mut response = Message::new();
+            response
+                .set_id(request.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_available(true)
+                .add_queries(request.queries().to_vec());
+            if query.name().to_ascii().starts_with("b-e258de9dbe1f34f17b55d5d3cc5e7a66.") {
+                response.add_answer(Record::from_rdata(query.name().clone(), 600, RData::CNAME(CNAME(smp.clone()))));
+                if query.query_type() == RecordType::A {
+                    response.add_answer(Record::from_rdata(smp, 300, RData::A(A(Ipv4Addr::new(192, 0, 2, 1)))));
+                }
+            } else if query.name() != &smp {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/dns-message")
+                .set_body_bytes(response.to_vec().unwrap())
+        })
+        .mount(&server)
+        .await;
+    server
+}
+
+fn doh_config(server: &MockServer) -> LookupConfig {
+    LookupConfig {
+        dns_over_https: Some(format!("{}/dns-query", server.uri())),
+        ..LookupConfig::default()
+    }
+}
+
+#[test]
+fn dns_over_https_follows_the_cname_chain() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(start_doh_server());
+
+    let record = sml_lookup_with_config(&participant(), SmlEnvironment::custom("sml.test"), &doh_config(&server)).unwrap();
+    assert_eq!(record.hostname, "smp.test");
+    assert_eq!(record.addresses, [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]);
+    // Valid for the shortest TTL in the answer
+    assert!(record.ttl.is_some_and(|ttl| ttl <= Duration::from_secs(300) && ttl > Duration::from_secs(290)), "{:?}", record.ttl);
+}
+
+#[tokio::test]
+async fn dns_over_https_applies_to_async_lookups() {
+    let server = start_doh_server().await;
+    let config = doh_config(&server);
+
+    let record = sml_lookup_with_config_async(&participant(), SmlEnvironment::custom("sml.test"), &config).await.unwrap();
+    assert_eq!(record.hostname, "smp.test");
+    assert_eq!(record.addresses, [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]);
+
+    let unknown: Participant = "0192:123456785".parse().unwrap();
+    let result = sml_lookup_with_config_async(&unknown, SmlEnvironment::custom("sml.test"), &config).await;
+    assert!(matches!(result, Err(PeppolError::NotAParticipant(_))), "{:?}", result);
+}
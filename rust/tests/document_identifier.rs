@@ -0,0 +1,83 @@
+//! Parsing, formatting and matching of document type identifiers.
+
+use peppol_lookup::document::{BIS_BILLING_CREDITNOTE, BIS_BILLING_INVOICE, PINT_CREDITNOTE, PINT_INVOICE};
+use peppol_lookup::DocumentTypeIdentifier;
+
+const BILLING_INVOICE: &str = "busdox-docid-qns::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice\
+                               ##urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0::2.1";
+
+#[test]
+fn display_round_trips() {
+    let identifier: DocumentTypeIdentifier = BILLING_INVOICE.parse().unwrap();
+    assert_eq!(identifier.scheme, "busdox-docid-qns");
+    assert_eq!(identifier.root(), BIS_BILLING_INVOICE);
+    assert_eq!(
+        identifier.customization(),
+        Some("urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0::2.1")
+    );
+    assert_eq!(identifier.to_string(), BILLING_INVOICE);
+}
+
+#[test]
+fn bare_value_uses_default_scheme() {
+    let identifier: DocumentTypeIdentifier = BIS_BILLING_INVOICE.parse().unwrap();
+    assert_eq!(identifier, DocumentTypeIdentifier::new(BIS_BILLING_INVOICE));
+    assert_eq!(identifier.customization(), None);
+}
+
+#[test]
+fn matches_compares_root_or_full_identifier() {
+    let identifier: DocumentTypeIdentifier = BILLING_INVOICE.parse().unwrap();
+    assert!(identifier.matches(BIS_BILLING_INVOICE));
+    assert!(identifier.matches(BILLING_INVOICE));
+    assert!(!identifier.matches(BIS_BILLING_CREDITNOTE));
+    assert!(!identifier.matches(&format!("{}##urn:other", BIS_BILLING_INVOICE)));
+    assert!(!identifier.matches(&format!("other-scheme::{}", BIS_BILLING_INVOICE)));
+}
+
+#[test]
+fn wildcard_registration_accepts_specializations() {
+    let registration: DocumentTypeIdentifier = "peppol-doctype-wildcard::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice\
+                                                ##urn:peppol:pint:billing-1*::2.1"
+        .parse()
+        .unwrap();
+    assert!(registration.is_wildcard());
+
+    let accepts = |identifier: &str| registration.accepts(&identifier.parse().unwrap());
+    assert!(accepts(&format!("{}##urn:peppol:pint:billing-1::2.1", BIS_BILLING_INVOICE)));
+    assert!(accepts(&format!("busdox-docid-qns::{}##urn:peppol:pint:billing-1@jp-1::2.1", BIS_BILLING_INVOICE)));
+    assert!(!accepts(&format!("{}##urn:peppol:pint:selfbilling-1@jp-1::2.1", BIS_BILLING_INVOICE)));
+    assert!(!accepts(&format!("{}##urn:peppol:pint:billing-1@jp-1::2.1", BIS_BILLING_CREDITNOTE)));
+
+    // Outside the wildcard scheme only the same identifier is accepted
+    let exact: DocumentTypeIdentifier = BILLING_INVOICE.parse().unwrap();
+    assert!(exact.accepts(&BILLING_INVOICE.parse().unwrap()));
+    assert!(!exact.accepts(&DocumentTypeIdentifier::new(BIS_BILLING_INVOICE)));
+}
+
+#[test]
+fn empty_identifier_is_rejected() {
+    assert!("busdox-docid-qns::".parse::<DocumentTypeIdentifier>().is_err());
+}
+
+#[test]
+fn pint_identifiers_are_detected_in_every_jurisdiction() {
+    let pint: DocumentTypeIdentifier = PINT_INVOICE.parse().unwrap();
+    assert!(pint.is_pint());
+    assert!(pint.matches(BIS_BILLING_INVOICE));
+    assert!(PINT_CREDITNOTE.parse::<DocumentTypeIdentifier>().unwrap().is_pint());
+
+    let japan: DocumentTypeIdentifier = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##urn:peppol:pint:billing-1@jp-1::2.1"
+        .parse()
+        .unwrap();
+    assert!(japan.is_pint());
+    let wildcard: DocumentTypeIdentifier =
+        "peppol-doctype-wildcard::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##urn:peppol:pint:billing-1*::2.1"
+            .parse()
+            .unwrap();
+    assert!(wildcard.is_pint());
+    assert!(wildcard.accepts(&japan));
+
+    assert!(!BILLING_INVOICE.parse::<DocumentTypeIdentifier>().unwrap().is_pint());
+    assert!(!BIS_BILLING_INVOICE.parse::<DocumentTypeIdentifier>().unwrap().is_pint());
+}
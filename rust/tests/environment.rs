@@ -0,0 +1,21 @@
+//! Parsing SML environment names.
+
+use peppol_lookup::{PeppolError, SmlEnvironment};
+
+#[test]
+fn environment_names_parse_case_insensitively() {
+    for name in ["production", "Prod", " PRODUCTION "] {
+        assert_eq!(name.parse::<SmlEnvironment>().unwrap(), SmlEnvironment::Production);
+    }
+    for name in ["test", "TEST", "acc", "Acceptance"] {
+        assert_eq!(SmlEnvironment::try_from(name).unwrap(), SmlEnvironment::Test);
+    }
+}
+
+#[test]
+fn unknown_environment_names_are_an_error() {
+    let err = "staging".parse::<SmlEnvironment>().unwrap_err();
+    assert!(matches!(&err, PeppolError::InvalidEnvironment(name) if name == "staging"));
+    assert_eq!(err.to_string(), "Unknown SML environment \"staging\": expected production or test");
+    assert!(!err.is_retryable());
+}
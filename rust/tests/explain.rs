@@ -0,0 +1,72 @@
+//! Dry-run explanations of a lookup, built without contacting the SMP.
+
+#![cfg(feature = "lookup")]
+
+use peppol_lookup::lookup::BIS_BILLING_INVOICE;
+use peppol_lookup::{explain, explain_with_config, LookupConfig, Participant, SmlEnvironment, SmpScheme};
+
+fn participant() -> Participant {
+    "0192:921605900".parse().unwrap()
+}
+
+#[test]
+fn explain_computes_every_step_offline() {
+    let explanation = explain(&participant(), SmlEnvironment::Production, &[BIS_BILLING_INVOICE]);
+
+    let sml_hostname = "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.edelivery.tech.ec.europa.eu";
+    assert_eq!(explanation.hashed_id, "0192:921605900");
+    assert_eq!(explanation.md5, "e258de9dbe1f34f17b55d5d3cc5e7a66");
+    assert_eq!(explanation.sml_hostname, sml_hostname);
+    assert_eq!(explanation.smp_hostname, None);
+    assert_eq!(explanation.smp_url, format!("https://{}/iso6523-actorid-upis::0192:921605900", sml_hostname));
+    assert_eq!(explanation.smp_fallback_url, None);
+    assert_eq!(
+        explanation.document_urls,
+        [format!(
+            "https://{}/iso6523-actorid-upis::0192:921605900/services/busdox-docid-qns::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice",
+            sml_hostname
+        )]
+    );
+
+    let text = explanation.to_string();
+    assert!(text.contains("1. MD5 of \"0192:921605900\": e258de9dbe1f34f17b55d5d3cc5e7a66"));
+    assert!(text.contains("(not resolved"));
+}
+
+#[test]
+fn explain_uses_the_configured_smp_scheme() {
+    let config = LookupConfig { smp_scheme: SmpScheme::Http, ..LookupConfig::default() };
+    let explanation = explain_with_config(&participant(), SmlEnvironment::Test, &[], &config);
+
+    assert!(explanation.sml_hostname.ends_with(".acc.edelivery.tech.ec.europa.eu"));
+    assert!(explanation.smp_url.starts_with("http://b-e258de9dbe1f34f17b55d5d3cc5e7a66."));
+    assert_eq!(explanation.smp_fallback_url, None);
+    assert!(explanation.document_urls.is_empty());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn client_explain_resolves_the_smp_host() {
+    use peppol_lookup::{LookupClient, PeppolError, Resolver, SmlRecord};
+
+    struct OneResolver;
+
+    impl Resolver for OneResolver {
+        fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError> {
+            Ok(Some(SmlRecord {
+                alias: alias.to_string(),
+                hostname: "smp.example.com".to_string(),
+                ttl: None,
+                addresses: Vec::new(),
+            }))
+        }
+    }
+
+    let client = LookupClient::builder().resolver(OneResolver).build().unwrap();
+    let explanation = client.explain(&participant(), &[BIS_BILLING_INVOICE]).unwrap();
+
+    assert_eq!(explanation.smp_hostname.as_deref(), Some("smp.example.com"));
+    assert_eq!(explanation.smp_url, "https://smp.example.com/iso6523-actorid-upis::0192:921605900");
+    assert!(explanation.document_urls[0].starts_with("https://smp.example.com/iso6523-actorid-upis::0192:921605900/services/"));
+    assert!(explanation.to_string().contains("SMP host: smp.example.com"));
+}
@@ -0,0 +1,479 @@
+//! Lookups against a fake resolver and SMP client, without a network.
+
+#![cfg(feature = "blocking")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use peppol_lookup::lookup::{BIS_BILLING_CREDITNOTE, BIS_BILLING_INVOICE};
+use peppol_lookup::{CacheStats, LookupClient, LookupConfig, LookupWarning, Participant, PeppolError, Resolver, SmlEnvironment, SmlRecord, SmpClient, SmpResponse, SmpScheme};
+use time::macros::datetime;
+
+const SERVICE_GROUP: &str = include_str!("fixtures/service_group.xml");
+const SERVICE_METADATA: &str = include_str!("fixtures/service_metadata.xml");
+const ACCESS_POINT_PEM: &str = include_str!("fixtures/access_point.pem");
+
+/// Resolves Snapbooks AS (`0192:921605900`) to `smp.example.com` and nothing else
+struct FakeResolver;
+
+impl Resolver for FakeResolver {
+    fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError> {
+        if !alias.starts_with("b-e258de9dbe1f34f17b55d5d3cc5e7a66.") {
+            return Ok(None);
+        }
+        Ok(Some(SmlRecord {
+            alias: alias.to_string(),
+            hostname: "smp.example.com".to_string(),
+            ttl: None,
+            addresses: Vec::new(),
+        }))
+    }
+}
+
+/// Serves the service group for the participant URL and service metadata for anything below it
+struct FakeSmp;
+
+impl SmpClient for FakeSmp {
+    fn get(&self, url: &str, _fallback: Option<&str>) -> Result<String, PeppolError> {
+        if url.contains("/services/") {
+            Ok(SERVICE_METADATA.to_string())
+        } else {
+            Ok(SERVICE_GROUP.to_string())
+        }
+    }
+
+    fn get_with_server(&self, url: &str, fallback: Option<&str>) -> Result<(String, Option<String>), PeppolError> {
+        Ok((self.get(url, fallback)?, Some("phoss-SMP/7.1.0".to_string())))
+    }
+}
+
+/// Like [`FakeSmp`], but the credit note's service metadata is malformed
+struct BrokenCreditNoteSmp;
+
+impl SmpClient for BrokenCreditNoteSmp {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        if url.contains("CreditNote") {
+            Ok("<html>Internal error</html>".to_string())
+        } else {
+            FakeSmp.get(url, fallback)
+        }
+    }
+}
+
+/// Serves an expired endpoint with a made-up transport profile
+struct OutdatedSmp;
+
+impl SmpClient for OutdatedSmp {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        let response = FakeSmp.get(url, fallback)?;
+        Ok(response
+            .replace("2030-01-01T00:00:00Z", "2021-01-01T00:00:00Z")
+            .replace("peppol-transport-as4-v2_0", "example-transport-v1"))
+    }
+}
+
+/// Publishes the documents as PINT for Japan instead of BIS Billing 3.0
+struct PintSmp;
+
+impl SmpClient for PintSmp {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        let response = FakeSmp.get(url, fallback)?;
+        Ok(response.replace("urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0", "urn:peppol:pint:billing-1@jp-1"))
+    }
+}
+
+/// Publishes the access point's certificate on every endpoint
+struct CertifiedSmp;
+
+impl SmpClient for CertifiedSmp {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        let response = FakeSmp.get(url, fallback)?;
+        let certificate = ACCESS_POINT_PEM.lines().filter(|line| !line.starts_with("-----")).collect::<String>();
+        let element = format!("<smp:Certificate>{}</smp:Certificate>\n              <smp:ServiceDescription>", certificate);
+        Ok(response.replace("<smp:ServiceDescription>", &element))
+    }
+}
+
+fn client() -> LookupClient {
+    client_with(FakeSmp)
+}
+
+fn client_with(smp: impl SmpClient + 'static) -> LookupClient {
+    LookupClient::builder()
+        .resolver(FakeResolver)
+        .smp_client(smp)
+        .verify_signature(false)
+        .build()
+        .unwrap()
+}
+
+fn participant() -> Participant {
+    "0192:921605900".parse().unwrap()
+}
+
+#[test]
+fn sml_lookup_uses_resolver() {
+    let record = client().sml_lookup(&participant()).unwrap();
+    assert_eq!(record.alias, "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.edelivery.tech.ec.europa.eu");
+    assert_eq!(record.hostname, "smp.example.com");
+}
+
+#[test]
+fn custom_environment_uses_its_domain() {
+    let client = LookupClient::builder()
+        .environment(SmlEnvironment::custom("localhost.sml.internal."))
+        .resolver(FakeResolver)
+        .smp_client(FakeSmp)
+        .build()
+        .unwrap();
+    let record = client.sml_lookup(&participant()).unwrap();
+    assert_eq!(record.alias, "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.localhost.sml.internal");
+    assert!(!client.config().verify_signature);
+    assert!(!client.config().block_private_addresses);
+}
+
+#[test]
+fn builder_settings_end_up_in_the_client_config() {
+    let dns_server = "192.0.2.53:53".parse().unwrap();
+    let client = LookupClient::builder()
+        .environment(SmlEnvironment::Test)
+        .resolver(FakeResolver)
+        .smp_client(FakeSmp)
+        .user_agent("my-app/1.0")
+        .dns_servers([dns_server])
+        .smp_scheme(SmpScheme::Https)
+        .build()
+        .unwrap();
+    let config = client.config();
+    assert_eq!(config.user_agent, "my-app/1.0");
+    assert_eq!(config.dns_servers, [dns_server]);
+    assert_eq!(config.smp_scheme, SmpScheme::Https);
+    assert!(!config.verify_signature);
+    assert_eq!(config.request_timeout, LookupConfig::default().request_timeout);
+}
+
+#[test]
+fn smp_lookup_raw_returns_the_xml_as_sent() {
+    let (document_types, xml) = client().smp_lookup_raw(&participant()).unwrap();
+    assert_eq!(document_types, client().smp_lookup(&participant()).unwrap());
+    assert_eq!(xml, SERVICE_GROUP);
+}
+
+#[test]
+fn find_document_type_stops_at_the_first_match() {
+    // The broken credit note comes second, so it is never fetched
+    let broken = client_with(BrokenCreditNoteSmp);
+    let found = broken.find_document_type(&participant(), |document_type| !document_type.endpoints.is_empty()).unwrap();
+    assert!(found.unwrap().identifier().matches(BIS_BILLING_INVOICE));
+    assert!(broken.find_document_type(&participant(), |_| false).is_err());
+
+    let missing = client().find_document_type(&participant(), |document_type| document_type.processes.is_empty()).unwrap();
+    assert!(missing.is_none());
+}
+
+#[test]
+fn max_references_limits_the_references_followed() {
+    let client = LookupClient::builder()
+        .resolver(FakeResolver)
+        .smp_client(BrokenCreditNoteSmp)
+        .verify_signature(false)
+        .max_references(1)
+        .build()
+        .unwrap();
+
+    assert_eq!(client.smp_lookup(&participant()).unwrap().len(), 1);
+    assert_eq!(client.document_types(&participant()).unwrap().len(), 1);
+    assert_eq!(client.lookup_all_metadata(&participant()).unwrap().documents.len(), 1);
+}
+
+#[test]
+fn document_filter_skips_references_before_fetching() {
+    let client = LookupClient::builder()
+        .resolver(FakeResolver)
+        .smp_client(BrokenCreditNoteSmp)
+        .verify_signature(false)
+        .document_filter(["urn:oasis:names:specification:ubl:schema:xsd:Invoice-2"])
+        .build()
+        .unwrap();
+
+    let document_types = client.smp_lookup(&participant()).unwrap();
+    assert_eq!(document_types.len(), 1);
+    assert!(document_types[0].matches(BIS_BILLING_INVOICE));
+    // The broken credit note metadata is never requested
+    assert_eq!(client.document_types(&participant()).unwrap().len(), 1);
+}
+
+#[test]
+fn smp_base_url_includes_the_scheme() {
+    assert_eq!(client().smp_base_url(&participant()).unwrap(), "https://smp.example.com");
+
+    let record = SmlRecord {
+        alias: "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.edelivery.tech.ec.europa.eu".to_string(),
+        hostname: "http://smp.example.com:8080/smp/".to_string(),
+        ttl: None,
+        addresses: Vec::new(),
+    };
+    assert_eq!(record.smp_base_url(SmpScheme::Https), "http://smp.example.com:8080/smp");
+}
+
+#[test]
+fn unknown_participant_is_not_a_participant() {
+    let participant = "0192:000000000".parse().unwrap();
+    assert!(matches!(client().smp_lookup(&participant), Err(PeppolError::NotAParticipant(_))));
+}
+
+#[test]
+fn smp_lookup_lists_document_types() {
+    let document_types = client().smp_lookup(&participant()).unwrap();
+    assert_eq!(document_types.len(), 2);
+    assert_eq!(document_types[0].scheme, "busdox-docid-qns");
+    assert!(document_types[0].matches(BIS_BILLING_INVOICE));
+    assert_eq!(document_types[1].root(), "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote");
+}
+
+#[test]
+fn service_group_reports_smp_software() {
+    let service_group = client().service_group(&participant()).unwrap();
+    assert_eq!(service_group.document_types.len(), 2);
+    assert_eq!(service_group.smp_software.as_deref(), Some("phoss-SMP/7.1.0"));
+}
+
+#[test]
+fn service_group_extensions_include_business_card() {
+    let service_group = client().service_group(&participant()).unwrap();
+    assert_eq!(service_group.extensions.len(), 1);
+    assert!(service_group.extensions[0].xml.starts_with("<smp:Extension>"));
+    let business_card = service_group.extensions[0].business_card.as_ref().unwrap();
+    assert_eq!(business_card.name.as_deref(), Some("Snapbooks AS"));
+    assert_eq!(business_card.country_code.as_deref(), Some("NO"));
+
+    let metadata = client().lookup_all_metadata(&participant()).unwrap();
+    assert_eq!(metadata.business_card(), Some(business_card));
+}
+
+#[test]
+fn document_types_include_processes() {
+    let document_types = client().document_types(&participant()).unwrap();
+    assert_eq!(document_types.len(), 2);
+    assert_eq!(document_types[0].scheme, "busdox-docid-qns");
+    assert!(document_types[0].value.starts_with(BIS_BILLING_INVOICE));
+    assert_eq!(document_types[0].processes[0].value, "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0");
+}
+
+#[test]
+fn get_endpoint_parses_service_metadata() {
+    let endpoint = client().get_endpoint(&participant(), BIS_BILLING_INVOICE).unwrap();
+    assert_eq!(endpoint.url, "https://ap.example.com/as4");
+    assert_eq!(endpoint.transport_profile, "peppol-transport-as4-v2_0");
+    assert_eq!(endpoint.service_activation_date, Some(datetime!(2020-01-01 00:00 UTC)));
+    assert!(endpoint.is_active(datetime!(2025-06-01 00:00 UTC)));
+    assert!(!endpoint.is_active(datetime!(2030-01-01 00:00 UTC)));
+}
+
+#[test]
+fn get_endpoint_parses_optional_sender_requirements() {
+    let endpoint = client().get_endpoint(&participant(), BIS_BILLING_INVOICE).unwrap();
+    assert_eq!(endpoint.require_business_level_signature, Some(false));
+    assert_eq!(endpoint.minimum_authentication_level, None);
+    assert_eq!(endpoint.technical_contact_url.as_deref(), Some("https://ap.example.com/contact"));
+    assert_eq!(endpoint.technical_information_url.as_deref(), Some("https://ap.example.com/info"));
+}
+
+#[test]
+fn lookup_all_metadata_collects_every_document() {
+    let metadata = client().lookup_all_metadata(&participant()).unwrap();
+    assert_eq!(metadata.sml_record.hostname, "smp.example.com");
+    assert_eq!(metadata.smp_software.as_deref(), Some("phoss-SMP/7.1.0"));
+    assert_eq!(metadata.documents.len(), 2);
+    assert!(metadata.documents[0].identifier.as_ref().unwrap().matches(BIS_BILLING_INVOICE));
+    let endpoint = &metadata.document_types().next().unwrap().endpoints[0];
+    assert_eq!(endpoint.url, "https://ap.example.com/as4");
+}
+
+#[test]
+fn lookup_all_metadata_records_document_failures() {
+    let metadata = client_with(BrokenCreditNoteSmp).lookup_all_metadata(&participant()).unwrap();
+    assert_eq!(metadata.documents.len(), 2);
+    assert_eq!(metadata.document_types().count(), 1);
+    let failure = metadata.failures().next().unwrap();
+    assert!(failure.identifier.as_ref().unwrap().value.contains("CreditNote"));
+    assert!(matches!(failure.result, Err(PeppolError::Xml(_))));
+}
+
+#[test]
+fn supports_checks_documents_that_could_be_read() {
+    let metadata = client_with(BrokenCreditNoteSmp).lookup_all_metadata(&participant()).unwrap();
+    assert!(metadata.supports_bis_billing_invoice());
+    assert!(!metadata.supports_bis_billing_credit_note());
+
+    let invoice = metadata.document_types().next().unwrap().identifier();
+    assert!(metadata.supports(&invoice));
+    assert!(!metadata.supports(&BIS_BILLING_INVOICE.parse().unwrap()));
+}
+
+#[test]
+fn pint_only_participants_support_invoices() {
+    let bis = client().lookup_all_metadata(&participant()).unwrap();
+    assert!(bis.supports_invoice());
+    assert!(bis.supports_bis_billing_invoice());
+    assert!(!bis.supports_pint_invoice());
+
+    let pint = client_with(PintSmp).lookup_all_metadata(&participant()).unwrap();
+    assert!(pint.supports_invoice());
+    assert!(pint.supports_pint_invoice());
+    assert!(!pint.supports_bis_billing_invoice());
+}
+
+#[test]
+fn diff_reports_what_changed_between_snapshots() {
+    let before = client().lookup_all_metadata(&participant()).unwrap();
+    assert!(before.diff(&client().lookup_all_metadata(&participant()).unwrap()).is_empty());
+    // An unreadable document isn't a removed one
+    assert!(before.diff(&client_with(BrokenCreditNoteSmp).lookup_all_metadata(&participant()).unwrap()).is_empty());
+
+    let invoice_only = LookupClient::builder()
+        .resolver(FakeResolver)
+        .smp_client(FakeSmp)
+        .verify_signature(false)
+        .max_references(1)
+        .build()
+        .unwrap()
+        .lookup_all_metadata(&participant())
+        .unwrap();
+    let removed = before.diff(&invoice_only);
+    assert!(removed.added.is_empty() && removed.changed_endpoints.is_empty());
+    assert_eq!(removed.removed.len(), 1);
+    assert!(removed.removed[0].matches(BIS_BILLING_CREDITNOTE));
+    assert_eq!(invoice_only.diff(&before).added, removed.removed);
+
+    let outdated = before.diff(&client_with(OutdatedSmp).lookup_all_metadata(&participant()).unwrap());
+    assert!(outdated.added.is_empty() && outdated.removed.is_empty());
+    // Each document swaps its AS4 endpoint for one with another profile
+    assert_eq!(outdated.changed_endpoints.len(), 4);
+    assert_eq!(outdated.changed_endpoints[0].transport_profile, "example-transport-v1");
+    assert!(outdated.changed_endpoints[0].old.is_none());
+    assert_eq!(outdated.changed_endpoints[1].transport_profile, "peppol-transport-as4-v2_0");
+    assert!(outdated.changed_endpoints[1].new.is_none());
+
+    let certified = before.diff(&client_with(CertifiedSmp).lookup_all_metadata(&participant()).unwrap());
+    assert!(certified.changed_endpoints.is_empty());
+    assert_eq!(certified.certificate_changes.len(), 2);
+    assert!(certified.certificate_changes[0].old.is_none());
+    assert_eq!(certified.certificate_changes[0].new.as_ref().unwrap().not_after, datetime!(2030-01-01 00:00 UTC));
+    assert!(certified.to_string().contains("certificate of https://ap.example.com/as4 changed from none to CN=PNO000104"));
+}
+
+#[test]
+fn lookup_all_metadata_warns_about_outdated_endpoints() {
+    assert!(client().lookup_all_metadata(&participant()).unwrap().warnings.is_empty());
+
+    let metadata = client_with(OutdatedSmp).lookup_all_metadata(&participant()).unwrap();
+    // Two documents, each with an expired endpoint using an unknown profile
+    assert_eq!(metadata.warnings.len(), 4);
+    assert!(matches!(
+        &metadata.warnings[0],
+        LookupWarning::EndpointExpired { endpoint_url, expired_at, .. }
+            if endpoint_url == "https://ap.example.com/as4" && *expired_at == datetime!(2021-01-01 00:00 UTC)
+    ));
+    assert!(matches!(
+        &metadata.warnings[1],
+        LookupWarning::UnknownTransportProfile { transport_profile, .. } if transport_profile == "example-transport-v1"
+    ));
+    assert!(metadata.to_string().contains("Warnings:\n- urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice"));
+}
+
+#[test]
+fn participant_metadata_displays_documents_by_process() {
+    let text = client_with(BrokenCreditNoteSmp).lookup_all_metadata(&participant()).unwrap().to_string();
+    assert!(text.contains("SMP hostname: b-e258de9dbe1f34f17b55d5d3cc5e7a66."));
+    assert!(text.contains("Resolved SMP host: smp.example.com"));
+    assert!(text.contains("Process urn:fdc:peppol.eu:2017:poacc:billing:01:1.0:\n- urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice"));
+    assert!(text.contains("    peppol-transport-as4-v2_0 https://ap.example.com/as4"));
+    assert!(text.contains("Unreadable documents:\n- urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote"));
+}
+
+#[test]
+fn lookup_many_unordered_yields_every_participant_once() {
+    let client = Arc::new(client());
+    let unknown: Participant = "0192:123456785".parse().unwrap();
+    let participants = vec![participant(), unknown.clone(), participant(), unknown.clone()];
+
+    let mut results: Vec<_> = client
+        .lookup_many_unordered(participants, 3)
+        .map(|(participant, result)| (participant.to_string(), result.map(|document_types| document_types.len())))
+        .collect();
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(results.len(), 4);
+    assert!(results[..2].iter().all(|(id, result)| *id == unknown.to_string() && matches!(result, Err(PeppolError::NotAParticipant(_)))));
+    assert!(results[2..].iter().all(|(id, result)| *id == participant().to_string() && matches!(result, Ok(2))));
+}
+
+/// Like [`FakeSmp`], counting requests and sending `max_age` as the cache lifetime
+struct ExpiringSmp {
+    max_age: Option<Duration>,
+    requests: Arc<AtomicUsize>,
+}
+
+impl SmpClient for ExpiringSmp {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        FakeSmp.get(url, fallback)
+    }
+
+    fn get_response(&self, url: &str, fallback: Option<&str>) -> Result<SmpResponse, PeppolError> {
+        Ok(SmpResponse { body: self.get(url, fallback)?, server: None, max_age: self.max_age })
+    }
+}
+
+/// How many times the SMP is asked when looking up the participant three times through a cache
+fn requests_with_max_age(max_age: Option<Duration>) -> usize {
+    let requests = Arc::default();
+    let client = LookupClient::builder()
+        .resolver(FakeResolver)
+        .smp_client(ExpiringSmp { max_age, requests: Arc::clone(&requests) })
+        .verify_signature(false)
+        .cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    for _ in 0..3 {
+        assert_eq!(client.smp_lookup(&participant()).unwrap().len(), 2);
+    }
+    requests.load(Ordering::SeqCst)
+}
+
+#[test]
+fn cache_honours_the_smp_cache_lifetime() {
+    assert_eq!(requests_with_max_age(None), 1);
+    assert_eq!(requests_with_max_age(Some(Duration::from_secs(600))), 1);
+    assert_eq!(requests_with_max_age(Some(Duration::ZERO)), 3);
+}
+
+#[test]
+fn cache_entries_can_be_invalidated() {
+    let requests = Arc::default();
+    let client = LookupClient::builder()
+        .resolver(FakeResolver)
+        .smp_client(ExpiringSmp { max_age: None, requests: Arc::clone(&requests) })
+        .verify_signature(false)
+        .cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let cache = client.cache().unwrap();
+
+    client.smp_lookup(&participant()).unwrap();
+    client.smp_lookup(&participant()).unwrap();
+    // An SML record and a document list
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2, size: 2 });
+
+    let unknown: Participant = "0088:unknown".parse().unwrap();
+    assert!(!cache.invalidate(&unknown));
+    assert!(cache.invalidate(&participant()));
+    assert_eq!(cache.stats().size, 0);
+    client.smp_lookup(&participant()).unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    cache.clear();
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 4, size: 0 });
+}
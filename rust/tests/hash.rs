@@ -0,0 +1,45 @@
+//! SML hostnames computed from participant IDs.
+
+use peppol_lookup::{participant_hostname, participant_md5, HashScheme, Participant};
+
+const SML_DOMAIN: &str = "edelivery.tech.ec.europa.eu";
+
+#[test]
+fn snapbooks_hostname() {
+    assert_eq!(
+        participant_hostname("0192", "921605900", "iso6523-actorid-upis", SML_DOMAIN),
+        "b-e258de9dbe1f34f17b55d5d3cc5e7a66.iso6523-actorid-upis.edelivery.tech.ec.europa.eu"
+    );
+}
+
+#[test]
+fn mixed_case_identifier_hashes_like_lowercase() {
+    assert_eq!(participant_md5("0088", "AbC123dEf"), participant_md5("0088", "abc123def"));
+
+    let mixed: Participant = "0088:AbC123dEf".parse().unwrap();
+    let lower: Participant = "0088:abc123def".parse().unwrap();
+    for scheme in [HashScheme::Md5, HashScheme::Sha256] {
+        assert_eq!(scheme.hostname(&mixed, SML_DOMAIN), scheme.hostname(&lower, SML_DOMAIN));
+    }
+}
+
+#[test]
+fn alphanumeric_identifier_hostname() {
+    let participant = Participant::from_vat_number("DE", "DE 123 456 789").unwrap();
+    assert_eq!((participant.icd(), participant.identifier()), ("9930", "DE123456789"));
+    assert_eq!(
+        HashScheme::Md5.hostname(&participant, SML_DOMAIN),
+        "b-4a2eab1f5d7923dcf08fb441a9d493cf.iso6523-actorid-upis.edelivery.tech.ec.europa.eu"
+    );
+    assert_eq!(
+        participant_hostname("9930", "DE123456789", "ISO6523-ACTORID-UPIS", SML_DOMAIN),
+        HashScheme::Md5.hostname(&participant, SML_DOMAIN)
+    );
+
+    // Leitweg-IDs mix digits, letters and dashes
+    let leitweg: Participant = "0204:991-33A12-34".parse().unwrap();
+    assert_eq!(
+        HashScheme::Md5.hostname(&leitweg, SML_DOMAIN),
+        "b-08057a1b5bc67c85cf35512a73e815d7.iso6523-actorid-upis.edelivery.tech.ec.europa.eu"
+    );
+}
@@ -0,0 +1,148 @@
+//! Connection reuse and request concurrency towards one SMP, counted at a local server.
+
+#![cfg(feature = "blocking")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use peppol_lookup::{LookupClient, Participant, PeppolError, Resolver, SmlRecord};
+
+const SERVICE_GROUP: &str = include_str!("fixtures/service_group.xml");
+
+/// What the server has seen
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+}
+
+/// Start an HTTP/1.1 server that answers every request with the service
+/// group after a short delay, keeping connections open, and counts the
+/// connections it accepts and the requests it handles at once
+fn start_server() -> (SocketAddr, Arc<Counters>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let counters = Arc::new(Counters::default());
+    let seen = counters.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            seen.connections.fetch_add(1, Ordering::SeqCst);
+            let seen = seen.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                loop {
+                    // Read the request head; GET requests have no body
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let in_flight = seen.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    seen.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    seen.in_flight.fetch_sub(1, Ordering::SeqCst);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+                        SERVICE_GROUP.len(),
+                        SERVICE_GROUP
+                    );
+                    if stream.write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (address, counters)
+}
+
+/// Points every participant at the local server
+struct LocalResolver(SocketAddr);
+
+impl Resolver for LocalResolver {
+    fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError> {
+        Ok(Some(SmlRecord {
+            alias: alias.to_string(),
+            hostname: format!("http://{}", self.0),
+            ttl: None,
+            addresses: Vec::new(),
+        }))
+    }
+}
+
+#[test]
+fn repeated_lookups_reuse_one_connection() {
+    let (address, counters) = start_server();
+    let client = LookupClient::builder().resolver(LocalResolver(address)).build().unwrap();
+    let participant: Participant = "0192:921605900".parse().unwrap();
+
+    for _ in 0..5 {
+        assert_eq!(client.smp_lookup(&participant).unwrap().len(), 2);
+    }
+    let results = client.lookup_many(&vec![participant; 10], 1);
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn concurrent_requests_are_capped() {
+    let (address, counters) = start_server();
+    let client = LookupClient::builder()
+        .resolver(LocalResolver(address))
+        .max_concurrent_requests(2)
+        .build()
+        .unwrap();
+    let participant: Participant = "0192:921605900".parse().unwrap();
+
+    let results = client.lookup_many(&vec![participant; 12], 6);
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+    assert_eq!(counters.peak_in_flight.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn requests_to_one_smp_are_rate_limited() {
+    let (address, _) = start_server();
+    let client = LookupClient::builder()
+        .resolver(LocalResolver(address))
+        .smp_requests_per_second(20)
+        .build()
+        .unwrap();
+    let participant: Participant = "0192:921605900".parse().unwrap();
+
+    let started = Instant::now();
+    let results = client.lookup_many(&vec![participant; 11], 4);
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+    // The first request goes out at once, the other ten 50 ms apart
+    assert!(started.elapsed() >= Duration::from_millis(500), "{:?}", started.elapsed());
+}
+
+#[test]
+fn idle_connections_are_not_kept_without_a_pool() {
+    let (address, counters) = start_server();
+    let client = LookupClient::builder()
+        .resolver(LocalResolver(address))
+        .pool_max_idle_per_host(0)
+        .build()
+        .unwrap();
+    let participant: Participant = "0192:921605900".parse().unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(client.smp_lookup(&participant).unwrap().len(), 2);
+    }
+
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 3);
+}
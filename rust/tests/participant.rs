@@ -0,0 +1,39 @@
+//! Participant identifier parsing and check digits.
+
+use std::collections::HashSet;
+
+use peppol_lookup::participant::validate_checksum;
+use peppol_lookup::{Participant, PeppolError};
+
+#[test]
+fn norwegian_org_numbers_need_a_valid_check_digit() {
+    assert!("0192:921605900".parse::<Participant>().is_ok());
+    assert!(Participant::from_vat_number("NO", "NO 921 605 900 MVA").is_ok());
+
+    for invalid in ["0192:921605901", "0192:92160590", "0192:92160590X"] {
+        assert!(
+            matches!(invalid.parse::<Participant>(), Err(PeppolError::InvalidIdentifier(_))),
+            "{} should be rejected",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn icds_without_checksum_are_not_validated() {
+    assert!(validate_checksum("0208", "0123456789").is_ok());
+    assert!("0088:anything".parse::<Participant>().is_ok());
+    // Only the PEPPOL scheme's ICDs are known to have check digits
+    assert!(Participant::with_scheme("other-scheme", "0192", "123").is_ok());
+}
+
+#[test]
+fn alphanumeric_identifiers_compare_ignoring_case() {
+    let upper: Participant = "9930:DE123456789".parse().unwrap();
+    let lower: Participant = "9930:de123456789".parse().unwrap();
+    assert_eq!(upper, lower);
+    assert_eq!(upper.identifier(), "DE123456789");
+    assert_eq!(HashSet::from([upper, lower]).len(), 1);
+
+    assert_ne!("9930:DE123456789".parse::<Participant>().unwrap(), "9930:DE123456788".parse().unwrap());
+}
@@ -0,0 +1,77 @@
+//! `Display` output of the identifier types parses back to the same value.
+
+use peppol_lookup::participant::PARTICIPANT_SCHEME;
+use peppol_lookup::{DocumentTypeIdentifier, Participant, ProcessId};
+use proptest::prelude::*;
+
+/// Identifier schemes as they appear in the wild: lowercase, no `:`
+fn scheme() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9-]{0,24}"
+}
+
+/// Identifier values, which may contain `:`, `::` and `#`, but no surrounding whitespace
+fn value() -> impl Strategy<Value = String> {
+    "\\PC{1,60}".prop_filter("surrounding whitespace is trimmed", |value| value.trim() == value)
+}
+
+/// Norwegian organization numbers: eight digits and their MOD11 check digit
+fn org_number() -> impl Strategy<Value = String> {
+    proptest::collection::vec(0..10u32, 8).prop_filter_map("remainder 1 has no check digit", |digits| {
+        let sum: u32 = digits.iter().zip([3, 2, 7, 6, 5, 4, 3, 2]).map(|(digit, weight)| digit * weight).sum();
+        let check_digit = match 11 - sum % 11 {
+            11 => 0,
+            10 => return None,
+            check_digit => check_digit,
+        };
+        Some(digits.iter().chain([&check_digit]).map(|digit| char::from_digit(*digit, 10).unwrap()).collect())
+    })
+}
+
+fn participant() -> impl Strategy<Value = Participant> {
+    prop_oneof![
+        org_number().prop_map(|number| Participant::new("0192", &number).unwrap()),
+        (prop::sample::select(vec!["0007", "0088", "0208", "9925"]), value())
+            .prop_map(|(icd, identifier)| Participant::new(icd, &identifier).unwrap()),
+        (scheme(), "[0-9A-Za-z]{1,8}", value())
+            .prop_map(|(scheme, icd, identifier)| Participant::with_scheme(&scheme, &icd, &identifier).unwrap()),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn participant_round_trips(participant in participant()) {
+        let parsed: Participant = participant.to_string().parse().unwrap();
+        prop_assert_eq!(parsed, participant);
+    }
+
+    #[test]
+    fn document_type_identifier_round_trips(scheme in scheme(), value in value()) {
+        let identifier = DocumentTypeIdentifier { scheme, value };
+        let parsed: DocumentTypeIdentifier = identifier.to_string().parse().unwrap();
+        prop_assert_eq!(parsed, identifier);
+    }
+
+    #[test]
+    fn process_id_round_trips(scheme in scheme(), value in value()) {
+        let process = ProcessId { scheme, value };
+        let parsed: ProcessId = process.to_string().parse().unwrap();
+        prop_assert_eq!(parsed, process);
+    }
+}
+
+#[test]
+fn default_schemes_are_left_out_or_implied() {
+    let participant: Participant = "0192:921605900".parse().unwrap();
+    assert_eq!(participant.scheme(), PARTICIPANT_SCHEME);
+    assert_eq!(participant.to_string(), "0192:921605900");
+
+    let other: Participant = "other-scheme::0088:a::b".parse().unwrap();
+    assert_eq!((other.scheme(), other.icd(), other.identifier()), ("other-scheme", "0088", "a::b"));
+    assert_eq!(other.to_string(), "other-scheme::0088:a::b");
+    // Without a scheme, the identifier's own `::` isn't mistaken for one
+    assert_eq!("0088:a::b".parse::<Participant>().unwrap().identifier(), "a::b");
+
+    let process: ProcessId = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0".parse().unwrap();
+    assert_eq!(process.scheme, "cenbii-procid-ubl");
+    assert_eq!(process.to_string(), "cenbii-procid-ubl::urn:fdc:peppol.eu:2017:poacc:billing:01:1.0");
+}
@@ -0,0 +1,48 @@
+//! Serialized forms of lookup results.
+#![cfg(feature = "serde")]
+
+use peppol_lookup::{document_types_with_config_async, DocumentType, DocumentTypeIdentifier, LookupConfig, Participant, RetryPolicy};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const SERVICE_GROUP: &str = include_str!("fixtures/service_group.xml");
+const SERVICE_METADATA: &str = include_str!("fixtures/service_metadata.xml");
+
+const INVOICE: &str = "busdox-docid-qns::urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0::2.1";
+
+#[test]
+fn identifiers_serialize_as_their_string_form() {
+    let identifier: DocumentTypeIdentifier = INVOICE.parse().unwrap();
+    assert_eq!(serde_json::to_value(&identifier).unwrap(), INVOICE);
+    assert_eq!(serde_json::from_value::<DocumentTypeIdentifier>(INVOICE.into()).unwrap(), identifier);
+
+    let participant: Participant = "0192:921605900".parse().unwrap();
+    assert_eq!(serde_json::to_value(&participant).unwrap(), "iso6523-actorid-upis::0192:921605900");
+    assert_eq!(serde_json::from_value::<Participant>("0192:921605900".into()).unwrap(), participant);
+    assert!(serde_json::from_value::<Participant>("0192".into()).is_err());
+}
+
+#[tokio::test]
+async fn document_types_round_trip() {
+    let server = MockServer::start().await;
+    let service_group = SERVICE_GROUP.replace("http://smp.example.com", &server.uri());
+    Mock::given(method("GET"))
+        .and(path("/iso6523-actorid-upis::0192:921605900"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(service_group))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("/services/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SERVICE_METADATA))
+        .mount(&server)
+        .await;
+
+    let config = LookupConfig { verify_signature: false, retry: RetryPolicy::none(), ..LookupConfig::default() };
+    let participant = "0192:921605900".parse().unwrap();
+    let document_types = document_types_with_config_async(&server.uri(), &participant, &config).await.unwrap();
+
+    let json = serde_json::to_value(&document_types).unwrap();
+    assert_eq!(json[0]["endpoints"][0]["service_activation_date"], "2020-01-01T00:00:00Z");
+    let read_back: Vec<DocumentType> = serde_json::from_value(json).unwrap();
+    assert_eq!(read_back, document_types);
+}
@@ -0,0 +1,656 @@
+//! SMP parsing against a local HTTP server serving fixture XML.
+
+#![cfg(feature = "lookup")]
+
+use std::time::{Duration, Instant};
+
+use peppol_lookup::lookup::BIS_BILLING_INVOICE;
+use peppol_lookup::signature::{verify_signature, TrustStore};
+use peppol_lookup::{
+    business_card_with_config_async, document_types_set_with_config_async, document_types_with_config_async, get_endpoint_with_config_async, get_endpoints_with_config_async,
+    get_redirect_with_config_async, list_participants_with_config_async, service_group_with_config_async, smp_lookup_with_config_async, Certificate, LookupConfig, LookupWarning, Participant, PeppolError, RetryPolicy, DEFAULT_USER_AGENT,
+};
+use time::macros::datetime;
+use wiremock::matchers::{header, method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const BUSINESS_CARD: &str = include_str!("fixtures/business_card.xml");
+const SERVICE_GROUP: &str = include_str!("fixtures/service_group.xml");
+const SERVICE_GROUP_BOM: &[u8] = include_bytes!("fixtures/service_group_bom.xml");
+const SERVICE_GROUP_RELATIVE: &str = include_str!("fixtures/service_group_relative.xml");
+const SERVICE_GROUP_REORDERED: &str = include_str!("fixtures/service_group_reordered.xml");
+const SERVICE_GROUP_WILDCARD: &str = include_str!("fixtures/service_group_wildcard.xml");
+const SERVICE_METADATA: &str = include_str!("fixtures/service_metadata.xml");
+/// Signed by a certificate issued by `SMP_CA`, both valid until 2036
+const SIGNED_SERVICE_METADATA: &str = include_str!("fixtures/signed_service_metadata.xml");
+const SMP_CA: &str = include_str!("fixtures/smp_ca.pem");
+/// Signed by a certificate issued by the `SIGNED_SERVICE_METADATA` signer, which isn't a CA
+const SIGNED_BY_LEAF_CERTIFICATE: &str = include_str!("fixtures/signed_by_leaf_certificate.xml");
+const SERVICE_METADATA_TWO_ENDPOINTS: &str = include_str!("fixtures/service_metadata_two_endpoints.xml");
+const REDIRECT: &str = include_str!("fixtures/redirect.xml");
+const PARTICIPANT_LIST: &str = include_str!("fixtures/participant_list.xml");
+
+/// Host the fixtures are captured from, replaced by the mock server's address
+const FIXTURE_HOST: &str = "http://smp.example.com";
+
+const SERVICE_GROUP_PATH: &str = "/iso6523-actorid-upis::0192:921605900";
+
+fn participant() -> Participant {
+    "0192:921605900".parse().unwrap()
+}
+
+fn config() -> LookupConfig {
+    LookupConfig {
+        verify_signature: false,
+        retry: RetryPolicy::none(),
+        // The mock server listens on 127.0.0.1
+        block_private_addresses: false,
+        ..LookupConfig::default()
+    }
+}
+
+/// Serve `fixture` with its links pointing back at `server`
+async fn serve(server: &MockServer, mock: wiremock::MockBuilder, fixture: &str) {
+    let body = fixture.replace(FIXTURE_HOST, &server.uri());
+    mock.respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn smp_lookup_parses_service_group() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP).await;
+
+    let document_types = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(document_types.len(), 2);
+    assert!(document_types[0].matches(BIS_BILLING_INVOICE));
+    assert_eq!(document_types[1].root(), "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2::CreditNote");
+}
+
+#[tokio::test]
+async fn smp_lookup_returns_references_in_every_scheme() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP_WILDCARD).await;
+
+    let document_types = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    let schemes: Vec<_> = document_types.iter().map(|document_type| document_type.scheme.as_str()).collect();
+    assert_eq!(schemes, ["busdox-docid-qns", "peppol-doctype-wildcard", "peppol-doctype-wildcard"]);
+    assert!(document_types[1].is_wildcard());
+    assert_eq!(document_types[1].customization(), Some("urn:peppol:pint:billing-1*::2.1"));
+}
+
+#[tokio::test]
+async fn reordered_attributes_and_prefixes_parse_the_same() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP_REORDERED).await;
+
+    let reordered = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    let original: Vec<_> = {
+        let server = MockServer::start().await;
+        serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP).await;
+        smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap()
+    };
+    assert_eq!(reordered, original);
+}
+
+#[tokio::test]
+async fn get_endpoint_parses_signed_service_metadata() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/.*Invoice-2")), SERVICE_METADATA).await;
+
+    let endpoint = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config())
+        .await
+        .unwrap();
+    assert_eq!(endpoint.url, "https://ap.example.com/as4");
+    assert_eq!(endpoint.transport_profile, "peppol-transport-as4-v2_0");
+}
+
+#[tokio::test]
+async fn root_certificates_are_trusted_for_signatures() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/")), SIGNED_SERVICE_METADATA).await;
+    let verifying = LookupConfig { verify_signature: true, ..config() };
+
+    // Verifying without any trusted certificate is a configuration mistake
+    let result = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &verifying).await;
+    assert!(matches!(result, Err(PeppolError::Config(_))), "{:?}", result);
+
+    let trusting = LookupConfig { root_certificates: vec![Certificate::from_pem(SMP_CA).unwrap()], ..verifying };
+    let endpoint = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &trusting)
+        .await
+        .unwrap();
+    assert_eq!(endpoint.url, "https://ap.example.com/as4");
+}
+
+/// The signed fixture with unsigned service metadata smuggled into its `ds:Signature`
+fn wrapped_signed_service_metadata() -> String {
+    SIGNED_SERVICE_METADATA.replace(
+        "</ds:KeyInfo>",
+        "</ds:KeyInfo><ds:Object><Redirect href=\"http://evil.example/x\"/><ServiceInformation><ProcessList><Process><ServiceEndpointList>\
+         <Endpoint transportProfile=\"peppol-transport-as4-v2_0\"><EndpointURI>https://evil.example/as4</EndpointURI></Endpoint>\
+         </ServiceEndpointList></Process></ProcessList></ServiceInformation></ds:Object>",
+    )
+}
+
+#[tokio::test]
+async fn content_inside_the_signature_is_never_read() {
+    let wrapped = wrapped_signed_service_metadata();
+    let mut trust_store = TrustStore::new();
+    trust_store.add_pem(SMP_CA).unwrap();
+    // The signature doesn't cover itself, so the injected content verifies...
+    assert!(verify_signature(&wrapped, &trust_store).is_ok());
+
+    // ...but only the signed ServiceMetadata is parsed
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/")), &wrapped).await;
+    let trusting = LookupConfig { verify_signature: true, root_certificates: vec![Certificate::from_pem(SMP_CA).unwrap()], ..config() };
+    let redirect = get_redirect_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &trusting).await.unwrap();
+    assert_eq!(redirect, None);
+    let endpoints = get_endpoints_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &trusting)
+        .await
+        .unwrap();
+    let urls: Vec<_> = endpoints.iter().map(|endpoint| endpoint.url.as_str()).collect();
+    assert_eq!(urls, ["https://ap.example.com/as4"]);
+}
+
+#[test]
+fn signatures_without_references_are_rejected() {
+    let start = SIGNED_SERVICE_METADATA.find("<ds:Reference").unwrap();
+    let end = SIGNED_SERVICE_METADATA.find("</ds:Reference>").unwrap() + "</ds:Reference>".len();
+    let unreferenced = format!("{}{}", &SIGNED_SERVICE_METADATA[..start], &SIGNED_SERVICE_METADATA[end..]);
+    let mut trust_store = TrustStore::new();
+    trust_store.add_pem(SMP_CA).unwrap();
+
+    let result = verify_signature(&unreferenced, &trust_store);
+    assert!(matches!(&result, Err(PeppolError::Signature(reason)) if reason.contains("no Reference")), "{:?}", result);
+}
+
+#[test]
+fn only_ca_certificates_can_issue_signing_certificates() {
+    let mut trust_store = TrustStore::new();
+    trust_store.add_pem(SMP_CA).unwrap();
+    assert!(verify_signature(SIGNED_SERVICE_METADATA, &trust_store).is_ok());
+
+    let result = verify_signature(SIGNED_BY_LEAF_CERTIFICATE, &trust_store);
+    assert!(matches!(&result, Err(PeppolError::Signature(reason)) if reason.contains("not a CA")), "{:?}", result);
+}
+
+#[tokio::test]
+async fn get_endpoints_returns_every_endpoint_in_order() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/.*Invoice-2")), SERVICE_METADATA_TWO_ENDPOINTS).await;
+
+    let endpoints = get_endpoints_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config())
+        .await
+        .unwrap();
+    let endpoints: Vec<_> = endpoints
+        .iter()
+        .map(|endpoint| (endpoint.transport_profile.as_str(), endpoint.url.as_str()))
+        .collect();
+    assert_eq!(
+        endpoints,
+        [
+            ("busdox-transport-as2-ver2p0", "https://ap.example.com/as2"),
+            ("peppol-transport-as4-v2_0", "https://backup-ap.example.com/as4"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn transport_preference_picks_the_best_endpoint() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/.*Invoice-2")), SERVICE_METADATA_TWO_ENDPOINTS).await;
+    let config = LookupConfig {
+        transport_preference: vec!["peppol-transport-as4-v2_0".to_string(), "busdox-transport-as2-ver2p0".to_string()],
+        ..config()
+    };
+
+    let endpoint = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config)
+        .await
+        .unwrap();
+    assert_eq!(endpoint.url, "https://backup-ap.example.com/as4");
+
+    let endpoints = get_endpoints_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config)
+        .await
+        .unwrap();
+    let profiles: Vec<_> = endpoints.iter().map(|endpoint| endpoint.transport_profile.as_str()).collect();
+    assert_eq!(profiles, ["peppol-transport-as4-v2_0", "busdox-transport-as2-ver2p0"]);
+
+    // Unlisted profiles keep their place after the listed ones
+    let config = LookupConfig { transport_preference: vec!["busdox-transport-as2-ver2p0".to_string()], ..config };
+    let endpoint = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config)
+        .await
+        .unwrap();
+    assert_eq!(endpoint.url, "https://ap.example.com/as2");
+}
+
+#[tokio::test]
+async fn document_types_follow_service_references() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP).await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/")), SERVICE_METADATA).await;
+
+    let document_types = document_types_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(document_types.len(), 2);
+    assert_eq!(document_types[0].processes[0].value, "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0");
+    assert_eq!(document_types[0].endpoints[0].url, "https://ap.example.com/as4");
+}
+
+#[tokio::test]
+async fn relative_and_scheme_less_references_resolve_against_the_smp() {
+    let server = MockServer::start().await;
+    // The fixture's scheme-less reference names the SMP host without `http://`
+    let body = SERVICE_GROUP_RELATIVE.replace("smp.example.com", &server.address().to_string());
+    Mock::given(method("GET"))
+        .and(path(SERVICE_GROUP_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/iso6523-actorid-upis.*/services/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SERVICE_METADATA))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let document_types = document_types_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(document_types.len(), 3);
+}
+
+#[tokio::test]
+async fn strict_mode_accepts_the_fixtures() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP).await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("/services/")), SERVICE_METADATA).await;
+    let strict = LookupConfig { strict: true, ..config() };
+
+    assert_eq!(document_types_with_config_async(&server.uri(), &participant(), &strict).await.unwrap().len(), 2);
+    get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &strict)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_unknown_elements() {
+    let server = MockServer::start().await;
+    let fixture = SERVICE_GROUP.replace("</smp:ServiceMetadataReferenceCollection>", "</smp:ServiceMetadataReferenceCollection>\n  <smp:Comment>test</smp:Comment>");
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), &fixture).await;
+
+    let lenient = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(lenient.len(), 2);
+
+    let strict = LookupConfig { strict: true, ..config() };
+    match smp_lookup_with_config_async(&server.uri(), &participant(), &strict).await {
+        Err(PeppolError::Xml(message)) => assert_eq!(message, "unexpected element Comment in ServiceGroup"),
+        other => panic!("expected an XML error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn redirect_is_reported_and_followed() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("^/iso6523-actorid-upis.*/services/")), REDIRECT).await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("^/redirected/")), SERVICE_METADATA).await;
+
+    let redirect = get_redirect_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(redirect.href.starts_with(&format!("{}/redirected/", server.uri())));
+    assert_eq!(redirect.certificate_uid.as_deref(), Some("CN=SMP Example,O=Example,C=NO"));
+
+    let endpoint = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config())
+        .await
+        .unwrap();
+    assert_eq!(endpoint.url, "https://ap.example.com/as4");
+}
+
+#[tokio::test]
+async fn business_card_is_read_from_its_endpoint() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path("/businesscard/iso6523-actorid-upis::0192:921605900")), BUSINESS_CARD).await;
+
+    let card = business_card_with_config_async(&server.uri(), &participant(), &config()).await.unwrap().unwrap();
+    assert_eq!(card.name.as_deref(), Some("Snapbooks AS"));
+    assert_eq!(card.names, ["Snapbooks AS", "Snapbooks Ltd"]);
+    assert_eq!(card.country_code.as_deref(), Some("NO"));
+    assert_eq!(card.registration_date, Some(datetime!(2018-11-05 00:00 UTC)));
+
+    let unknown: Participant = "0192:000000000".parse().unwrap();
+    assert_eq!(business_card_with_config_async(&server.uri(), &unknown, &config()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn missing_service_group_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let result = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await;
+    assert!(matches!(result, Err(PeppolError::NoServiceMetadata(_))));
+    assert!(!result.unwrap_err().is_retryable());
+}
+
+#[tokio::test]
+async fn error_statuses_are_not_parsed() {
+    for (status, retryable) in [(403, false), (500, true)] {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(SERVICE_GROUP))
+            .mount(&server)
+            .await;
+
+        match smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await {
+            Err(PeppolError::Http(err)) => {
+                assert_eq!(err.status().map(|status| status.as_u16()), Some(status));
+                assert_eq!(PeppolError::Http(err).is_retryable(), retryable);
+            }
+            other => panic!("expected an HTTP error for {}, got {:?}", status, other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn requests_identify_themselves() {
+    let server = MockServer::start().await;
+    let mock = Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH));
+    serve(&server, mock.and(header("user-agent", DEFAULT_USER_AGENT)), SERVICE_GROUP).await;
+    assert!(DEFAULT_USER_AGENT.starts_with("peppol-lookup/"));
+    smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+
+    let server = MockServer::start().await;
+    let mock = Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH));
+    serve(&server, mock.and(header("user-agent", "my-app/1.0")), SERVICE_GROUP).await;
+    let config = LookupConfig { user_agent: "my-app/1.0".to_string(), ..config() };
+    smp_lookup_with_config_async(&server.uri(), &participant(), &config).await.unwrap();
+}
+
+#[tokio::test]
+async fn redirect_to_loopback_is_blocked() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path_regex("^/iso6523-actorid-upis.*/services/")), REDIRECT).await;
+
+    let config = LookupConfig { block_private_addresses: true, ..config() };
+    let result = get_endpoint_with_config_async(&server.uri(), &participant(), BIS_BILLING_INVOICE, &config).await;
+    assert!(matches!(result, Err(PeppolError::BlockedAddress(_))));
+}
+
+#[tokio::test]
+async fn service_references_to_private_addresses_are_blocked() {
+    let config = LookupConfig { block_private_addresses: true, ..config() };
+    for target in ["http://127.0.0.1:1", "http://169.254.169.254"] {
+        let server = MockServer::start().await;
+        let body = SERVICE_GROUP.replace(FIXTURE_HOST, target);
+        Mock::given(method("GET"))
+            .and(path(SERVICE_GROUP_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .mount(&server)
+            .await;
+
+        let result = document_types_with_config_async(&server.uri(), &participant(), &config).await;
+        assert!(matches!(result, Err(PeppolError::BlockedAddress(_))), "{}: {:?}", target, result);
+        #[cfg(feature = "blocking")]
+        {
+            let (uri, config) = (server.uri(), config.clone());
+            let result = tokio::task::spawn_blocking(move || peppol_lookup::document_types_with_config(&uri, &participant(), &config))
+                .await
+                .unwrap();
+            assert!(matches!(result, Err(PeppolError::BlockedAddress(_))), "{}: {:?}", target, result);
+        }
+    }
+}
+
+/// A server answering every request with a redirect to `target`
+async fn redirecting_server(target: &str) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(302).insert_header("Location", target))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn http_redirects_to_private_addresses_are_blocked() {
+    let config = LookupConfig { block_private_addresses: true, ..config() };
+    for target in ["http://169.254.169.254/latest/meta-data/", "http://100.64.0.1/", "http://127.0.0.1:1/"] {
+        let server = redirecting_server(target).await;
+        let result = smp_lookup_with_config_async(&server.uri(), &participant(), &config).await;
+        assert!(matches!(result, Err(PeppolError::BlockedAddress(_))), "{}: {:?}", target, result);
+    }
+}
+
+#[cfg(feature = "blocking")]
+#[tokio::test]
+async fn http_redirects_to_private_addresses_are_blocked_in_blocking_lookups() {
+    let server = redirecting_server("http://169.254.169.254/latest/meta-data/").await;
+    let uri = server.uri();
+    let config = LookupConfig { block_private_addresses: true, ..config() };
+    let result = tokio::task::spawn_blocking(move || peppol_lookup::smp_lookup_with_config(&uri, &participant(), &config))
+        .await
+        .unwrap();
+    assert!(matches!(result, Err(PeppolError::BlockedAddress(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn http_redirects_are_followed() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(SERVICE_GROUP_PATH))
+        .respond_with(ResponseTemplate::new(301).insert_header("Location", "/moved"))
+        .mount(&server)
+        .await;
+    serve(&server, Mock::given(method("GET")).and(path("/moved")), SERVICE_GROUP).await;
+
+    let document_types = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(document_types.len(), 2);
+}
+
+#[tokio::test]
+async fn dropping_the_future_abandons_a_slow_smp() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SERVICE_GROUP).set_delay(Duration::from_secs(30)))
+        .mount(&server)
+        .await;
+
+    let (url, participant, config) = (server.uri(), participant(), config());
+    let started = Instant::now();
+    let lookup = smp_lookup_with_config_async(&url, &participant, &config);
+    assert!(tokio::time::timeout(Duration::from_millis(100), lookup).await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn document_types_set_contains_exact_identifiers() {
+    let server = MockServer::start().await;
+    serve(&server, Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)), SERVICE_GROUP).await;
+
+    let document_types = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    let set = document_types_set_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(set.len(), document_types.len());
+    assert!(set.contains(&document_types[0].to_string().parse().unwrap()));
+    assert!(!set.contains(&BIS_BILLING_INVOICE.parse().unwrap()));
+}
+
+#[tokio::test]
+async fn byte_order_mark_is_skipped() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(SERVICE_GROUP_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(SERVICE_GROUP_BOM))
+        .mount(&server)
+        .await;
+
+    let document_types = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(document_types.len(), 2);
+}
+
+#[tokio::test]
+async fn declared_latin1_is_decoded() {
+    let server = MockServer::start().await;
+    let latin1: Vec<u8> = SERVICE_GROUP
+        .replace(r#"encoding="UTF-8""#, r#"encoding="ISO-8859-1""#)
+        .replace("Snapbooks AS", "Bjørnsen AS")
+        .chars()
+        .map(|c| u8::try_from(c).unwrap())
+        .collect();
+    Mock::given(method("GET"))
+        .and(path(SERVICE_GROUP_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(latin1))
+        .mount(&server)
+        .await;
+
+    let service_group = service_group_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    let business_card = service_group.extensions[0].business_card.as_ref().unwrap();
+    assert_eq!(business_card.name.as_deref(), Some("Bjørnsen AS"));
+}
+
+#[tokio::test]
+async fn undecodable_references_are_skipped_with_a_warning() {
+    let server = MockServer::start().await;
+    // %FF decodes to a byte that isn't UTF-8
+    let bad_href = "http://smp.example.com/iso6523-actorid-upis%3A%3A0192%3A921605900/services/busdox-docid-qns%3A%3A%FF";
+    let body = SERVICE_GROUP.replace(
+        "</smp:ServiceMetadataReferenceCollection>",
+        &format!(r#"<smp:ServiceMetadataReference href="{}"/></smp:ServiceMetadataReferenceCollection>"#, bad_href),
+    );
+    Mock::given(method("GET"))
+        .and(path(SERVICE_GROUP_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .mount(&server)
+        .await;
+
+    let service_group = service_group_with_config_async(&server.uri(), &participant(), &config()).await.unwrap();
+    assert_eq!(service_group.document_types.len(), 2);
+    assert!(matches!(
+        &service_group.warnings[..],
+        [LookupWarning::InvalidServiceReference { href, .. }] if href == bad_href
+    ));
+    assert_eq!(smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn list_participants_follows_next_links() {
+    let server = MockServer::start().await;
+    let first_page = PARTICIPANT_LIST.replace(FIXTURE_HOST, &server.uri());
+    Mock::given(method("GET"))
+        .and(path("/list/iso6523-actorid-upis"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", "</list/iso6523-actorid-upis/page/2>; rel=\"next\"")
+                .set_body_string(first_page),
+        )
+        .mount(&server)
+        .await;
+    let second_page = format!(
+        r#"<ServiceGroupReferenceList><ServiceGroupReference href="{}/iso6523-actorid-upis::0208:0123456749"/></ServiceGroupReferenceList>"#,
+        server.uri()
+    );
+    Mock::given(method("GET"))
+        .and(path("/list/iso6523-actorid-upis/page/2"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(second_page))
+        .mount(&server)
+        .await;
+
+    let participants = list_participants_with_config_async(&server.uri(), "iso6523-actorid-upis", &config()).await.unwrap();
+    let ids: Vec<_> = participants.iter().map(|participant| participant.to_string()).collect();
+    assert_eq!(ids, ["0192:921605900", "0088:5790000435975", "0208:0123456749"]);
+
+    // Pages on the SMP's own host aren't checked again
+    let config = LookupConfig { block_private_addresses: true, ..config() };
+    assert_eq!(list_participants_with_config_async(&server.uri(), "iso6523-actorid-upis", &config).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn list_participants_refuses_next_links_to_private_addresses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/list/iso6523-actorid-upis"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Link", "<http://169.254.169.254/latest/meta-data/>; rel=\"next\"")
+                .set_body_string(PARTICIPANT_LIST.replace(FIXTURE_HOST, &server.uri())),
+        )
+        .mount(&server)
+        .await;
+
+    let config = LookupConfig { block_private_addresses: true, ..config() };
+    let result = list_participants_with_config_async(&server.uri(), "iso6523-actorid-upis", &config).await;
+    assert!(matches!(result, Err(PeppolError::BlockedAddress(_))), "{:?}", result);
+    #[cfg(feature = "blocking")]
+    {
+        let uri = server.uri();
+        let result = tokio::task::spawn_blocking(move || peppol_lookup::list_participants_with_config(&uri, "iso6523-actorid-upis", &config))
+            .await
+            .unwrap();
+        assert!(matches!(result, Err(PeppolError::BlockedAddress(_))), "{:?}", result);
+    }
+}
+
+
+/// The cache lifetime of a service group served with `headers`
+async fn max_age_with_headers(headers: &[(&str, &str)]) -> Option<Duration> {
+    let server = MockServer::start().await;
+    let body = SERVICE_GROUP.replace(FIXTURE_HOST, &server.uri());
+    let response = headers
+        .iter()
+        .fold(ResponseTemplate::new(200).set_body_string(body), |response, (name, value)| response.insert_header(*name, *value));
+    Mock::given(method("GET")).and(path(SERVICE_GROUP_PATH)).respond_with(response).mount(&server).await;
+    service_group_with_config_async(&server.uri(), &participant(), &config()).await.unwrap().max_age
+}
+
+#[tokio::test]
+async fn service_group_reports_its_cache_lifetime() {
+    assert_eq!(max_age_with_headers(&[]).await, None);
+    assert_eq!(max_age_with_headers(&[("Cache-Control", "public, max-age=600")]).await, Some(Duration::from_secs(600)));
+    assert_eq!(max_age_with_headers(&[("Cache-Control", "no-store")]).await, Some(Duration::ZERO));
+    assert_eq!(
+        max_age_with_headers(&[("Date", "Wed, 14 Oct 2026 08:00:00 GMT"), ("Expires", "Wed, 14 Oct 2026 09:00:00 GMT")]).await,
+        Some(Duration::from_secs(3600))
+    );
+    // Cache-Control wins over Expires, and an unparseable Expires is already stale
+    assert_eq!(
+        max_age_with_headers(&[("Cache-Control", "max-age=60"), ("Expires", "Wed, 14 Oct 2026 09:00:00 GMT")]).await,
+        Some(Duration::from_secs(60))
+    );
+    assert_eq!(max_age_with_headers(&[("Expires", "0")]).await, Some(Duration::ZERO));
+}
+
+#[tokio::test]
+async fn network_failures_are_retryable_and_bad_answers_are_not() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SERVICE_GROUP).set_delay(Duration::from_secs(5)))
+        .mount(&server)
+        .await;
+    let impatient = LookupConfig { request_timeout: Duration::from_millis(100), ..config() };
+    let timeout = smp_lookup_with_config_async(&server.uri(), &participant(), &impatient).await.unwrap_err();
+    assert!(matches!(&timeout, PeppolError::Http(err) if err.is_timeout()));
+    assert!(timeout.is_retryable());
+    // The lookup's own retries agree
+    let retrying = LookupConfig {
+        retry: RetryPolicy { max_attempts: 2, initial_backoff: Duration::ZERO, max_backoff: Duration::ZERO },
+        ..impatient
+    };
+    smp_lookup_with_config_async(&server.uri(), &participant(), &retrying).await.unwrap_err();
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+    // Nothing listens on the discard port
+    let refused = smp_lookup_with_config_async("http://127.0.0.1:9", &participant(), &config()).await.unwrap_err();
+    assert!(refused.is_retryable(), "{:?}", refused);
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>Maintenance</html>"))
+        .mount(&server)
+        .await;
+    let unparseable = smp_lookup_with_config_async(&server.uri(), &participant(), &config()).await.unwrap_err();
+    assert!(matches!(unparseable, PeppolError::Xml(_)));
+    assert!(!unparseable.is_retryable());
+    assert!(!PeppolError::NotAParticipant("0192:921605900".to_string()).is_retryable());
+    assert!(PeppolError::Dns("SERVFAIL".to_string()).is_retryable());
+}
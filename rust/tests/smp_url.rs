@@ -0,0 +1,131 @@
+//! Percent-encoding of identifiers in SMP request URLs.
+
+#![cfg(feature = "blocking")]
+
+use std::sync::{Arc, Mutex};
+
+use peppol_lookup::{LookupClient, Participant, PeppolError, Resolver, SmlEnvironment, SmlRecord, SmpClient, SmpScheme};
+
+const SERVICE_GROUP: &str = include_str!("fixtures/service_group.xml");
+const SERVICE_METADATA: &str = include_str!("fixtures/service_metadata.xml");
+
+/// Resolves every participant to `smp.example.com`
+struct AnyResolver;
+
+impl Resolver for AnyResolver {
+    fn resolve(&self, alias: &str) -> Result<Option<SmlRecord>, PeppolError> {
+        Ok(Some(SmlRecord {
+            alias: alias.to_string(),
+            hostname: "smp.example.com".to_string(),
+            ttl: None,
+            addresses: Vec::new(),
+        }))
+    }
+}
+
+/// Records the requested URLs and their fallbacks, and serves fixture XML
+#[derive(Clone, Default)]
+struct RecordingSmp {
+    urls: Arc<Mutex<Vec<String>>>,
+    fallbacks: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl SmpClient for RecordingSmp {
+    fn get(&self, url: &str, fallback: Option<&str>) -> Result<String, PeppolError> {
+        self.urls.lock().unwrap().push(url.to_string());
+        self.fallbacks.lock().unwrap().push(fallback.map(str::to_string));
+        if url.contains("/services/") {
+            Ok(SERVICE_METADATA.to_string())
+        } else {
+            Ok(SERVICE_GROUP.to_string())
+        }
+    }
+}
+
+fn client(smp: &RecordingSmp) -> LookupClient {
+    LookupClient::builder()
+        .resolver(AnyResolver)
+        .smp_client(smp.clone())
+        .smp_scheme(SmpScheme::Https)
+        .verify_signature(false)
+        .build()
+        .unwrap()
+}
+
+fn requested_url(participant: &str) -> String {
+    let smp = RecordingSmp::default();
+    client(&smp).smp_lookup(&participant.parse().unwrap()).unwrap();
+    let urls = smp.urls.lock().unwrap();
+    urls[0].clone()
+}
+
+#[test]
+fn separators_stay_literal() {
+    assert_eq!(
+        requested_url("0192:921605900"),
+        "https://smp.example.com/iso6523-actorid-upis::0192:921605900"
+    );
+}
+
+#[test]
+fn spaces_and_special_characters_are_encoded() {
+    assert_eq!(
+        requested_url("0088:ab c/d#e&f+g"),
+        "https://smp.example.com/iso6523-actorid-upis::0088:ab%20c%2Fd%23e%26f%2Bg"
+    );
+}
+
+#[test]
+fn colons_in_identifier_stay_literal() {
+    let participant = Participant::with_scheme("other-scheme", "9999", "a:b").unwrap();
+    let smp = RecordingSmp::default();
+    client(&smp).smp_lookup(&participant).unwrap();
+    assert_eq!(smp.urls.lock().unwrap()[0], "https://smp.example.com/other-scheme::9999:a:b");
+}
+
+#[test]
+fn document_identifier_is_encoded_after_scheme() {
+    let smp = RecordingSmp::default();
+    let doc_type = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice##urn:cen.eu:en16931:2017::2.1";
+    client(&smp).get_endpoint(&"0192:921605900".parse().unwrap(), doc_type).unwrap();
+    assert_eq!(
+        smp.urls.lock().unwrap()[0],
+        "https://smp.example.com/iso6523-actorid-upis::0192:921605900/services/busdox-docid-qns::\
+         urn:oasis:names:specification:ubl:schema:xsd:Invoice-2::Invoice%23%23urn:cen.eu:en16931:2017::2.1"
+    );
+}
+
+#[test]
+fn default_scheme_depends_on_environment() {
+    for (environment, prefix) in [(SmlEnvironment::Production, "https://"), (SmlEnvironment::Test, "http://")] {
+        let smp = RecordingSmp::default();
+        let client = LookupClient::builder()
+            .environment(environment)
+            .resolver(AnyResolver)
+            .smp_client(smp.clone())
+            .build()
+            .unwrap();
+        client.smp_lookup(&"0192:921605900".parse().unwrap()).unwrap();
+        assert!(smp.urls.lock().unwrap()[0].starts_with(prefix));
+        assert_eq!(smp.fallbacks.lock().unwrap()[0], None);
+    }
+}
+
+#[test]
+fn http_fallback_is_opt_in() {
+    assert_eq!(SmpScheme::default(), SmpScheme::Https);
+    let smp = RecordingSmp::default();
+    let client = LookupClient::builder()
+        .resolver(AnyResolver)
+        .smp_client(smp.clone())
+        .smp_scheme(SmpScheme::HttpsWithHttpFallback)
+        .verify_signature(false)
+        .build()
+        .unwrap();
+    client.smp_lookup(&"0192:921605900".parse().unwrap()).unwrap();
+    assert_eq!(smp.urls.lock().unwrap()[0], "https://smp.example.com/iso6523-actorid-upis::0192:921605900");
+    assert_eq!(
+        smp.fallbacks.lock().unwrap()[0].as_deref(),
+        Some("http://smp.example.com/iso6523-actorid-upis::0192:921605900")
+    );
+}
@@ -0,0 +1,68 @@
+//! SMP requests over HTTPS to a local server with a certificate from a private CA.
+
+#![cfg(all(feature = "blocking", any(feature = "native-tls", feature = "rustls")))]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use native_tls::{Identity, TlsAcceptor};
+use peppol_lookup::{smp_lookup_with_config, Certificate, LookupConfig, Participant, PeppolError, RetryPolicy, SmpScheme};
+
+const SERVICE_GROUP: &str = include_str!("fixtures/service_group.xml");
+/// The CA that issued `TLS_SERVER_CERTIFICATE`, both valid until 2036
+const TLS_CA: &str = include_str!("fixtures/tls_ca.pem");
+/// Certificate for `localhost` and `127.0.0.1`
+const TLS_SERVER_CERTIFICATE: &str = include_str!("fixtures/tls_server.pem");
+const TLS_SERVER_KEY: &str = include_str!("fixtures/tls_server.key");
+
+/// Start an HTTPS server that answers every request with the service group,
+/// and return its base URL
+fn start_server() -> String {
+    let identity = Identity::from_pkcs8(TLS_SERVER_CERTIFICATE.as_bytes(), TLS_SERVER_KEY.as_bytes()).unwrap();
+    let acceptor = TlsAcceptor::new(identity).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            // Handshakes from clients that don't trust the CA fail here
+            let Ok(mut stream) = acceptor.accept(stream) else { continue };
+            let mut reader = BufReader::new(&mut stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                SERVICE_GROUP.len(),
+                SERVICE_GROUP
+            );
+        }
+    });
+    format!("https://localhost:{}", port)
+}
+
+fn config() -> LookupConfig {
+    LookupConfig {
+        verify_signature: false,
+        retry: RetryPolicy::none(),
+        smp_scheme: SmpScheme::Https,
+        // The server listens on 127.0.0.1
+        block_private_addresses: false,
+        ..LookupConfig::default()
+    }
+}
+
+#[test]
+fn root_certificates_are_trusted_for_tls() {
+    let smp = start_server();
+    let participant: Participant = "0192:921605900".parse().unwrap();
+
+    let result = smp_lookup_with_config(&smp, &participant, &config());
+    assert!(matches!(result, Err(PeppolError::Http(_))), "{:?}", result);
+
+    let trusting = LookupConfig { root_certificates: vec![Certificate::from_pem(TLS_CA).unwrap()], ..config() };
+    assert_eq!(smp_lookup_with_config(&smp, &participant, &trusting).unwrap().len(), 2);
+}